@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use image_compressor::crawler::get_file_list;
+
+/// Extensions of the files left out of the archives, independent of the files that are compressed,
+/// and whether symbolic links are stored as links.
+///
+/// Extensions are written separated by commas, e.g. `raw, dng, *.xmp`, and compared without case.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArchiveFilter {
+    extensions: HashSet<String>,
+    keep_symlinks: bool,
+}
+
+impl ArchiveFilter {
+    /// Parse the extensions.
+    pub fn parse(extensions: &str) -> Self {
+        let extensions = extensions.split(',')
+            .map(|e| e.trim().trim_start_matches("*.").trim_start_matches('.').to_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect();
+        ArchiveFilter { extensions, keep_symlinks: false }
+    }
+
+    /// Whether the file is left out of the archives.
+    pub fn is_excluded<P: AsRef<Path>>(&self, file_path: P) -> bool {
+        match file_path.as_ref().extension() {
+            Some(e) => self.extensions.contains(&e.to_string_lossy().to_lowercase()),
+            None => false,
+        }
+    }
+
+    /// Set whether to store symbolic links as links, instead of the files and directories they point to.
+    pub fn set_keep_symlinks(&mut self, to_keep: bool) {
+        self.keep_symlinks = to_keep;
+    }
+
+    /// Whether symbolic links are stored as links.
+    pub fn is_symlink_kept(&self) -> bool {
+        self.keep_symlinks
+    }
+
+    /// Whether no file is left out.
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty()
+    }
+
+    /// Whether the archives have every file with the links followed, as `zip_archive` makes them.
+    pub fn is_default(&self) -> bool {
+        self.is_empty() && !self.keep_symlinks
+    }
+
+    /// Get the arguments that make 7z leave the files out, in every subdirectory, and store the links.
+    pub fn get_7z_args(&self) -> Vec<OsString> {
+        let mut extensions = self.extensions.iter().collect::<Vec<_>>();
+        extensions.sort();
+        let mut args = extensions.into_iter()
+            .map(|e| OsString::from(format!("-xr!*.{}", e)))
+            .collect::<Vec<_>>();
+        if self.keep_symlinks {
+            args.push(OsString::from("-snl"));
+        }
+        args
+    }
+
+    /// Get the files of the directory that go into its archive.
+    /// When links are kept, the links are in the list instead of the files in the linked directories.
+    pub fn get_file_list<D: AsRef<Path>>(&self, dir: D) -> io::Result<Vec<PathBuf>> {
+        let mut file_list = match self.keep_symlinks {
+            true => {
+                let mut file_list = Vec::new();
+                read_files_and_links(dir.as_ref(), &mut file_list)?;
+                file_list
+            }
+            false => get_file_list(dir)?,
+        };
+        file_list.retain(|f| !self.is_excluded(f));
+        Ok(file_list)
+    }
+}
+
+/// Read the files and links in the directory recursively without following the links.
+/// Hidden files are skipped, like `get_file_list` does.
+fn read_files_and_links(dir: &Path, file_list: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            read_files_and_links(&path, file_list)?;
+        } else if !entry.file_name().to_string_lossy().starts_with('.') {
+            file_list.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_filter_test(){
+        let filter = ArchiveFilter::parse("raw, *.DNG, .xmp,");
+        assert!(filter.is_excluded("album/a.RAW"));
+        assert!(filter.is_excluded("album/a.dng"));
+        assert!(filter.is_excluded("a.xmp"));
+        assert!(!filter.is_excluded("album/a.jpg"));
+        assert!(!filter.is_excluded("album/raw"));
+        assert!(ArchiveFilter::parse(" , ").is_empty());
+        assert_eq!(ArchiveFilter::parse("xmp, raw").get_7z_args(), ["-xr!*.raw", "-xr!*.xmp"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_symlinks_test(){
+        let test_dir = PathBuf::from("test_keep_symlinks");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let dir = test_dir.join("album");
+        fs::create_dir_all(test_dir.join("originals")).unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(test_dir.join("originals").join("a.jpg"), "image").unwrap();
+        std::os::unix::fs::symlink("../originals", dir.join("linked")).unwrap();
+
+        let mut filter = ArchiveFilter::default();
+        assert_eq!(filter.get_file_list(&dir).unwrap(), vec![dir.join("linked").join("a.jpg")]);
+        filter.set_keep_symlinks(true);
+        assert!(!filter.is_default());
+        assert_eq!(filter.get_file_list(&dir).unwrap(), vec![dir.join("linked")]);
+        assert_eq!(filter.get_7z_args(), ["-snl"]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use image_compressor::crawler::get_file_list;
+use zip_archive::Format;
+use crate::pipeline::ArchiveOutput;
+use crate::preflight::get_total_size;
+
+/// Interval between reading the sizes of the archives being written.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Percent of progress between two reports of the same archive.
+pub const PROGRESS_STEP: u32 = 10;
+
+/// Progress of an archive estimated from the bytes written to its file.
+///
+/// The archivers report nothing until an archive is done, but they write it in place,
+/// so the written size is compared with the total size of the directory.
+/// Compressed images barely shrink in an archive, so the estimate is close for them,
+/// and it stays at most 99 percent until the archiver finishes.
+pub struct ArchiveProgress {
+    name: String,
+    file_list: Vec<PathBuf>,
+    total_size: u64,
+    reported_percent: u32,
+}
+
+impl ArchiveProgress {
+    /// Watch the archive file of the directory.
+    /// For `Xz`, the `.tar` file written before the `.tar.xz` file is also watched.
+    pub fn new<D: AsRef<Path>, A: AsRef<Path>>(dir: D, archive_path: A, output: &ArchiveOutput) -> Self {
+        let archive_path = archive_path.as_ref();
+        let mut file_list = vec![archive_path.to_path_buf()];
+        if let ArchiveOutput::Archive(Format::Xz) = output {
+            file_list.push(archive_path.with_extension(""));
+        }
+        ArchiveProgress {
+            name: archive_path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            file_list,
+            total_size: get_total_size(&get_file_list(dir).unwrap_or_default()),
+            reported_percent: 0,
+        }
+    }
+
+    /// Get the file name of the archive.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the written size, and return the percent if it is at least [`PROGRESS_STEP`] above the last returned one.
+    pub fn poll(&mut self) -> Option<u32> {
+        if self.total_size == 0 {
+            return None;
+        }
+        let written_size = self.file_list.iter()
+            .filter_map(|f| f.metadata().ok())
+            .map(|m| m.len())
+            .max()
+            .unwrap_or(0);
+        let percent = (written_size as u128 * 100 / self.total_size as u128).min(99) as u32;
+        if percent < self.reported_percent + PROGRESS_STEP {
+            return None;
+        }
+        self.reported_percent = percent;
+        Some(percent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn archive_progress_test(){
+        let test_dir = PathBuf::from("test_archive_progress");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let dir = test_dir.join("dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.jpg"), [0u8; 1000]).unwrap();
+        let archive_path = test_dir.join("dir.tar.xz");
+
+        let mut progress = ArchiveProgress::new(&dir, &archive_path, &ArchiveOutput::Archive(Format::Xz));
+        assert_eq!(progress.name(), "dir.tar.xz");
+        assert_eq!(progress.poll(), None);
+        fs::write(test_dir.join("dir.tar"), [0u8; 350]).unwrap();
+        assert_eq!(progress.poll(), Some(35));
+        fs::write(test_dir.join("dir.tar"), [0u8; 400]).unwrap();
+        assert_eq!(progress.poll(), None);
+        fs::write(&archive_path, [0u8; 1200]).unwrap();
+        assert_eq!(progress.poll(), Some(99));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// Where the files of an archived directory are placed inside its archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ArchiveRoot {
+    /// Under the name of the directory, like `album/a.jpg`, as `zip_archive` does.
+    #[default]
+    Dir,
+    /// At the root of the archive, like `a.jpg`.
+    Contents,
+    /// Under the path of the directory relative to the destination of the job, like `2021/album/a.jpg`.
+    JobRoot,
+    /// Under a fixed prefix, like `photos/a.jpg`.
+    Prefix,
+}
+
+impl ArchiveRoot {
+    /// Create an [`ArchiveRoot`] from the str. Unknown strings fall back to the default.
+    pub fn from(root_str: &str) -> Self {
+        match root_str {
+            "contents" => ArchiveRoot::Contents,
+            "job" => ArchiveRoot::JobRoot,
+            "prefix" => ArchiveRoot::Prefix,
+            _ => ArchiveRoot::default(),
+        }
+    }
+
+    /// Get the path in the archive that the files of the directory are placed under.
+    ///
+    /// Only the normal components of the prefix are kept, so the files cannot be extracted outside the extraction directory.
+    pub fn get_path<D: AsRef<Path>, J: AsRef<Path>, P: AsRef<Path>>(&self, dir: D, job_root: J, prefix: P) -> PathBuf {
+        let dir = dir.as_ref();
+        let path = match self {
+            ArchiveRoot::Dir => PathBuf::from(dir.file_name().unwrap_or_default()),
+            ArchiveRoot::Contents => PathBuf::new(),
+            ArchiveRoot::JobRoot => dir.strip_prefix(job_root).map(Path::to_path_buf)
+                .unwrap_or_else(|_| PathBuf::from(dir.file_name().unwrap_or_default())),
+            ArchiveRoot::Prefix => prefix.as_ref().to_path_buf(),
+        };
+        path.components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect()
+    }
+}
+
+impl fmt::Display for ArchiveRoot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveRoot::Dir => write!(f, "dir"),
+            ArchiveRoot::Contents => write!(f, "contents"),
+            ArchiveRoot::JobRoot => write!(f, "job"),
+            ArchiveRoot::Prefix => write!(f, "prefix"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_root_test(){
+        let dest = Path::new("dest");
+        let dir = dest.join("2021").join("album");
+        assert_eq!(ArchiveRoot::Dir.get_path(&dir, dest, ""), PathBuf::from("album"));
+        assert_eq!(ArchiveRoot::Contents.get_path(&dir, dest, ""), PathBuf::new());
+        assert_eq!(ArchiveRoot::JobRoot.get_path(&dir, dest, ""), Path::new("2021").join("album"));
+        assert_eq!(ArchiveRoot::JobRoot.get_path(dest, dest, ""), PathBuf::new());
+        assert_eq!(ArchiveRoot::Prefix.get_path(&dir, dest, "/photos/../2021/"), Path::new("photos").join("2021"));
+
+        for root in [ArchiveRoot::Dir, ArchiveRoot::Contents, ArchiveRoot::JobRoot, ArchiveRoot::Prefix] {
+            assert_eq!(ArchiveRoot::from(&root.to_string()), root);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use serde_json::{from_reader, to_writer_pretty};
+use sha2::{Digest, Sha256};
+use crate::archive_filter::ArchiveFilter;
+use crate::verify::hash_files;
+
+/// Name of the state file in the archive directory.
+pub const ARCHIVE_STATE_FILE_NAME: &str = ".archive_state.json";
+
+/// Archives that are completed in the archive directory, so an interrupted run can skip them.
+///
+/// The archives are stored relative to the archive directory with the fingerprint of their directory, if it was read,
+/// and an archive counts as completed only while its file exists and is not empty.
+pub struct ArchiveState {
+    archive_dir: PathBuf,
+    archived: BTreeMap<PathBuf, Option<String>>,
+}
+
+/// State file, which was a list of archives before the fingerprints were recorded.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StateFile {
+    Fingerprints(BTreeMap<PathBuf, Option<String>>),
+    Archives(BTreeSet<PathBuf>),
+}
+
+impl ArchiveState {
+    /// Load the state of the archive directory. A missing or broken state file is an empty state.
+    pub fn load<A: AsRef<Path>>(archive_dir: A) -> Self {
+        let archived = match File::open(archive_dir.as_ref().join(ARCHIVE_STATE_FILE_NAME)).ok().and_then(|f| from_reader(BufReader::new(f)).ok()) {
+            Some(StateFile::Fingerprints(f)) => f,
+            Some(StateFile::Archives(a)) => a.into_iter().map(|p| (p, None)).collect(),
+            None => BTreeMap::new(),
+        };
+        ArchiveState {
+            archive_dir: archive_dir.as_ref().to_path_buf(),
+            archived,
+        }
+    }
+
+    /// Check whether the archive is completed in an earlier run.
+    pub fn is_archived<P: AsRef<Path>>(&self, archive_path: P) -> bool {
+        let is_recorded = match archive_path.as_ref().strip_prefix(&self.archive_dir) {
+            Ok(p) => self.archived.contains_key(p),
+            Err(_) => false,
+        };
+        is_recorded && archive_path.as_ref().metadata().map(|m| m.len() > 0).unwrap_or(false)
+    }
+
+    /// Get the fingerprint of the directory recorded with the archive.
+    pub fn get_fingerprint<P: AsRef<Path>>(&self, archive_path: P) -> Option<&str> {
+        let p = archive_path.as_ref().strip_prefix(&self.archive_dir).ok()?;
+        self.archived.get(p)?.as_deref()
+    }
+
+    /// Record the completed archive with the fingerprint of its directory, and save the state file.
+    pub fn insert<P: AsRef<Path>>(&mut self, archive_path: P, fingerprint: Option<String>) -> Result<(), Box<dyn Error>> {
+        if let Ok(p) = archive_path.as_ref().strip_prefix(&self.archive_dir) {
+            self.archived.insert(p.to_path_buf(), fingerprint);
+        }
+        let state_file = File::create(self.archive_dir.join(ARCHIVE_STATE_FILE_NAME))?;
+        to_writer_pretty(state_file, &self.archived)?;
+        Ok(())
+    }
+}
+
+/// Get the fingerprint of the contents of the directory, which changes when a file is added, removed, renamed or changed.
+///
+/// Every file is read, because the compressed files are written again with new modified times
+/// when their folders are removed after archiving.
+pub fn get_fingerprint<D: AsRef<Path>>(dir: D) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    for (path, hash) in hash_files(dir.as_ref(), &ArchiveFilter::default())? {
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(hash);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn archive_state_test(){
+        let archive_dir = PathBuf::from("test_archive_state");
+        if archive_dir.is_dir() {
+            fs::remove_dir_all(&archive_dir).unwrap();
+        }
+        fs::create_dir_all(archive_dir.join("2021")).unwrap();
+        let done = archive_dir.join("2021").join("01.zip");
+        let partial = archive_dir.join("2021").join("02.zip");
+        fs::write(&done, "archive").unwrap();
+        fs::write(&partial, "").unwrap();
+
+        let mut state = ArchiveState::load(&archive_dir);
+        assert!(!state.is_archived(&done));
+        state.insert(&done, Some(String::from("fingerprint"))).unwrap();
+        state.insert(&partial, None).unwrap();
+
+        let state = ArchiveState::load(&archive_dir);
+        assert!(state.is_archived(&done));
+        assert!(!state.is_archived(&partial));
+        assert_eq!(state.get_fingerprint(&done), Some("fingerprint"));
+        assert_eq!(state.get_fingerprint(&partial), None);
+        fs::remove_file(&done).unwrap();
+        assert!(!state.is_archived(&done));
+        fs::remove_dir_all(&archive_dir).unwrap();
+    }
+
+    #[test]
+    fn old_archive_state_test(){
+        let archive_dir = PathBuf::from("test_old_archive_state");
+        if archive_dir.is_dir() {
+            fs::remove_dir_all(&archive_dir).unwrap();
+        }
+        fs::create_dir_all(&archive_dir).unwrap();
+        fs::write(archive_dir.join("01.zip"), "archive").unwrap();
+        fs::write(archive_dir.join(ARCHIVE_STATE_FILE_NAME), r#"["01.zip"]"#).unwrap();
+
+        let state = ArchiveState::load(&archive_dir);
+        assert!(state.is_archived(archive_dir.join("01.zip")));
+        assert_eq!(state.get_fingerprint(archive_dir.join("01.zip")), None);
+        fs::remove_dir_all(&archive_dir).unwrap();
+    }
+
+    #[test]
+    fn fingerprint_test(){
+        let dir = PathBuf::from("test_fingerprint");
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.jpg"), "first").unwrap();
+        fs::write(dir.join("sub").join("b.jpg"), "second").unwrap();
+
+        let fingerprint = get_fingerprint(&dir).unwrap();
+        assert_eq!(fingerprint.len(), 64);
+        fs::write(dir.join("a.jpg"), "first").unwrap();
+        assert_eq!(get_fingerprint(&dir).unwrap(), fingerprint);
+        fs::write(dir.join("sub").join("b.jpg"), "changed").unwrap();
+        assert_ne!(get_fingerprint(&dir).unwrap(), fingerprint);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
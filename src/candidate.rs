@@ -0,0 +1,59 @@
+/// Default qualities tried for each image.
+pub const DEFAULT_CANDIDATE_QUALITIES: &str = "70, 80, 90";
+
+/// Qualities that each image is compressed with, and the limit of the candidate that is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidates {
+    qualities: Vec<f32>,
+    max_size_percent: u32,
+}
+
+impl Candidates {
+    /// Parse the qualities separated by commas, such as `70, 80, 90`.
+    /// Values that are not numbers from 1 to 100 are ignored, and duplicates are removed.
+    ///
+    /// Returns `None` when no quality is given.
+    pub fn parse(qualities: &str, max_size_percent: u32) -> Option<Self> {
+        let mut quality_list = qualities.split(',')
+            .filter_map(|q| q.trim().parse::<f32>().ok())
+            .filter(|q| *q >= 1. && *q <= 100.)
+            .collect::<Vec<_>>();
+        quality_list.sort_by(|a, b| a.total_cmp(b));
+        quality_list.dedup();
+        if quality_list.is_empty() {
+            return None;
+        }
+        Some(Candidates { qualities: quality_list, max_size_percent: max_size_percent.max(1) })
+    }
+
+    /// Get the qualities from the lowest.
+    pub fn qualities(&self) -> &[f32] {
+        &self.qualities
+    }
+
+    /// Choose the candidate to keep from the output sizes in the order of [`Candidates::qualities`].
+    ///
+    /// The highest quality whose output is at most `max_size_percent` of the source is kept.
+    /// When no candidate is small enough, the smallest one is kept.
+    pub fn choose(&self, source_size: u64, output_sizes: &[u64]) -> Option<usize> {
+        let max_size = source_size as u128 * self.max_size_percent as u128 / 100;
+        output_sizes.iter()
+            .rposition(|s| *s as u128 <= max_size)
+            .or_else(|| output_sizes.iter().enumerate().min_by_key(|(_, s)| **s).map(|(i, _)| i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_test(){
+        let candidates = Candidates::parse("90, 70, x, 80, 0, 70", 50).unwrap();
+        assert_eq!(candidates.qualities(), &[70., 80., 90.]);
+        assert_eq!(candidates.choose(1000, &[300, 500, 700]), Some(1));
+        assert_eq!(candidates.choose(1000, &[600, 550, 700]), Some(1));
+        assert_eq!(candidates.choose(1000, &[]), None);
+        assert_eq!(Candidates::parse(" , 200", 50), None);
+    }
+}
@@ -0,0 +1,221 @@
+use std::path::PathBuf;
+use log::warn;
+use crate::event::Verbosity;
+use crate::job_manager::Priority;
+
+/// Prefix of the environment variables of the options, like `IC_ORIGIN` for `--origin`.
+pub const ENV_PREFIX: &str = "IC_";
+
+pub const USAGE: &str = "Usage: ImageCompressor [OPTIONS]
+
+Options:
+    --origin <DIR>      Folder of the original images
+    --url <URL>         Download the image into the original folder first. Can be repeated
+    --dest <DIR>        Folder to put the compressed images
+    --archive <DIR>     Archive the compressed folders into the folder
+    --format <FORMAT>   Archive format: zip, 7z, xz, tar, zstd or pdf
+    --threads <COUNT>   Number of threads
+    --quality <1-100>   Quality of the compressed images
+    --resize <1-100>    Percentage of the original width and height
+    --priority <LEVEL>  Priority of the job: low, normal or high.
+                        A low priority job runs at a lower OS priority on Linux
+    --verbosity <LEVEL> Messages of the job and the log: silent, errors, normal or debug
+    --no-gui            Run the job without opening the window
+    --resume            Keep a journal of the compressed files, and skip the files done by an interrupted run
+    --portable          Keep the settings, history and reports next to the program.
+                        Also turned on by a portable.flag file next to the program
+    --help              Print this message
+
+Every option can also be set with an environment variable named after it, like IC_ORIGIN, IC_THREADS or IC_NO_GUI=1.
+IC_URL takes URLs separated by spaces. Options on the command line override the environment variables.
+
+Options that are not given are read from the settings saved by the window.";
+
+/// Options given on the command line. Options that are not given are `None`.
+#[derive(Debug, Default, PartialEq)]
+pub struct CliArgs {
+    pub origin: Option<PathBuf>,
+    pub urls: Vec<String>,
+    pub dest: Option<PathBuf>,
+    pub archive: Option<PathBuf>,
+    pub format: Option<String>,
+    pub threads: Option<u32>,
+    pub quality: Option<u32>,
+    pub resize: Option<u32>,
+    pub priority: Option<Priority>,
+    pub verbosity: Option<Verbosity>,
+    pub no_gui: bool,
+    pub resume: bool,
+    pub portable: bool,
+    pub help: bool,
+}
+
+impl CliArgs {
+    /// Parse the arguments, without the program name.
+    ///
+    /// # Error
+    /// - When an option is unknown.
+    /// - When an option has no value or its value is invalid.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut cli_args = CliArgs::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--no-gui" => cli_args.no_gui = true,
+                "--resume" => cli_args.resume = true,
+                "--portable" => cli_args.portable = true,
+                "--help" | "-h" => cli_args.help = true,
+                "--origin" => cli_args.origin = Some(PathBuf::from(get_value(&arg, args.next())?)),
+                "--url" => cli_args.urls.push(get_value(&arg, args.next())?),
+                "--dest" => cli_args.dest = Some(PathBuf::from(get_value(&arg, args.next())?)),
+                "--archive" => cli_args.archive = Some(PathBuf::from(get_value(&arg, args.next())?)),
+                "--format" => {
+                    let format = get_value(&arg, args.next())?;
+                    match format.as_str() {
+                        "zip" | "7z" | "xz" | "tar" | "zstd" | "pdf" => cli_args.format = Some(format),
+                        _ => return Err(format!("Unknown archive format: {}", format)),
+                    }
+                }
+                "--threads" => cli_args.threads = Some(get_number(&arg, args.next(), 1, 256)?),
+                "--quality" => cli_args.quality = Some(get_number(&arg, args.next(), 1, 100)?),
+                "--resize" => cli_args.resize = Some(get_number(&arg, args.next(), 1, 100)?),
+                "--priority" => {
+                    let priority = get_value(&arg, args.next())?;
+                    match priority.as_str() {
+                        "low" | "normal" | "high" => cli_args.priority = Some(Priority::from(&priority)),
+                        _ => return Err(format!("Unknown priority: {}", priority)),
+                    }
+                }
+                "--verbosity" => {
+                    let verbosity = get_value(&arg, args.next())?;
+                    match verbosity.as_str() {
+                        "silent" | "errors" | "normal" | "debug" => cli_args.verbosity = Some(Verbosity::from(&verbosity)),
+                        _ => return Err(format!("Unknown verbosity: {}", verbosity)),
+                    }
+                }
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+        Ok(cli_args)
+    }
+
+    /// Parse the `IC_*` environment variables among the variables, like those from [`std::env::vars`].
+    /// Flags are set by `1`, `true` or `yes`. Other variables, and unknown `IC_*` variables, are ignored.
+    ///
+    /// # Error
+    /// - When the value of a variable is invalid.
+    pub fn parse_env<I: IntoIterator<Item = (String, String)>>(vars: I) -> Result<Self, String> {
+        let mut args = Vec::new();
+        for (name, value) in vars {
+            let option = match name.strip_prefix(ENV_PREFIX) {
+                Some(o) => format!("--{}", o.to_lowercase().replace('_', "-")),
+                None => continue,
+            };
+            match option.as_str() {
+                "--no-gui" | "--resume" | "--portable" | "--help" => {
+                    if matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes") {
+                        args.push(option);
+                    }
+                }
+                "--url" => {
+                    for url in value.split_whitespace() {
+                        args.extend([option.to_string(), url.to_string()]);
+                    }
+                }
+                "--origin" | "--dest" | "--archive" | "--format" | "--threads" | "--quality" | "--resize" | "--priority" | "--verbosity" => args.extend([option, value]),
+                _ => warn!("Unknown environment variable: {}", name),
+            }
+        }
+        CliArgs::parse(args).map_err(|e| format!("{} (from the {}* environment variables)", e, ENV_PREFIX))
+    }
+
+    /// Fill the options that are not given with those of the other arguments, such as the environment variables.
+    pub fn or(self, other: CliArgs) -> Self {
+        CliArgs {
+            origin: self.origin.or(other.origin),
+            urls: if self.urls.is_empty() { other.urls } else { self.urls },
+            dest: self.dest.or(other.dest),
+            archive: self.archive.or(other.archive),
+            format: self.format.or(other.format),
+            threads: self.threads.or(other.threads),
+            quality: self.quality.or(other.quality),
+            resize: self.resize.or(other.resize),
+            priority: self.priority.or(other.priority),
+            verbosity: self.verbosity.or(other.verbosity),
+            no_gui: self.no_gui || other.no_gui,
+            resume: self.resume || other.resume,
+            portable: self.portable || other.portable,
+            help: self.help || other.help,
+        }
+    }
+}
+
+fn get_value(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or(format!("{} needs a value", option))
+}
+
+fn get_number(option: &str, value: Option<String>, min: u32, max: u32) -> Result<u32, String> {
+    match get_value(option, value)?.parse::<u32>() {
+        Ok(n) if (min..=max).contains(&n) => Ok(n),
+        _ => Err(format!("{} needs a number from {} to {}", option, min, max)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs, String> {
+        CliArgs::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn parse_test(){
+        let cli_args = parse(&["--origin", "photos", "--dest", "out", "--threads", "8", "--no-gui", "--portable"]).unwrap();
+        assert_eq!(cli_args, CliArgs {
+            origin: Some(PathBuf::from("photos")),
+            dest: Some(PathBuf::from("out")),
+            threads: Some(8),
+            no_gui: true,
+            portable: true,
+            ..Default::default()
+        });
+        assert_eq!(parse(&["--url", "https://example.com/a.png", "--url", "https://example.com/b.png"]).unwrap().urls,
+                   vec!["https://example.com/a.png", "https://example.com/b.png"]);
+        assert_eq!(parse(&[]).unwrap(), CliArgs::default());
+        assert!(parse(&["--threads"]).is_err());
+        assert!(parse(&["--quality", "0"]).is_err());
+        assert!(parse(&["--format", "rar"]).is_err());
+        assert_eq!(parse(&["--priority", "low"]).unwrap().priority, Some(Priority::Low));
+        assert!(parse(&["--priority", "urgent"]).is_err());
+        assert_eq!(parse(&["--verbosity", "errors"]).unwrap().verbosity, Some(Verbosity::ErrorsOnly));
+        assert!(parse(&["--verbosity", "loud"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+
+    #[test]
+    fn parse_env_test(){
+        let vars = [("IC_ORIGIN", "/photos"), ("IC_THREADS", "4"), ("IC_PRIORITY", "high"), ("IC_VERBOSITY", "silent"), ("IC_NO_GUI", "true"), ("IC_RESUME", "1"), ("IC_URL", "https://a.png https://b.png"), ("HOME", "/root")]
+            .map(|(n, v)| (n.to_string(), v.to_string()));
+        let env_args = CliArgs::parse_env(vars).unwrap();
+        assert_eq!(env_args, CliArgs {
+            origin: Some(PathBuf::from("/photos")),
+            urls: vec![String::from("https://a.png"), String::from("https://b.png")],
+            threads: Some(4),
+            priority: Some(Priority::High),
+            verbosity: Some(Verbosity::Silent),
+            no_gui: true,
+            resume: true,
+            ..Default::default()
+        });
+
+        let cli_args = parse(&["--threads", "8", "--dest", "out"]).unwrap().or(env_args);
+        assert_eq!(cli_args.threads, Some(8));
+        assert_eq!(cli_args.origin, Some(PathBuf::from("/photos")));
+        assert_eq!(cli_args.dest, Some(PathBuf::from("out")));
+        assert!(cli_args.no_gui && cli_args.resume);
+
+        assert!(CliArgs::parse_env([(String::from("IC_QUALITY"), String::from("high"))]).is_err());
+        assert_eq!(CliArgs::parse_env([(String::from("IC_NO_GUI"), String::from("0"))]).unwrap(), CliArgs::default());
+    }
+}
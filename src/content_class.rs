@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use image::imageops::FilterType;
+use image::{ImageResult, RgbImage};
+
+/// Longest side of the thumbnail that images are classified by.
+const ANALYSIS_SIZE: u32 = 256;
+
+/// Kind of content of an image, which decides how much quality it needs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentClass {
+    /// Photographs, whose noise hides the loss of a lower quality.
+    Photo,
+
+    /// Screenshots, drawings and charts with flat colors, whose sharp edges show artifacts.
+    Graphic,
+
+    /// Scanned documents and pictures of text, which must stay legible.
+    Text,
+}
+
+impl ContentClass {
+    /// Get the quality to compress the image of the class with, from the quality for photos.
+    pub fn adjust_quality(&self, quality: f32) -> f32 {
+        match self {
+            ContentClass::Photo => quality,
+            ContentClass::Graphic => quality.max(85.),
+            ContentClass::Text => quality.max(90.),
+        }
+    }
+}
+
+impl fmt::Display for ContentClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentClass::Photo => write!(f, "photo"),
+            ContentClass::Graphic => write!(f, "graphic"),
+            ContentClass::Text => write!(f, "text"),
+        }
+    }
+}
+
+/// Statistics of an image that tell its kind of content.
+#[derive(Debug, PartialEq)]
+struct ContentStats {
+    /// Share of the pixels with the same color as the pixel to their right, the flat areas.
+    flat_ratio: f64,
+
+    /// Number of colors, with 5 bits per channel, per pixel.
+    color_ratio: f64,
+
+    /// Share of the pixels that are nearly white and unsaturated, like paper.
+    paper_ratio: f64,
+
+    /// Share of the pixels on a strong edge.
+    edge_ratio: f64,
+}
+
+/// Classify the image at the path by the statistics of its thumbnail.
+///
+/// # Error
+/// - When the image cannot be opened.
+pub fn classify_image<P: AsRef<Path>>(path: P) -> ImageResult<ContentClass> {
+    let image = image::open(path)?;
+    let image = match image.width().max(image.height()) > ANALYSIS_SIZE {
+        true => image.resize(ANALYSIS_SIZE, ANALYSIS_SIZE, FilterType::Triangle),
+        false => image,
+    };
+    Ok(classify(&get_stats(&image.to_rgb8())))
+}
+
+/// Text is dark edges on a lot of paper, graphics are flat areas of few colors, and the rest are photos.
+fn classify(stats: &ContentStats) -> ContentClass {
+    if stats.paper_ratio > 0.5 && stats.edge_ratio > 0.02 {
+        ContentClass::Text
+    } else if stats.flat_ratio > 0.6 || stats.color_ratio < 0.02 {
+        ContentClass::Graphic
+    } else {
+        ContentClass::Photo
+    }
+}
+
+fn get_stats(image: &RgbImage) -> ContentStats {
+    let (width, height) = image.dimensions();
+    let pixel_count = (width as f64 * height as f64).max(1.);
+    let luma = |x: u32, y: u32| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+    };
+    let mut flat_count = 0;
+    let mut paper_count = 0;
+    let mut edge_count = 0;
+    let mut colors = HashSet::new();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let [r, g, b] = pixel.0;
+        colors.insert((r >> 3, g >> 3, b >> 3));
+        if x + 1 < width && image.get_pixel(x + 1, y) == pixel {
+            flat_count += 1;
+        }
+        if r.min(g).min(b) > 200 && r.max(g).max(b) - r.min(g).min(b) < 30 {
+            paper_count += 1;
+        }
+        if x + 1 < width && y + 1 < height {
+            let gradient = (luma(x + 1, y) - luma(x, y)).abs() + (luma(x, y + 1) - luma(x, y)).abs();
+            if gradient > 100. {
+                edge_count += 1;
+            }
+        }
+    }
+    ContentStats {
+        flat_ratio: flat_count as f64 / pixel_count,
+        color_ratio: colors.len() as f64 / pixel_count,
+        paper_ratio: paper_count as f64 / pixel_count,
+        edge_ratio: edge_count as f64 / pixel_count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+    use super::*;
+
+    #[test]
+    fn classify_test(){
+        // Black strokes on white paper.
+        let text = RgbImage::from_fn(200, 200, |x, y| match y % 20 < 8 && x % 12 < 2 {
+            true => Rgb([20, 20, 20]),
+            false => Rgb([245, 245, 240]),
+        });
+        assert_eq!(classify(&get_stats(&text)), ContentClass::Text);
+
+        // A chart of flat colored bars.
+        let graphic = RgbImage::from_fn(200, 200, |x, _| match x / 50 {
+            0 => Rgb([200, 40, 40]),
+            1 => Rgb([40, 160, 40]),
+            _ => Rgb([40, 40, 200]),
+        });
+        assert_eq!(classify(&get_stats(&graphic)), ContentClass::Graphic);
+
+        // A noisy gradient.
+        let photo = RgbImage::from_fn(200, 200, |x, y| {
+            let noise = ((x * 7919 + y * 104729) % 31) as u8;
+            Rgb([(x / 2) as u8 + noise, (y / 2) as u8 + noise, 100 + noise])
+        });
+        assert_eq!(classify(&get_stats(&photo)), ContentClass::Photo);
+
+        assert_eq!(ContentClass::Photo.adjust_quality(70.), 70.);
+        assert_eq!(ContentClass::Text.adjust_quality(70.), 90.);
+        assert_eq!(ContentClass::Graphic.adjust_quality(95.), 95.);
+    }
+}
@@ -0,0 +1,215 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use image::{DynamicImage, Rgb32FImage, RgbImage};
+
+/// Operator that maps the light of a high dynamic range image to the range of an 8-bit image.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ToneMap {
+    /// Cut off everything brighter than white, which keeps the midtones as they are.
+    Clamp,
+    /// Compress the highlights smoothly, which keeps some detail in every light.
+    #[default]
+    Reinhard,
+    /// Filmic curve of ACES, with more contrast than Reinhard.
+    Aces,
+}
+
+impl ToneMap {
+    /// Create a [`ToneMap`] from the str. Unknown strings fall back to the default.
+    pub fn from(tone_map_str: &str) -> Self {
+        match tone_map_str {
+            "clamp" => ToneMap::Clamp,
+            "aces" => ToneMap::Aces,
+            _ => ToneMap::default(),
+        }
+    }
+
+    /// Map the linear light, where 1 is white, to the range from 0 to 1.
+    fn apply(&self, light: f32) -> f32 {
+        let light = light.max(0.);
+        let mapped = match self {
+            ToneMap::Clamp => light,
+            ToneMap::Reinhard => light / (1. + light),
+            ToneMap::Aces => (light * (2.51 * light + 0.03)) / (light * (2.43 * light + 0.59) + 0.14),
+        };
+        mapped.clamp(0., 1.)
+    }
+}
+
+impl fmt::Display for ToneMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToneMap::Clamp => write!(f, "clamp"),
+            ToneMap::Reinhard => write!(f, "reinhard"),
+            ToneMap::Aces => write!(f, "aces"),
+        }
+    }
+}
+
+/// Resolution of the SVG images at their own size, which CSS defines as 96 pixels per inch.
+pub const DEFAULT_SVG_DPI: f32 = 96.;
+
+/// How the formats that the compressor cannot handle well are decoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeOptions {
+    /// Operator of the EXR and HDR images.
+    pub tone_map: ToneMap,
+    /// Exposure of the EXR and HDR images in stops, added before the tone mapping.
+    pub exposure: f32,
+    /// Resolution the SVG images are rasterized at, where [`DEFAULT_SVG_DPI`] keeps their own size.
+    pub svg_dpi: f32,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions { tone_map: ToneMap::default(), exposure: 0., svg_dpi: DEFAULT_SVG_DPI }
+    }
+}
+
+/// Check whether the file is in a format that is decoded by [`open_image`] instead of the compressor.
+/// The compressor cannot tone map EXR and HDR images, which hold more light than an 8-bit image,
+/// and cannot decode HEIF and SVG images at all, which are decoded with the `heic` and `svg` features.
+pub fn is_own_format<P: AsRef<Path>>(path: P) -> bool {
+    match get_extension(path.as_ref()).as_str() {
+        "exr" | "hdr" => true,
+        #[cfg(feature = "heic")]
+        "heic" | "heif" | "hif" => true,
+        #[cfg(feature = "svg")]
+        "svg" => true,
+        _ => false,
+    }
+}
+
+/// Open the image, and tone map the images of floating point samples, like EXR and HDR images, to 8 bits.
+/// HEIF images, like the photos of iPhones, are decoded with libheif with the `heic` feature,
+/// and SVG images are rasterized at the resolution of the options with resvg with the `svg` feature.
+///
+/// # Error
+/// - When the file cannot be opened or decoded.
+pub fn open_image<P: AsRef<Path>>(path: P, options: &DecodeOptions) -> Result<DynamicImage, Box<dyn Error>> {
+    #[cfg(feature = "heic")]
+    if matches!(get_extension(path.as_ref()).as_str(), "heic" | "heif" | "hif") {
+        return open_heif(path.as_ref());
+    }
+    #[cfg(feature = "svg")]
+    if get_extension(path.as_ref()) == "svg" {
+        return open_svg(path.as_ref(), options.svg_dpi);
+    }
+    let image = image::open(path.as_ref())?;
+    Ok(match image {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => DynamicImage::ImageRgb8(tone_map(&image.to_rgb32f(), options)),
+        _ => image,
+    })
+}
+
+/// Get the extension of the file in lowercase, or an empty string.
+fn get_extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase()
+}
+
+/// Decode the primary image of the HEIF file to 8-bit RGB with libheif.
+#[cfg(feature = "heic")]
+fn open_heif(path: &Path) -> Result<DynamicImage, Box<dyn Error>> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+    let context = HeifContext::read_from_file(path.to_str().ok_or("The path of the HEIF file is not valid UTF-8")?)?;
+    let handle = context.primary_image_handle()?;
+    let image = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+    let plane = image.planes().interleaved.ok_or("The HEIF image has no RGB plane")?;
+    // Each row of the plane may be padded after its pixels.
+    let row_len = plane.width as usize * 3;
+    let pixels = plane.data.chunks(plane.stride)
+        .take(plane.height as usize)
+        .flat_map(|row| &row[..row_len])
+        .copied()
+        .collect();
+    let image = RgbImage::from_raw(plane.width, plane.height, pixels).ok_or("The HEIF image is smaller than its size")?;
+    Ok(DynamicImage::ImageRgb8(image))
+}
+
+/// Fonts of the system for the texts of SVG images, which are loaded once for every image.
+#[cfg(feature = "svg")]
+static SVG_FONTS: std::sync::OnceLock<std::sync::Arc<resvg::usvg::fontdb::Database>> = std::sync::OnceLock::new();
+
+/// Rasterize the SVG image at the resolution in pixels per inch on a white background, since JPEG has no transparency.
+/// Images linked by the SVG image are looked up next to it.
+#[cfg(feature = "svg")]
+fn open_svg(path: &Path, dpi: f32) -> Result<DynamicImage, Box<dyn Error>> {
+    use resvg::{tiny_skia, usvg};
+    let mut options = usvg::Options { resources_dir: path.parent().map(Path::to_path_buf), ..usvg::Options::default() };
+    options.fontdb = SVG_FONTS.get_or_init(|| {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+        std::sync::Arc::new(fonts)
+    }).clone();
+    let tree = usvg::Tree::from_data(&std::fs::read(path)?, &options)?;
+    let scale = dpi / DEFAULT_SVG_DPI;
+    let size = tree.size().to_int_size().scale_by(scale).ok_or("The SVG image is too small to rasterize")?;
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height()).ok_or("The SVG image is too large to rasterize")?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    let image = image::RgbaImage::from_raw(size.width(), size.height(), pixmap.take()).ok_or("The SVG image is smaller than its size")?;
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+/// Scale the linear light of the image by the exposure, map it with the operator and encode it in sRGB.
+fn tone_map(image: &Rgb32FImage, options: &DecodeOptions) -> RgbImage {
+    let scale = 2f32.powf(options.exposure);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        image::Rgb(image.get_pixel(x, y).0.map(|c| (to_srgb(options.tone_map.apply(c * scale)) * 255.).round() as u8))
+    })
+}
+
+/// Encode the linear value from 0 to 1 with the transfer function of sRGB.
+fn to_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1. / 2.4) - 0.055,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use super::*;
+
+    #[test]
+    fn tone_map_test(){
+        let test_dir = PathBuf::from("test_tone_map");
+        fs::create_dir_all(&test_dir).unwrap();
+        let light = [0., 0.25, 1., 4.];
+        let image = Rgb32FImage::from_fn(4, 1, |x, _| image::Rgb([light[x as usize]; 3]));
+        DynamicImage::ImageRgb32F(image.clone()).save(test_dir.join("a.exr")).unwrap();
+        DynamicImage::ImageRgb32F(image).save(test_dir.join("a.hdr")).unwrap();
+        assert!(is_own_format(test_dir.join("a.exr")) && is_own_format(test_dir.join("A.HDR")));
+        assert!(!is_own_format(test_dir.join("a.png")));
+
+        let red_of = |file: &str, options: DecodeOptions| -> Vec<u8> {
+            let image = open_image(test_dir.join(file), &options).unwrap().to_rgb8();
+            image.pixels().map(|p| p[0]).collect()
+        };
+        let clamp = DecodeOptions { tone_map: ToneMap::Clamp, ..DecodeOptions::default() };
+        assert_eq!(red_of("a.exr", clamp), [0, 137, 255, 255]);
+        assert_eq!(red_of("a.hdr", clamp), [0, 137, 255, 255]);
+        assert_eq!(red_of("a.exr", DecodeOptions { exposure: -2., ..clamp }), [0, 71, 137, 255]);
+        // Reinhard keeps the highlights apart, where clamping makes them the same white.
+        let reinhard = red_of("a.exr", DecodeOptions::default());
+        assert!(reinhard[2] < reinhard[3] && reinhard[3] < 255);
+        let aces = red_of("a.exr", DecodeOptions { tone_map: ToneMap::Aces, ..DecodeOptions::default() });
+        assert!(aces[1] < aces[2] && aces[2] < aces[3]);
+
+        // Images of 8-bit samples are not changed.
+        RgbImage::from_pixel(2, 2, image::Rgb([10, 20, 30])).save(test_dir.join("b.png")).unwrap();
+        assert_eq!(open_image(test_dir.join("b.png"), &clamp).unwrap().to_rgb8().get_pixel(0, 0).0, [10, 20, 30]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn tone_map_from_test(){
+        for tone_map in [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces] {
+            assert_eq!(ToneMap::from(&tone_map.to_string()), tone_map);
+        }
+        assert_eq!(ToneMap::from("unknown"), ToneMap::Reinhard);
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use log::warn;
+
+/// Lock of a destination folder, held while a job writes to it so that another job does not write to it at the same time.
+///
+/// The lock is a file beside the destination, like `.dest.lock`, with the process ID of the job.
+/// It is removed when the lock is dropped, even when the job fails.
+/// A lock left by a process that is no longer running is taken over, where the running processes can be checked.
+#[derive(Debug)]
+pub struct DestLock {
+    path: PathBuf,
+}
+
+impl DestLock {
+    /// Lock the destination folder.
+    ///
+    /// # Error
+    /// - When another job holds the lock.
+    /// - When the lock file cannot be written.
+    pub fn acquire<D: AsRef<Path>>(dest: D) -> io::Result<Self> {
+        let path = get_lock_path(dest.as_ref());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let pid = fs::read_to_string(&path).ok().and_then(|s| s.trim().parse::<u32>().ok());
+                match pid {
+                    Some(pid) if !is_running(pid) => {
+                        warn!("Took over the lock of {} left by the stopped process {}", dest.as_ref().display(), pid);
+                        fs::remove_file(&path)?;
+                        OpenOptions::new().write(true).create_new(true).open(&path)?
+                    }
+                    _ => return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!(
+                        "Another job is writing to the destination. Remove {} if no other job is running.", path.display()))),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        file.write_all(process::id().to_string().as_bytes())?;
+        Ok(DestLock { path })
+    }
+}
+
+impl Drop for DestLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Cannot remove the lock file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Get the path of the lock file of the destination, like `.dest.lock` beside it,
+/// so that the lock is not compressed or archived with the destination. It is in the destination when it has no parent.
+pub fn get_lock_path<D: AsRef<Path>>(dest: D) -> PathBuf {
+    let dest = dest.as_ref();
+    match (dest.parent(), dest.file_name()) {
+        (Some(parent), Some(name)) => parent.join(format!(".{}.lock", name.to_string_lossy())),
+        _ => dest.join(".lock"),
+    }
+}
+
+/// Whether the process is running. It is assumed to be running where it cannot be checked.
+fn is_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dest_lock_test(){
+        let test_dir = PathBuf::from("test_dest_lock");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let dest = test_dir.join("dest");
+        let lock_path = get_lock_path(&dest);
+        assert_eq!(lock_path, test_dir.join(".dest.lock"));
+
+        let lock = DestLock::acquire(&dest).unwrap();
+        assert_eq!(fs::read_to_string(&lock_path).unwrap(), process::id().to_string());
+        assert!(DestLock::acquire(&dest).is_err());
+        drop(lock);
+        assert!(!lock_path.exists());
+
+        // A lock whose process is not running any more.
+        if cfg!(target_os = "linux") {
+            fs::write(&lock_path, "4194305").unwrap();
+            let _lock = DestLock::acquire(&dest).unwrap();
+        }
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
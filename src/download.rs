@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use crate::path_util::sanitize_path;
+use crate::remote::to_remote_error;
+
+/// Parse the URLs written one per line. Blank lines and lines starting with `#` are skipped.
+pub fn parse_url_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Download the image at the URL into the directory, and return the path of the downloaded file.
+///
+/// The file is named after the last segment of the URL, with the extension of its content type when the name has none,
+/// and numbered like `a (1).jpg` when a file of the name already exists.
+///
+/// # Error
+/// - When the URL is not an HTTP or HTTPS URL.
+/// - When the server does not respond with the image.
+/// - When the file cannot be written.
+pub fn download_image<D: AsRef<Path>>(url: &str, dir: D) -> Result<PathBuf, Box<dyn Error>> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "Only http:// and https:// URLs can be downloaded!")));
+    }
+    let response = ureq::get(url)
+        .set("User-Agent", concat!("ImageCompressor/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .map_err(to_remote_error)?;
+    let file_name = get_file_name(url, response.content_type());
+    fs::create_dir_all(dir.as_ref())?;
+    let file_path = get_unused_path(dir.as_ref(), &file_name);
+
+    let mut writer = BufWriter::new(File::create(&file_path)?);
+    let result = io::copy(&mut response.into_reader(), &mut writer).and_then(|_| writer.flush());
+    if let Err(e) = result {
+        // Do not leave a broken image to be compressed.
+        let _ = fs::remove_file(&file_path);
+        return Err(Box::new(e));
+    }
+    Ok(file_path)
+}
+
+/// Get the name of the file from the last segment of the URL, and the extension from the content type if it has none.
+fn get_file_name(url: &str, content_type: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = path.split_once("://").map(|(_, p)| p).unwrap_or(path);
+    let name = match path.split_once('/') {
+        Some((_, p)) => p.rsplit('/').next().unwrap_or_default(),
+        None => "",
+    };
+    let decoded = decode_percent(name);
+    let name = decoded.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name = sanitize_path(name).to_string_lossy().to_string();
+    let name = match name.trim_matches('.') {
+        "" => String::from("image"),
+        n => n.to_string(),
+    };
+    if Path::new(&name).extension().is_some() {
+        return name;
+    }
+    let extension = match content_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        "image/bmp" => "bmp",
+        "image/tiff" => "tif",
+        _ => return name,
+    };
+    format!("{}.{}", name, extension)
+}
+
+/// Decode the percent-encoded bytes of the URL segment, like `%20`.
+fn decode_percent(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], segment.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Get the path of the file name in the directory, numbered if a file of the name already exists.
+fn get_unused_path(dir: &Path, file_name: &str) -> PathBuf {
+    let path = dir.join(file_name);
+    if !path.exists() {
+        return path;
+    }
+    let name = Path::new(file_name);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = name.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..).map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|p| !p.exists())
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_file_name_test(){
+        assert_eq!(get_file_name("https://example.com/img/a%20b.jpg?w=100#top", "image/png"), "a b.jpg");
+        assert_eq!(get_file_name("https://example.com/img/photo", "image/jpeg"), "photo.jpg");
+        assert_eq!(get_file_name("https://example.com/", "image/webp"), "image.webp");
+        assert_eq!(get_file_name("https://example.com", "text/html"), "image");
+        assert_eq!(get_file_name("https://example.com/..%2F..%2Fa.png", "image/png"), "a.png");
+        assert_eq!(parse_url_list("# assets\nhttps://example.com/a.png\n\n  https://example.com/b.png  "),
+                   vec!["https://example.com/a.png", "https://example.com/b.png"]);
+    }
+}
@@ -0,0 +1,264 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::ops::AddAssign;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{ImageReader, Limits, RgbImage};
+use image_compressor::compressor::{Compressor, Factor};
+use log::warn;
+
+/// Encoder of the compressed JPEG files.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EncoderBackend {
+    /// mozjpeg through `image_compressor`, which makes smaller files.
+    #[default]
+    MozJpeg,
+    /// The pure Rust encoder of the `image` crate, for systems where mozjpeg cannot run.
+    ImageRs,
+}
+
+impl EncoderBackend {
+    /// Create an [`EncoderBackend`] from the str. Unknown strings fall back to the default.
+    pub fn from(backend_str: &str) -> Self {
+        match backend_str {
+            "image-rs" => EncoderBackend::ImageRs,
+            _ => EncoderBackend::default(),
+        }
+    }
+}
+
+impl fmt::Display for EncoderBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncoderBackend::MozJpeg => write!(f, "mozjpeg"),
+            EncoderBackend::ImageRs => write!(f, "image-rs"),
+        }
+    }
+}
+
+/// Time spent in each stage of compressing an image.
+///
+/// mozjpeg decodes, resizes and encodes in a single call, so all of its time counts as encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageTimes {
+    /// Reading and decoding the source.
+    pub decode: Duration,
+    /// Resizing and applying the image steps.
+    pub steps: Duration,
+    /// Encoding and writing the output.
+    pub encode: Duration,
+}
+
+impl StageTimes {
+    /// Time spent in all the stages.
+    pub fn total(&self) -> Duration {
+        self.decode + self.steps + self.encode
+    }
+}
+
+impl AddAssign for StageTimes {
+    fn add_assign(&mut self, other: Self) {
+        self.decode += other.decode;
+        self.steps += other.steps;
+        self.encode += other.encode;
+    }
+}
+
+/// Compressed image written by [`compress_to_jpg`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressOutput {
+    pub path: PathBuf,
+    /// Size of the source file in bytes.
+    pub original_size: u64,
+    /// Size of the compressed image in bytes.
+    pub compressed_size: u64,
+    /// Width of the compressed image.
+    pub width: u32,
+    /// Height of the compressed image.
+    pub height: u32,
+    /// Time to decode, resize and encode the image.
+    pub times: StageTimes,
+}
+
+/// Compress the file into `{file stem}.jpg` in the destination directory with the encoder.
+///
+/// Both encoders treat the files that are not images alike, as `Compressor::compress_to_jpg` does:
+/// a file whose format cannot be guessed is left out, and a file of a known format that cannot be decoded
+/// is copied into the destination directory. An error of `io::ErrorKind::InvalidInput` is returned for the former,
+/// and one of `io::ErrorKind::InvalidData` for the latter.
+/// The output is written only after the image is encoded, so a failed encode leaves no file behind.
+///
+/// # Error
+/// - When the source file cannot be opened.
+/// - When the output file already exists.
+/// - When the format of the file is unknown, or the file cannot be decoded or compressed.
+/// - When the output cannot be written, or the source cannot be deleted.
+pub fn compress_to_jpg<S: AsRef<Path>, D: AsRef<Path>>(source: S, dest_dir: D, factor: Factor, delete_source: bool, backend: EncoderBackend) -> Result<CompressOutput, Box<dyn Error>> {
+    let start = Instant::now();
+    if backend == EncoderBackend::MozJpeg {
+        // The compressor reports a file it cannot open as an unrecognized format, which hides the permission errors.
+        let original_size = File::open(source.as_ref())?.metadata()?.len();
+        let mut compressor = Compressor::new(source.as_ref(), dest_dir.as_ref());
+        compressor.set_factor(factor);
+        compressor.set_delete_source(delete_source);
+        let path = compressor.compress_to_jpg()?;
+        // The compressor only returns the path, so the size is read from the header of the output.
+        let (width, height) = image::image_dimensions(&path)?;
+        let compressed_size = fs::metadata(&path)?.len();
+        let times = StageTimes { encode: start.elapsed(), ..StageTimes::default() };
+        return Ok(CompressOutput { path, original_size, compressed_size, width, height, times });
+    }
+
+    let source = source.as_ref();
+    let file_name = source.file_name().unwrap_or_default();
+    let output = dest_dir.as_ref().join(source.file_stem().unwrap_or_default()).with_extension("jpg");
+    if output.is_file() {
+        return Err(Box::new(io::Error::new(io::ErrorKind::AlreadyExists, format!("A file with the same name exists: {}", output.display()))));
+    }
+    let file = File::open(source)?;
+    let original_size = file.metadata()?.len();
+    let mut reader = ImageReader::new(BufReader::new(file)).with_guessed_format()?;
+    if reader.format().is_none() {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "Unrecognized image format")));
+    }
+    // Large images are decoded without the default limits of the decoder, like the compressor does.
+    reader.limits(Limits::no_limits());
+    let decoded = reader.decode();
+    let decode = start.elapsed();
+    let image = match decoded {
+        Ok(i) => i,
+        Err(e) => {
+            fs::copy(source, dest_dir.as_ref().join(file_name))?;
+            let message = format!("Cannot open file {} as image. Just copy it: {}", file_name.to_string_lossy(), e);
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, message)));
+        }
+    };
+    // Small images at a low ratio would round to nothing.
+    let width = ((image.width() as f32 * factor.size_ratio()) as u32).max(1);
+    let height = ((image.height() as f32 * factor.size_ratio()) as u32).max(1);
+    let image = image.resize(width, height, FilterType::Triangle).to_rgb8();
+    let steps = start.elapsed() - decode;
+    let quality = factor.quality().round().clamp(1., 100.) as u8;
+    let mut encoded = Vec::new();
+    JpegEncoder::new_with_quality(&mut encoded, quality).encode_image(&image)
+        .map_err(|e| format!("Cannot compress file {}: {}", file_name.to_string_lossy(), e))?;
+    fs::write(&output, &encoded)?;
+    if delete_source {
+        fs::remove_file(source)?;
+    }
+    Ok(CompressOutput {
+        path: output,
+        original_size,
+        compressed_size: encoded.len() as u64,
+        width: image.width(),
+        height: image.height(),
+        times: StageTimes { decode, steps, encode: start.elapsed() - decode - steps },
+    })
+}
+
+/// Result of the mozjpeg check, which is done once for every pipeline of the process.
+static MOZJPEG_AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+/// Check that mozjpeg works on this system by compressing a small image, so that image-rs can be used instead.
+/// The check runs once in the process, and later calls return its result.
+///
+/// mozjpeg panics when it fails, so a panic fails the check as well.
+/// Failures that are not panics, like an illegal instruction on an old CPU, still end the process,
+/// so select [`EncoderBackend::ImageRs`] yourself on such systems.
+pub fn is_mozjpeg_available() -> bool {
+    *MOZJPEG_AVAILABLE.get_or_init(check_mozjpeg)
+}
+
+fn check_mozjpeg() -> bool {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or_default();
+    let temp_dir = env::temp_dir().join(format!("image_compressor_encoder_check_{}_{}", process::id(), nanos));
+    let check = || -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&temp_dir)?;
+        let source = temp_dir.join("check.png");
+        RgbImage::from_fn(16, 16, |x, y| image::Rgb([x as u8 * 16, y as u8 * 16, 128])).save(&source)?;
+        let output_dir = temp_dir.join("output");
+        fs::create_dir_all(&output_dir)?;
+        compress_to_jpg(&source, &output_dir, Factor::default(), false, EncoderBackend::MozJpeg)?;
+        Ok(())
+    };
+    let result = panic::catch_unwind(check);
+    if let Err(e) = fs::remove_dir_all(&temp_dir) {
+        warn!("Cannot remove the encoder check folder {}: {}", temp_dir.display(), e);
+    }
+    matches!(result, Ok(Ok(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_rs_backend_test(){
+        let test_dir = PathBuf::from("test_image_rs_backend");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let dest_dir = test_dir.join("dest");
+        fs::create_dir_all(&dest_dir).unwrap();
+        let source = test_dir.join("a.png");
+        RgbImage::from_fn(40, 30, |x, y| image::Rgb([x as u8 * 6, y as u8 * 8, 100])).save(&source).unwrap();
+
+        let original_size = fs::metadata(&source).unwrap().len();
+        let output = compress_to_jpg(&source, &dest_dir, Factor::new(70., 0.5), true, EncoderBackend::ImageRs).unwrap();
+        assert_eq!(output.path, dest_dir.join("a.jpg"));
+        assert_eq!(image::image_dimensions(&output.path).unwrap(), (20, 15));
+        assert_eq!((output.width, output.height), (20, 15));
+        assert_eq!(output.original_size, original_size);
+        assert_eq!(output.compressed_size, fs::metadata(&output.path).unwrap().len());
+        assert!(!source.exists());
+
+        // A tiny image at a low ratio is still encoded.
+        let source = test_dir.join("tiny.png");
+        RgbImage::from_pixel(3, 2, image::Rgb([10, 20, 30])).save(&source).unwrap();
+        let output = compress_to_jpg(&source, &dest_dir, Factor::new(70., 0.1), false, EncoderBackend::ImageRs).unwrap();
+        assert_eq!(image::image_dimensions(&output.path).unwrap(), (1, 1));
+
+        // mozjpeg reports the same details of its output.
+        let moz_dir = test_dir.join("moz");
+        fs::create_dir_all(&moz_dir).unwrap();
+        let output = compress_to_jpg(test_dir.join("tiny.png"), &moz_dir, Factor::new(70., 1.), false, EncoderBackend::MozJpeg).unwrap();
+        assert_eq!((output.path.as_path(), output.width, output.height), (moz_dir.join("tiny.jpg").as_path(), 3, 2));
+        assert_eq!(output.original_size, fs::metadata(test_dir.join("tiny.png")).unwrap().len());
+        assert_eq!(output.compressed_size, fs::metadata(&output.path).unwrap().len());
+
+        // Both encoders leave out unknown formats and copy broken images of a known format.
+        fs::write(test_dir.join("b.txt"), "not an image").unwrap();
+        let mut broken = fs::read(test_dir.join("tiny.png")).unwrap();
+        broken.truncate(20);
+        fs::write(test_dir.join("c.png"), broken).unwrap();
+        for (backend, dir) in [(EncoderBackend::ImageRs, "image_rs"), (EncoderBackend::MozJpeg, "mozjpeg")] {
+            let dest_dir = test_dir.join(dir);
+            fs::create_dir_all(&dest_dir).unwrap();
+            assert!(compress_to_jpg(test_dir.join("b.txt"), &dest_dir, Factor::default(), false, backend).is_err());
+            assert!(compress_to_jpg(test_dir.join("c.png"), &dest_dir, Factor::default(), false, backend).is_err());
+            let mut names = fs::read_dir(&dest_dir).unwrap().map(|e| e.unwrap().file_name()).collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(names, ["c.png"], "{}", backend);
+        }
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn encoder_backend_test(){
+        assert!(is_mozjpeg_available());
+        for backend in [EncoderBackend::MozJpeg, EncoderBackend::ImageRs] {
+            assert_eq!(EncoderBackend::from(&backend.to_string()), backend);
+        }
+        assert_eq!(EncoderBackend::from("unknown"), EncoderBackend::MozJpeg);
+    }
+}
@@ -0,0 +1,106 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use image::ImageFormat;
+use image_compressor::compressor::{Compressor, Factor};
+use log::warn;
+use crate::preflight::get_total_size;
+
+/// Number of images compressed to estimate the output size.
+pub const SAMPLE_COUNT: usize = 8;
+
+/// Output size estimated from compressing a few sample images.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeEstimate {
+    pub sample_count: usize,
+    pub original_size: u64,
+    pub estimated_size: u64,
+}
+
+impl SizeEstimate {
+    /// Percentage of the original size that is saved. It is negative when the output is larger.
+    pub fn savings_percent(&self) -> f64 {
+        match self.original_size {
+            0 => 0.,
+            s => (1. - self.estimated_size as f64 / s as f64) * 100.,
+        }
+    }
+}
+
+/// Estimate the total output size of the files by compressing [`SAMPLE_COUNT`] images spread over the list with the factor.
+///
+/// The sampled images are compressed into a temporary directory that is removed afterwards.
+/// The compression ratio of the samples is applied to every image, and the other files are counted as they are,
+/// since the compressor copies them.
+pub fn estimate_output_size<P: AsRef<Path>>(file_list: &[P], factor: Factor) -> io::Result<SizeEstimate> {
+    let (image_list, other_list): (Vec<&Path>, Vec<&Path>) = file_list.iter()
+        .map(|f| f.as_ref())
+        .partition(|f| ImageFormat::from_path(f).is_ok());
+    let image_size = get_total_size(&image_list);
+    let other_size = get_total_size(&other_list);
+
+    let temp_dir = env::temp_dir().join(format!("image_compressor_estimate_{}", process::id()));
+    if temp_dir.exists() {
+        fs::remove_dir_all(&temp_dir)?;
+    }
+    let mut sample_count = 0;
+    let mut sample_size = 0;
+    let mut compressed_size = 0;
+    for (i, image) in pick_samples(&image_list, SAMPLE_COUNT).into_iter().enumerate() {
+        // Each sample gets its own directory, since samples may share a file stem.
+        let sample_dir = temp_dir.join(i.to_string());
+        fs::create_dir_all(&sample_dir)?;
+        let mut compressor = Compressor::new(image, &sample_dir);
+        compressor.set_factor(factor);
+        let output = match compressor.compress_to_jpg() {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        sample_count += 1;
+        sample_size += fs::metadata(image).map(|m| m.len()).unwrap_or(0);
+        compressed_size += fs::metadata(output).map(|m| m.len()).unwrap_or(0);
+    }
+    if let Err(e) = fs::remove_dir_all(&temp_dir) {
+        warn!("Cannot remove the estimation folder {}: {}", temp_dir.display(), e);
+    }
+
+    let ratio = match sample_size {
+        0 => 1.,
+        s => compressed_size as f64 / s as f64,
+    };
+    Ok(SizeEstimate {
+        sample_count,
+        original_size: image_size + other_size,
+        estimated_size: (image_size as f64 * ratio) as u64 + other_size,
+    })
+}
+
+/// Pick at most `count` items spread evenly over the list.
+fn pick_samples<T: Copy>(list: &[T], count: usize) -> Vec<T> {
+    if list.len() <= count {
+        return list.to_vec();
+    }
+    (0..count).map(|i| list[i * list.len() / count]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_samples_test(){
+        let list = (0..100).collect::<Vec<_>>();
+        assert_eq!(pick_samples(&list, 4), vec![0, 25, 50, 75]);
+        assert_eq!(pick_samples(&list[..3], 4), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn savings_percent_test(){
+        let estimate = SizeEstimate { sample_count: 1, original_size: 200, estimated_size: 50 };
+        assert_eq!(estimate.savings_percent(), 75.);
+        let estimate = SizeEstimate { sample_count: 0, original_size: 0, estimated_size: 0 };
+        assert_eq!(estimate.savings_percent(), 0.);
+    }
+}
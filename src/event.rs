@@ -0,0 +1,394 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use log::{warn, LevelFilter};
+use serde::{Deserialize, Serialize};
+
+/// Stage of a [`Pipeline`](crate::Pipeline) run that an [`Event`] comes from.
+/// It is serialized by the same name as it is displayed, like `compress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// The run as a whole, such as the error that stopped it.
+    Job,
+
+    /// Downloading the images at the URLs before compressing.
+    Download,
+
+    /// Checks before compressing.
+    Preflight,
+
+    /// Compressing images by `image_compressor`.
+    Compress,
+
+    /// Summary and report of the compressed files.
+    Report,
+
+    /// Replacing the original files when compressing in place.
+    Replace,
+
+    /// Archiving by `zip_archive` or making PDF files.
+    Archive,
+
+    /// Uploading the outputs to a remote destination.
+    Upload,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Job => "job",
+            Stage::Download => "download",
+            Stage::Preflight => "preflight",
+            Stage::Compress => "compress",
+            Stage::Report => "report",
+            Stage::Replace => "replace",
+            Stage::Archive => "archive",
+            Stage::Upload => "upload",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How serious an [`Event`] is, which [`Verbosity`] chooses the events by.
+/// It is serialized in lowercase, like `warning`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Details of each step, such as the progress of a download or an archive.
+    Debug,
+
+    /// Progress and results of the run.
+    #[default]
+    Info,
+
+    /// Problems that the run works around, such as a renamed file or a retried upload.
+    Warning,
+
+    /// Failures of a file or a stage.
+    Error,
+}
+
+/// Which events of a [`Pipeline`](crate::Pipeline) run are sent, from none to every detail.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Verbosity {
+    /// Send no events.
+    Silent,
+    /// Send only the errors.
+    ErrorsOnly,
+    /// Send every event but the details.
+    #[default]
+    Normal,
+    /// Send every event.
+    Debug,
+}
+
+impl Verbosity {
+    /// Create a [`Verbosity`] from the str. Unknown strings fall back to the default.
+    pub fn from(verbosity_str: &str) -> Self {
+        match verbosity_str {
+            "silent" => Verbosity::Silent,
+            "errors" => Verbosity::ErrorsOnly,
+            "debug" => Verbosity::Debug,
+            _ => Verbosity::default(),
+        }
+    }
+
+    /// Check whether the events of the severity are sent.
+    pub fn allows(&self, severity: Severity) -> bool {
+        match self {
+            Verbosity::Silent => false,
+            Verbosity::ErrorsOnly => severity == Severity::Error,
+            Verbosity::Normal => severity >= Severity::Info,
+            Verbosity::Debug => true,
+        }
+    }
+
+    /// Get the level of the log messages of the program that matches the verbosity.
+    pub fn log_level(&self) -> LevelFilter {
+        match self {
+            Verbosity::Silent => LevelFilter::Off,
+            Verbosity::ErrorsOnly => LevelFilter::Error,
+            Verbosity::Normal => LevelFilter::Warn,
+            Verbosity::Debug => LevelFilter::Debug,
+        }
+    }
+}
+
+impl fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verbosity::Silent => write!(f, "silent"),
+            Verbosity::ErrorsOnly => write!(f, "errors"),
+            Verbosity::Normal => write!(f, "normal"),
+            Verbosity::Debug => write!(f, "debug"),
+        }
+    }
+}
+
+/// Count of the files that are compressed or copied so far in a pipeline run, out of every file of the run.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FileProgress {
+    pub done: usize,
+    pub total: usize,
+
+    /// Original file that was done last.
+    pub file: PathBuf,
+
+    /// Time that the file took to compress or copy.
+    pub elapsed: Duration,
+}
+
+impl FileProgress {
+    /// Share of the files that are done, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        match self.total {
+            0 => 1.,
+            t => (self.done as f32 / t as f32).min(1.),
+        }
+    }
+}
+
+/// Message from a pipeline run, tagged with the stage it comes from and its severity.
+///
+/// It is serialized like `{"stage":"compress","severity":"info","message":"...","time":{...},"progress":null}`, for example to pass the events
+/// to another process, and displayed like `[compress] ...` for the users of the string messages.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Event {
+    pub stage: Stage,
+
+    /// Events serialized before the severity was added are read as [`Severity::Info`].
+    #[serde(default)]
+    pub severity: Severity,
+    pub message: String,
+
+    /// Time when the event was made, however late it is received.
+    pub time: SystemTime,
+
+    /// Progress of the run when the message is about a file that is done.
+    pub progress: Option<FileProgress>,
+}
+
+impl Event {
+    /// Create an event of the stage made now, of [`Severity::Info`].
+    pub fn new<T: ToString>(stage: Stage, message: T) -> Self {
+        Event {
+            stage,
+            severity: Severity::Info,
+            message: message.to_string(),
+            time: SystemTime::now(),
+            progress: None,
+        }
+    }
+
+    /// Create an event about a file that is done, with the progress of the run.
+    pub fn with_progress<T: ToString>(stage: Stage, message: T, progress: FileProgress) -> Self {
+        Event {
+            progress: Some(progress),
+            ..Event::new(stage, message)
+        }
+    }
+
+    /// Set the severity of the event.
+    pub fn with_severity(self, severity: Severity) -> Self {
+        Event { severity, ..self }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.stage, self.message)
+    }
+}
+
+/// Receiver of the [`Event`]s of a [`Pipeline`](crate::Pipeline) run, such as the sender of a channel or a closure.
+///
+/// It is implemented for the `std::sync::mpsc` senders of events, and of strings which get the text of the events,
+/// for the `tokio::sync::mpsc` senders of events with the `tokio` feature, and for closures that take an event.
+/// Other channels, like `crossbeam_channel`, are given as a closure that sends the event.
+pub trait EventSink: Send + Sync + 'static {
+    /// Pass on the event, or give it back if the receiver is gone.
+    fn send_event(&self, event: Event) -> Result<(), Event>;
+}
+
+impl EventSink for Sender<Event> {
+    fn send_event(&self, event: Event) -> Result<(), Event> {
+        self.send(event).map_err(|e| e.0)
+    }
+}
+
+impl EventSink for Sender<String> {
+    fn send_event(&self, event: Event) -> Result<(), Event> {
+        self.send(event.to_string()).map_err(|_| event)
+    }
+}
+
+impl<F: Fn(Event) + Send + Sync + 'static> EventSink for F {
+    fn send_event(&self, event: Event) -> Result<(), Event> {
+        self(event);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl EventSink for tokio::sync::mpsc::UnboundedSender<Event> {
+    fn send_event(&self, event: Event) -> Result<(), Event> {
+        self.send(event).map_err(|e| e.0)
+    }
+}
+
+/// Waits while the channel is full, so the pipeline must not run in an async task but in a thread,
+/// like `tokio::task::spawn_blocking`.
+#[cfg(feature = "tokio")]
+impl EventSink for tokio::sync::mpsc::Sender<Event> {
+    fn send_event(&self, event: Event) -> Result<(), Event> {
+        self.blocking_send(event).map_err(|e| e.0)
+    }
+}
+
+/// Send the event to the sink if it is set and the verbosity allows its severity.
+/// If the receiver is gone, the event is logged instead.
+pub(crate) fn try_send_event(sink: &Option<Arc<dyn EventSink>>, verbosity: Verbosity, event: Event) {
+    if !verbosity.allows(event.severity) {
+        return;
+    }
+    if let Some(Err(e)) = sink.as_ref().map(|s| s.send_event(event)) {
+        warn!("Message passing error: {}", e);
+    }
+}
+
+/// Thread that turns the string messages of a library into [`Event`]s of a stage.
+///
+/// The libraries only take a `Sender<String>`, so give them [`sender`](Forwarder::sender)
+/// and [`join`](Forwarder::join) when the stage is done to keep the events in order.
+pub(crate) struct Forwarder {
+    sender: Sender<String>,
+    handle: JoinHandle<()>,
+}
+
+impl Forwarder {
+    pub(crate) fn new(event_sink: &Arc<dyn EventSink>, stage: Stage, verbosity: Verbosity) -> Self {
+        let (sender, receiver) = mpsc::channel::<String>();
+        let event_sink = Some(Arc::clone(event_sink));
+        let handle = thread::spawn(move || {
+            for message in receiver {
+                try_send_event(&event_sink, verbosity, Event::new(stage, message));
+            }
+        });
+        Forwarder { sender, handle }
+    }
+
+    pub(crate) fn sender(&self) -> Sender<String> {
+        self.sender.clone()
+    }
+
+    /// Wait until every message is forwarded. Every clone of the sender must be dropped before.
+    pub(crate) fn join(self) {
+        drop(self.sender);
+        if self.handle.join().is_err() {
+            warn!("The message forwarding thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use super::*;
+
+    #[test]
+    fn forwarder_test(){
+        let (tx, tr) = mpsc::channel::<Event>();
+        let forwarder = Forwarder::new(&(Arc::new(tx) as Arc<dyn EventSink>), Stage::Compress, Verbosity::Normal);
+        let sender = forwarder.sender();
+        sender.send(String::from("Compress complete! File: a.jpg")).unwrap();
+        drop(sender);
+        forwarder.join();
+
+        let event = tr.try_recv().unwrap();
+        assert_eq!((event.stage, event.message.as_str()), (Stage::Compress, "Compress complete! File: a.jpg"));
+        assert_eq!(event.to_string(), "[compress] Compress complete! File: a.jpg");
+        assert!(tr.try_recv().is_err());
+    }
+
+    #[test]
+    fn file_progress_test(){
+        let progress = FileProgress { done: 34, total: 340, file: PathBuf::from("a.jpg"), elapsed: Duration::from_millis(120) };
+        assert_eq!(progress.fraction(), 0.1);
+        assert_eq!(FileProgress { done: 0, total: 0, ..progress.clone() }.fraction(), 1.);
+        let event = Event::with_progress(Stage::Compress, "Compress complete! File: a.jpg", progress.clone());
+        assert_eq!(event.progress.as_ref().map(|p| p.elapsed), Some(Duration::from_millis(120)));
+        assert_eq!(event.progress, Some(progress));
+        assert_eq!(event.to_string(), "[compress] Compress complete! File: a.jpg");
+    }
+
+    #[test]
+    fn event_time_test(){
+        let before = SystemTime::now();
+        let event = Event::new(Stage::Job, "Job started");
+        assert!(before <= event.time && event.time <= SystemTime::now());
+        // The time of the event is kept while it waits to be received.
+        let (tx, tr) = mpsc::channel::<Event>();
+        tx.send_event(event.clone()).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(tr.try_recv().unwrap().time, event.time);
+    }
+
+    #[test]
+    fn serialize_event_test(){
+        let event = Event { time: SystemTime::UNIX_EPOCH + Duration::from_millis(1500), ..Event::new(Stage::Archive, "Archive complete!") };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(json, r#"{"stage":"archive","severity":"info","message":"Archive complete!","time":{"secs_since_epoch":1,"nanos_since_epoch":500000000},"progress":null}"#);
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+        let old_json = r#"{"stage":"archive","message":"Archive complete!","time":{"secs_since_epoch":1,"nanos_since_epoch":500000000},"progress":null}"#;
+        assert_eq!(serde_json::from_str::<Event>(old_json).unwrap(), event);
+        let event = event.with_severity(Severity::Warning);
+        assert!(serde_json::to_string(&event).unwrap().contains(r#""severity":"warning""#));
+
+        let progress = FileProgress { done: 1, total: 2, file: PathBuf::from("a.jpg"), elapsed: Duration::from_secs(2) };
+        let event = Event::with_progress(Stage::Compress, "Compress complete! File: a.jpg", progress);
+        assert_eq!(serde_json::from_str::<Event>(&serde_json::to_string(&event).unwrap()).unwrap(), event);
+    }
+
+    #[test]
+    fn event_sink_test(){
+        let event = Event::new(Stage::Upload, "Upload complete!");
+        let (tx, tr) = mpsc::channel::<String>();
+        assert!(tx.send_event(event.clone()).is_ok());
+        assert_eq!(tr.try_recv().unwrap(), "[upload] Upload complete!");
+        drop(tr);
+        assert_eq!(tx.send_event(event.clone()), Err(event.clone()));
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let closure_received = Arc::clone(&received);
+        let sink: Option<Arc<dyn EventSink>> = Some(Arc::new(move |e| closure_received.lock().unwrap().push(e)));
+        try_send_event(&sink, Verbosity::Normal, event.clone());
+        try_send_event(&None, Verbosity::Normal, event.clone());
+        assert_eq!(*received.lock().unwrap(), vec![event]);
+    }
+
+    #[test]
+    fn verbosity_test(){
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let closure_received = Arc::clone(&received);
+        let sink: Option<Arc<dyn EventSink>> = Some(Arc::new(move |e: Event| closure_received.lock().unwrap().push(e.severity)));
+        let severities = [Severity::Debug, Severity::Info, Severity::Warning, Severity::Error];
+        for verbosity in [Verbosity::Silent, Verbosity::ErrorsOnly, Verbosity::Normal, Verbosity::Debug] {
+            for severity in severities {
+                try_send_event(&sink, verbosity, Event::new(Stage::Job, "message").with_severity(severity));
+            }
+            assert_eq!(Verbosity::from(&verbosity.to_string()), verbosity);
+        }
+        assert_eq!(*received.lock().unwrap(), [
+            Severity::Error,
+            Severity::Info, Severity::Warning, Severity::Error,
+            Severity::Debug, Severity::Info, Severity::Warning, Severity::Error,
+        ]);
+        assert_eq!(Verbosity::from("loud"), Verbosity::Normal);
+        assert_eq!(Verbosity::Silent.log_level(), LevelFilter::Off);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+/// What is done with the files of an extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExtensionAction {
+    /// Copy the files as they are.
+    Copy,
+    /// Compress the files with the quality from 1 to 100.
+    Quality(f32),
+}
+
+/// Actions for the files of each extension, used instead of the factor of the job.
+///
+/// Rules are written as `extension=action` separated by commas,
+/// where the action is `copy` or a quality, e.g. `png=copy, webp=copy, jpg=80`.
+/// Extensions are compared without case, so `jpg` and `jpeg` need a rule each.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtensionRules {
+    actions: HashMap<String, ExtensionAction>,
+}
+
+impl ExtensionRules {
+    /// Parse the rules.
+    ///
+    /// # Error
+    /// - When a rule has no `=`, or has an action that is not `copy` or a quality from 1 to 100.
+    pub fn parse(rules: &str) -> Result<Self, Box<dyn Error>> {
+        let mut actions = HashMap::new();
+        for rule in rules.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let (extension, action) = match rule.split_once('=') {
+                Some((e, a)) => (e.trim().trim_start_matches("*.").trim_start_matches('.').to_lowercase(), a.trim()),
+                None => return Err(invalid_rule(rule)),
+            };
+            let action = match action.parse::<f32>() {
+                Ok(q) if (1. ..=100.).contains(&q) => ExtensionAction::Quality(q),
+                Err(_) if action.eq_ignore_ascii_case("copy") => ExtensionAction::Copy,
+                _ => return Err(invalid_rule(rule)),
+            };
+            if extension.is_empty() {
+                return Err(invalid_rule(rule));
+            }
+            actions.insert(extension, action);
+        }
+        Ok(ExtensionRules { actions })
+    }
+
+    /// Get the action for the extension of the file.
+    pub fn get<P: AsRef<Path>>(&self, file_path: P) -> Option<ExtensionAction> {
+        let extension = file_path.as_ref().extension()?.to_string_lossy().to_lowercase();
+        self.actions.get(&extension).copied()
+    }
+
+    /// Whether there is no rule.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+fn invalid_rule(rule: &str) -> Box<dyn Error> {
+    let message = format!("\"{}\" is not an extension with copy or a quality from 1 to 100", rule);
+    Box::new(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_rules_test(){
+        let rules = ExtensionRules::parse("png=copy, *.WebP = Copy, .jpg=80,").unwrap();
+        assert_eq!(rules.get("a/b.PNG"), Some(ExtensionAction::Copy));
+        assert_eq!(rules.get("b.webp"), Some(ExtensionAction::Copy));
+        assert_eq!(rules.get("c.jpg"), Some(ExtensionAction::Quality(80.)));
+        assert_eq!(rules.get("d.jpeg"), None);
+        assert_eq!(rules.get("e"), None);
+        assert!(ExtensionRules::parse("").unwrap().is_empty());
+        assert!(ExtensionRules::parse("png").is_err());
+        assert!(ExtensionRules::parse("png=0").is_err());
+        assert!(ExtensionRules::parse("=copy").is_err());
+    }
+}
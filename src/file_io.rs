@@ -8,7 +8,7 @@ use serde_json::{from_reader, to_writer_pretty};
 use serde::{Deserialize, Serialize};
 
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum DataType{
     Directory(Option<PathBuf>),
     Number(Option<i32>),
@@ -16,7 +16,7 @@ pub enum DataType{
     String(Option<String>),
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ProgramData {
     data: HashMap<String, DataType>,
 }
@@ -0,0 +1,130 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Check whether the file is one of the excluded paths or is inside one of them.
+pub fn is_excluded<F: AsRef<Path>, E: AsRef<Path>>(file: F, excluded: &[E]) -> bool {
+    excluded.iter().any(|e| file.as_ref().starts_with(e))
+}
+
+/// File or directory in the selection, relative to the selected directory.
+pub struct Entry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+impl Entry {
+    /// Depth of the entry below the selected directory, starting from 0.
+    pub fn depth(&self) -> usize {
+        self.path.components().count().saturating_sub(1)
+    }
+}
+
+/// Files and subdirectories of a directory that the user can exclude from compressing.
+pub struct FileSelection {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    excluded: BTreeSet<PathBuf>,
+}
+
+impl FileSelection {
+    /// Read every file and subdirectory of the directory. Nothing is excluded at first.
+    pub fn new<D: AsRef<Path>>(dir: D) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        read_entries(dir.as_ref(), dir.as_ref(), &mut entries)?;
+        Ok(FileSelection {
+            dir: dir.as_ref().to_path_buf(),
+            entries,
+            excluded: BTreeSet::new(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Entries sorted by path, so every directory comes right before its contents.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Excluded entries. Entries inside an excluded directory are not listed.
+    pub fn excluded(&self) -> Vec<PathBuf> {
+        self.excluded.iter()
+            .filter(|p| !p.ancestors().skip(1).any(|a| self.excluded.contains(a)))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_excluded<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.excluded.contains(path.as_ref())
+    }
+
+    /// Check whether the entry is excluded because a directory containing it is excluded.
+    pub fn is_parent_excluded<P: AsRef<Path>>(&self, path: P) -> bool {
+        path.as_ref().ancestors().skip(1).any(|a| self.excluded.contains(a))
+    }
+
+    pub fn set_excluded<P: AsRef<Path>>(&mut self, path: P, to_exclude: bool) {
+        match to_exclude {
+            true => self.excluded.insert(path.as_ref().to_path_buf()),
+            false => self.excluded.remove(path.as_ref()),
+        };
+    }
+
+    /// Include every entry again.
+    pub fn clear(&mut self) {
+        self.excluded.clear();
+    }
+}
+
+fn read_entries(root: &Path, dir: &Path, entries: &mut Vec<Entry>) -> io::Result<()> {
+    let mut path_list = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect::<Vec<_>>();
+    path_list.sort();
+    for path in path_list {
+        let is_dir = path.is_dir();
+        if let Ok(p) = path.strip_prefix(root) {
+            entries.push(Entry { path: p.to_path_buf(), is_dir });
+        }
+        if is_dir {
+            read_entries(root, &path, entries)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_selection_test(){
+        let test_dir = PathBuf::from("test_file_selection");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        fs::create_dir_all(test_dir.join("album")).unwrap();
+        fs::write(test_dir.join("album").join("1.jpg"), "").unwrap();
+        fs::write(test_dir.join("cover.jpg"), "").unwrap();
+
+        let mut selection = FileSelection::new(&test_dir).unwrap();
+        let entries = selection.entries().iter().map(|e| (e.path.to_path_buf(), e.is_dir, e.depth())).collect::<Vec<_>>();
+        assert_eq!(entries, vec![
+            (PathBuf::from("album"), true, 0),
+            (PathBuf::from("album/1.jpg"), false, 1),
+            (PathBuf::from("cover.jpg"), false, 0),
+        ]);
+
+        selection.set_excluded("album/1.jpg", true);
+        selection.set_excluded("album", true);
+        assert!(selection.is_parent_excluded("album/1.jpg"));
+        assert_eq!(selection.excluded(), vec![PathBuf::from("album")]);
+        assert!(is_excluded(test_dir.join("album").join("1.jpg"), &[test_dir.join("album")]));
+        assert!(!is_excluded(test_dir.join("cover.jpg"), &[test_dir.join("album")]));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
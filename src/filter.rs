@@ -0,0 +1,157 @@
+use image::{imageops, Rgb, RgbImage};
+
+/// Radius of the neighborhood that the denoising filter averages.
+const DENOISE_RADIUS: i64 = 2;
+
+/// Reduce the noise of the image with a bilateral filter, which averages each pixel with its neighbors of similar color,
+/// so that the noise is smoothed while the edges are kept.
+/// `strength` from 0 to 1 is how different the colors averaged together may be.
+pub fn denoise(image: &RgbImage, strength: f32) -> RgbImage {
+    let strength = strength.clamp(0., 1.);
+    if strength == 0. {
+        return image.clone();
+    }
+    let range_sigma = 8. + 32. * strength;
+    let spatial_sigma = 1.5f32;
+    let spatial_weights = (-DENOISE_RADIUS..=DENOISE_RADIUS)
+        .flat_map(|dy| (-DENOISE_RADIUS..=DENOISE_RADIUS).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| (dx, dy, (-((dx * dx + dy * dy) as f32) / (2. * spatial_sigma * spatial_sigma)).exp()))
+        .collect::<Vec<_>>();
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let center = image.get_pixel(x, y).0;
+        let mut total = [0f32; 3];
+        let mut total_weight = 0.;
+        for (dx, dy, spatial_weight) in &spatial_weights {
+            let nx = (x as i64 + dx).clamp(0, width - 1) as u32;
+            let ny = (y as i64 + dy).clamp(0, height - 1) as u32;
+            let neighbor = image.get_pixel(nx, ny).0;
+            let distance = (0..3).map(|i| (neighbor[i] as f32 - center[i] as f32).powi(2)).sum::<f32>();
+            let weight = spatial_weight * (-distance / (2. * range_sigma * range_sigma)).exp();
+            for i in 0..3 {
+                total[i] += neighbor[i] as f32 * weight;
+            }
+            total_weight += weight;
+        }
+        Rgb(total.map(|t| (t / total_weight).round().clamp(0., 255.) as u8))
+    })
+}
+
+/// Sharpen the image with an unsharp mask, which adds the difference between the image and its blurred copy.
+/// `amount` is how much of the difference is added, like 0.5 for 50%, and `radius` is the sigma of the blur in pixels.
+pub fn sharpen(image: &RgbImage, amount: f32, radius: f32) -> RgbImage {
+    if amount <= 0. || radius <= 0. {
+        return image.clone();
+    }
+    let blurred = imageops::blur(image, radius);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let original = image.get_pixel(x, y).0;
+        let blurred = blurred.get_pixel(x, y).0;
+        Rgb([0, 1, 2].map(|i| (original[i] as f32 + (original[i] as f32 - blurred[i] as f32) * amount).round().clamp(0., 255.) as u8))
+    })
+}
+
+/// Balance the white and stretch the levels of the image, for faded scans and old photos.
+///
+/// The channels are scaled so that their means are equal, as the average of a scene is usually gray,
+/// then the darkest and brightest 0.5% of the pixels are made black and white.
+pub fn auto_enhance(image: &RgbImage) -> RgbImage {
+    let pixel_count = (image.width() as f64 * image.height() as f64).max(1.);
+    let mut sums = [0f64; 3];
+    for pixel in image.pixels() {
+        for i in 0..3 {
+            sums[i] += pixel[i] as f64;
+        }
+    }
+    let means = sums.map(|s| s / pixel_count);
+    let gray = means.iter().sum::<f64>() / 3.;
+    // A scene of one color is not balanced to gray entirely.
+    let gains = means.map(|m| if m > 0. { (gray / m).clamp(0.5, 2.) } else { 1. });
+    let balanced = RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = image.get_pixel(x, y).0;
+        Rgb([0, 1, 2].map(|i| (pixel[i] as f64 * gains[i]).round().clamp(0., 255.) as u8))
+    });
+
+    let mut histogram = [0usize; 256];
+    for pixel in balanced.pixels() {
+        histogram[pixel.0.iter().map(|c| *c as usize).sum::<usize>() / 3] += 1;
+    }
+    let clip_count = (pixel_count * 0.005) as usize;
+    let find_level = |levels: Vec<usize>| {
+        let mut count = 0;
+        levels.into_iter().find(|l| {
+            count += histogram[*l];
+            count > clip_count
+        }).unwrap_or(0)
+    };
+    let low = find_level((0..256).collect()) as f64;
+    let high = find_level((0..256).rev().collect()) as f64;
+    // Images of almost one level would only be posterized.
+    if high - low < 16. {
+        return balanced;
+    }
+    let scale = 255. / (high - low);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = balanced.get_pixel(x, y).0;
+        Rgb(pixel.map(|c| ((c as f64 - low) * scale).round().clamp(0., 255.) as u8))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mean absolute difference between horizontally adjacent pixels.
+    fn get_roughness(image: &RgbImage) -> f32 {
+        let mut total = 0.;
+        for (x, y, pixel) in image.enumerate_pixels().filter(|(x, _, _)| x + 1 < image.width()) {
+            total += (pixel[0] as f32 - image.get_pixel(x + 1, y)[0] as f32).abs();
+        }
+        total / (image.width() * image.height()) as f32
+    }
+
+    #[test]
+    fn denoise_test(){
+        // Noise on two flat halves, with an edge between them.
+        let noisy = RgbImage::from_fn(64, 64, |x, y| {
+            let base = if x < 32 { 60 } else { 200 };
+            let noise = ((x.wrapping_mul(2654435761) ^ y.wrapping_mul(40503)) >> 7) as u8 % 21;
+            Rgb([base + noise, base + noise, base + noise])
+        });
+        let denoised = denoise(&noisy, 0.5);
+        assert!(get_roughness(&denoised) < get_roughness(&noisy) / 2.);
+        // The edge stays sharp.
+        assert!(denoised.get_pixel(31, 10)[0] < 100 && denoised.get_pixel(32, 10)[0] > 180);
+        assert_eq!(denoise(&noisy, 0.), noisy);
+    }
+
+    #[test]
+    fn sharpen_test(){
+        // A soft edge, like a downscaled one.
+        let soft = RgbImage::from_fn(64, 16, |x, _| {
+            let value = (x as i32 - 28).clamp(0, 8) as u8 * 20 + 60;
+            Rgb([value, value, value])
+        });
+        let sharpened = sharpen(&soft, 1., 1.5);
+        assert!(get_roughness(&sharpened) > get_roughness(&soft));
+        // The flat areas are left as they are.
+        assert_eq!(sharpened.get_pixel(5, 5), soft.get_pixel(5, 5));
+        assert_eq!(sharpen(&soft, 0., 1.), soft);
+    }
+
+    #[test]
+    fn auto_enhance_test(){
+        // A faded, yellowish scan.
+        let faded = RgbImage::from_fn(32, 32, |x, _| {
+            let value = 90 + x as u8 * 3;
+            Rgb([value + 20, value + 15, value - 20])
+        });
+        let enhanced = auto_enhance(&faded);
+        let means = (0..3).map(|i| enhanced.pixels().map(|p| p[i] as f32).sum::<f32>() / 1024.).collect::<Vec<_>>();
+        assert!((means[0] - means[2]).abs() < 8.);
+        assert!(enhanced.get_pixel(0, 0)[1] < 20 && enhanced.get_pixel(31, 0)[1] > 235);
+
+        let flat = RgbImage::from_pixel(8, 8, Rgb([120, 120, 120]));
+        assert_eq!(auto_enhance(&flat), flat);
+    }
+}
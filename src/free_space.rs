@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::preflight::get_free_space;
+
+/// Interval between reading the free space of the same directory again.
+pub const FREE_SPACE_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Free space of the volumes of directories, read again after [`FREE_SPACE_REFRESH_INTERVAL`].
+///
+/// The window is repainted every frame, so the volumes are not read on every frame.
+#[derive(Default)]
+pub struct FreeSpaceMonitor {
+    free_spaces: HashMap<PathBuf, (Instant, Option<u64>)>,
+}
+
+impl FreeSpaceMonitor {
+    /// Get the free space in bytes of the volume the directory is, or will be, created on.
+    /// Returns `None` when the volume cannot be read.
+    pub fn get<D: AsRef<Path>>(&mut self, dir: D) -> Option<u64> {
+        match self.free_spaces.get(dir.as_ref()) {
+            Some((read_at, free_space)) if read_at.elapsed() < FREE_SPACE_REFRESH_INTERVAL => *free_space,
+            _ => {
+                let free_space = get_free_space(dir.as_ref()).ok();
+                self.free_spaces.insert(dir.as_ref().to_path_buf(), (Instant::now(), free_space));
+                free_space
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_space_monitor_test(){
+        let mut monitor = FreeSpaceMonitor::default();
+        assert!(monitor.get("test_free_space_monitor/not_created").is_some());
+        assert_eq!(monitor.free_spaces.len(), 1);
+        monitor.get("test_free_space_monitor/not_created");
+        assert_eq!(monitor.free_spaces.len(), 1);
+    }
+}
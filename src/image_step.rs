@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops;
+use image::imageops::FilterType;
+use image::RgbImage;
+use crate::decode::{open_image, DecodeOptions};
+use crate::encoder::StageTimes;
+use crate::filter::{auto_enhance, denoise, sharpen};
+use crate::output_spec::OutputSpec;
+use crate::saliency::blur_background;
+
+/// Step that changes an image after it is resized and before it is encoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageStep {
+    /// Reduce the noise by the strength from 0 to 1, since noise takes much of the size of a JPEG file.
+    Denoise(f32),
+
+    /// Balance the white and stretch the levels, to clean up scanned documents and old photos.
+    AutoEnhance,
+
+    /// Blur the background around the subjects by the strength from 0 to 1, so the encoder spends the bits on the subjects.
+    BlurBackground(f32),
+
+    /// Sharpen the edges softened by resizing with an unsharp mask of the amount, like 0.5 for 50%, and the radius in pixels.
+    Sharpen { amount: f32, radius: f32 },
+}
+
+impl ImageStep {
+    pub fn apply(&self, image: RgbImage) -> RgbImage {
+        match self {
+            ImageStep::Denoise(strength) => denoise(&image, *strength),
+            ImageStep::AutoEnhance => auto_enhance(&image),
+            ImageStep::BlurBackground(strength) => blur_background(&image, *strength),
+            ImageStep::Sharpen { amount, radius } => sharpen(&image, *amount, *radius),
+        }
+    }
+}
+
+/// Directory of the thumbnails in each output directory.
+pub const THUMBNAIL_DIR: &str = "thumbs";
+
+/// Longest side of the thumbnails in pixels by default.
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+
+/// Quality of the thumbnails, which are too small to show the artifacts of a lower quality.
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Image saved by [`process_image`].
+#[derive(Debug)]
+pub struct ProcessedImage {
+    pub path: PathBuf,
+    /// Thumbnail of the image saved as a JPEG file, if a thumbnail size is given.
+    pub thumbnail: Option<PathBuf>,
+    /// Image resized for each output spec and saved as a lossless PNG file, in the order of the specs.
+    pub variants: Vec<PathBuf>,
+    /// Time spent decoding and processing the image, and encoding the thumbnail.
+    pub times: StageTimes,
+}
+
+/// Open the image with the decode options, resize it by the ratio, apply the steps in order, and save the result as a lossless PNG file
+/// named after the image in the directory, to be encoded without resizing.
+/// With a thumbnail size, a thumbnail whose longest side is at most the size is also made from the same pixels
+/// and saved as `{stem}_thumb.jpg` in the directory.
+/// The result is also resized to the width of each output spec and saved as `{stem}{suffix}.png`,
+/// so every size is made from a single decode. A result narrower than the width is saved as it is.
+///
+/// # Error
+/// - When the image cannot be opened, or the result cannot be saved.
+pub fn process_image<I: AsRef<Path>, D: AsRef<Path>>(image_path: I, dir: D, size_ratio: f32, steps: &[ImageStep], thumbnail_size: Option<u32>, output_specs: &[OutputSpec], decode_options: &DecodeOptions) -> Result<ProcessedImage, Box<dyn Error>> {
+    let start = Instant::now();
+    let image = open_image(image_path.as_ref(), decode_options)?;
+    let decode = start.elapsed();
+    let width = ((image.width() as f32 * size_ratio).round() as u32).max(1);
+    let height = ((image.height() as f32 * size_ratio).round() as u32).max(1);
+    let image = match (width, height) == (image.width(), image.height()) {
+        true => image,
+        false => image.resize_exact(width, height, FilterType::Triangle),
+    };
+    let image = steps.iter().fold(image.to_rgb8(), |image, step| step.apply(image));
+    let mut times = StageTimes { decode, steps: start.elapsed() - decode, ..StageTimes::default() };
+    let stem = image_path.as_ref().file_stem().unwrap_or_default();
+    let thumbnail = match thumbnail_size {
+        Some(size) => {
+            let encode_start = Instant::now();
+            let mut file_name = stem.to_os_string();
+            file_name.push("_thumb.jpg");
+            let thumbnail = dir.as_ref().join(file_name);
+            save_thumbnail(&image, size, &thumbnail)?;
+            times.encode = encode_start.elapsed();
+            Some(thumbnail)
+        }
+        None => None,
+    };
+    let mut variants = Vec::new();
+    for spec in output_specs {
+        let steps_start = Instant::now();
+        let width = spec.width.min(image.width());
+        let height = ((image.height() as f32 * width as f32 / image.width() as f32).round() as u32).max(1);
+        let variant = match (width, height) == image.dimensions() {
+            true => image.clone(),
+            false => imageops::resize(&image, width, height, FilterType::Triangle),
+        };
+        times.steps += steps_start.elapsed();
+        let mut file_name = stem.to_os_string();
+        file_name.push(&spec.suffix);
+        file_name.push(".png");
+        let path = dir.as_ref().join(file_name);
+        variant.save(&path)?;
+        variants.push(path);
+    }
+    let mut file_name = stem.to_os_string();
+    file_name.push(".png");
+    let output = dir.as_ref().join(file_name);
+    image.save(&output)?;
+    Ok(ProcessedImage { path: output, thumbnail, variants, times })
+}
+
+/// Save a thumbnail of the image whose longest side is at most the size as a JPEG file.
+/// Images smaller than the size are not enlarged.
+fn save_thumbnail(image: &RgbImage, size: u32, path: &Path) -> Result<(), Box<dyn Error>> {
+    let scale = (size as f32 / image.width().max(image.height()) as f32).min(1.);
+    let width = ((image.width() as f32 * scale).round() as u32).max(1);
+    let height = ((image.height() as f32 * scale).round() as u32).max(1);
+    let thumbnail = imageops::thumbnail(image, width, height);
+    JpegEncoder::new_with_quality(BufWriter::new(File::create(path)?), THUMBNAIL_QUALITY).encode_image(&thumbnail)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn process_image_test(){
+        let test_dir = PathBuf::from("test_process_image");
+        fs::create_dir_all(&test_dir).unwrap();
+        RgbImage::from_fn(40, 20, |x, _| image::Rgb([(x * 6) as u8, 0, 0])).save(test_dir.join("a.bmp")).unwrap();
+        let output = process_image(test_dir.join("a.bmp"), test_dir.join("out"), 0.5, &[], None, &[], &DecodeOptions::default());
+        assert!(output.is_err());
+
+        fs::create_dir_all(test_dir.join("out")).unwrap();
+        let output = process_image(test_dir.join("a.bmp"), test_dir.join("out"), 0.5, &[ImageStep::Denoise(0.5), ImageStep::AutoEnhance, ImageStep::BlurBackground(0.5), ImageStep::Sharpen { amount: 0.5, radius: 1. }], None, &[], &DecodeOptions::default()).unwrap();
+        assert_eq!(output.path, test_dir.join("out").join("a.png"));
+        assert_eq!(output.thumbnail, None);
+        assert!(!output.times.steps.is_zero());
+        assert!(output.times.encode.is_zero());
+        assert_eq!(image::image_dimensions(&output.path).unwrap(), (20, 10));
+        assert!(process_image(test_dir.join("missing.png"), test_dir.join("out"), 1., &[], None, &[], &DecodeOptions::default()).is_err());
+
+        // The thumbnail fits in the size, and a small image is not enlarged.
+        let output = process_image(test_dir.join("a.bmp"), test_dir.join("thumb"), 1., &[], Some(8), &[], &DecodeOptions::default());
+        assert!(output.is_err());
+        fs::create_dir_all(test_dir.join("thumb")).unwrap();
+        let output = process_image(test_dir.join("a.bmp"), test_dir.join("thumb"), 1., &[], Some(8), &[], &DecodeOptions::default()).unwrap();
+        assert_eq!(output.thumbnail, Some(test_dir.join("thumb").join("a_thumb.jpg")));
+        assert_eq!(image::image_dimensions(test_dir.join("thumb").join("a_thumb.jpg")).unwrap(), (8, 4));
+        assert_eq!(image::image_dimensions(&output.path).unwrap(), (40, 20));
+        let output = process_image(test_dir.join("a.bmp"), test_dir.join("thumb"), 1., &[], Some(DEFAULT_THUMBNAIL_SIZE), &[], &DecodeOptions::default()).unwrap();
+        assert_eq!(image::image_dimensions(output.thumbnail.unwrap()).unwrap(), (40, 20));
+
+        // Each size is made from the processed image, without enlarging it.
+        fs::create_dir_all(test_dir.join("sizes")).unwrap();
+        let specs = OutputSpec::parse_list("10, 30", "-{w}w").unwrap();
+        let output = process_image(test_dir.join("a.bmp"), test_dir.join("sizes"), 0.5, &[], None, &specs, &DecodeOptions::default()).unwrap();
+        assert_eq!(output.variants, [test_dir.join("sizes").join("a-10w.png"), test_dir.join("sizes").join("a-30w.png")]);
+        assert_eq!(image::image_dimensions(&output.variants[0]).unwrap(), (10, 5));
+        assert_eq!(image::image_dimensions(&output.variants[1]).unwrap(), (20, 10));
+        assert_eq!(image::image_dimensions(&output.path).unwrap(), (20, 10));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::event::{try_send_event, Event, EventSink, Severity, Stage, Verbosity};
+
+/// Get the temporary folder that compressed images are written to before they replace the originals.
+///
+/// The folder is a sibling of the original folder,
+/// so moving the compressed images into place is a rename on the same volume.
+pub fn get_temp_dir<O: AsRef<Path>>(origin: O) -> io::Result<PathBuf> {
+    let origin = origin.as_ref();
+    match (origin.parent(), origin.file_name()) {
+        (Some(parent), Some(name)) => {
+            let mut temp_name = OsString::from(".");
+            temp_name.push(name);
+            temp_name.push("_compressing");
+            Ok(parent.join(temp_name))
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot compress the root directory in place")),
+    }
+}
+
+/// Replace the original files with their compressed images in `temp_dir`.
+///
+/// Every compressed image is named `{stem}.jpg` in the mirrored subdirectory of `temp_dir`.
+/// It is renamed over the original, or next to it if the original has another extension,
+/// and the original is removed or renamed to `{file name}.bak` when `keep_backup` is set.
+/// Originals without a compressed image (copied or failed files) are left untouched.
+///
+/// Returns the number of replaced files.
+pub fn replace_originals<O: AsRef<Path>, T: AsRef<Path>>(origin: O, temp_dir: T, file_list: &[PathBuf], keep_backup: bool, sender: &Option<Arc<dyn EventSink>>, verbosity: Verbosity) -> usize {
+    let mut replaced_count = 0;
+    for file in file_list {
+        let compressed_file = match file.strip_prefix(origin.as_ref()) {
+            Ok(p) => temp_dir.as_ref().join(p).with_extension("jpg"),
+            Err(_) => continue,
+        };
+        if !compressed_file.is_file() {
+            continue;
+        }
+        match replace_original(file, &compressed_file, keep_backup) {
+            Ok(_) => replaced_count += 1,
+            Err(e) => {
+                let message = format!("Cannot replace the original file {}: {}", file.display(), e);
+                try_send_event(sender, verbosity, Event::new(Stage::Replace, message).with_severity(Severity::Error));
+            }
+        }
+    }
+    replaced_count
+}
+
+fn replace_original(original: &Path, compressed_file: &Path, keep_backup: bool) -> io::Result<()> {
+    let target = original.with_extension("jpg");
+    if keep_backup {
+        let mut backup = original.as_os_str().to_os_string();
+        backup.push(".bak");
+        fs::rename(original, backup)?;
+    } else if target != original {
+        fs::remove_file(original)?;
+    }
+    fs::rename(compressed_file, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(test_name: &str) -> (PathBuf, PathBuf, Vec<PathBuf>) {
+        let origin = PathBuf::from(test_name).join("origin");
+        let temp_dir = get_temp_dir(&origin).unwrap();
+        if Path::new(test_name).is_dir() {
+            fs::remove_dir_all(test_name).unwrap();
+        }
+        fs::create_dir_all(origin.join("sub")).unwrap();
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+
+        let file_list = vec![origin.join("a.png"), origin.join("sub").join("b.jpg"), origin.join("c.txt")];
+        for file in &file_list {
+            fs::write(file, "original").unwrap();
+        }
+        fs::write(temp_dir.join("a.jpg"), "compressed").unwrap();
+        fs::write(temp_dir.join("sub").join("b.jpg"), "compressed").unwrap();
+        fs::write(temp_dir.join("c.txt"), "original").unwrap();
+        (origin, temp_dir, file_list)
+    }
+
+    #[test]
+    fn temp_dir_test(){
+        assert_eq!(get_temp_dir("parent/origin").unwrap(), PathBuf::from("parent/.origin_compressing"));
+        assert!(get_temp_dir("/").is_err());
+    }
+
+    #[test]
+    fn replace_originals_test(){
+        let test_name = "test_replace_originals";
+        let (origin, temp_dir, file_list) = setup(test_name);
+
+        assert_eq!(replace_originals(&origin, &temp_dir, &file_list, false, &None, Verbosity::Normal), 2);
+        assert!(!origin.join("a.png").exists());
+        assert_eq!(fs::read_to_string(origin.join("a.jpg")).unwrap(), "compressed");
+        assert_eq!(fs::read_to_string(origin.join("sub").join("b.jpg")).unwrap(), "compressed");
+        assert_eq!(fs::read_to_string(origin.join("c.txt")).unwrap(), "original");
+        fs::remove_dir_all(test_name).unwrap();
+    }
+
+    #[test]
+    fn replace_originals_with_backup_test(){
+        let test_name = "test_replace_originals_with_backup";
+        let (origin, temp_dir, file_list) = setup(test_name);
+
+        assert_eq!(replace_originals(&origin, &temp_dir, &file_list, true, &None, Verbosity::Normal), 2);
+        assert_eq!(fs::read_to_string(origin.join("a.png.bak")).unwrap(), "original");
+        assert_eq!(fs::read_to_string(origin.join("a.jpg")).unwrap(), "compressed");
+        assert_eq!(fs::read_to_string(origin.join("sub").join("b.jpg.bak")).unwrap(), "original");
+        assert_eq!(fs::read_to_string(origin.join("sub").join("b.jpg")).unwrap(), "compressed");
+        fs::remove_dir_all(test_name).unwrap();
+    }
+}
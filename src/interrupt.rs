@@ -0,0 +1,66 @@
+use std::io;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::AtomicBool;
+
+/// Flag set by the signal handler, shared with the pipelines that it stops.
+static STOP_FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Set the returned flag when the process receives SIGINT (Ctrl-C) or SIGTERM, instead of exiting at once,
+/// so that a [`Pipeline`](crate::Pipeline) with the flag finishes the files in progress and stops.
+/// A second signal exits at once, as the handler is reset after the first one.
+///
+/// # Error
+/// - When the handler cannot be installed, or the signals cannot be trapped on this system.
+pub fn trap_stop_signals() -> io::Result<Arc<AtomicBool>> {
+    let flag = STOP_FLAG.get_or_init(|| Arc::new(AtomicBool::new(false))).clone();
+    install_handler()?;
+    Ok(flag)
+}
+
+#[cfg(unix)]
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    // Only an atomic store is done here, since the handler may interrupt any code.
+    if let Some(flag) = STOP_FLAG.get() {
+        flag.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+fn install_handler() -> io::Result<()> {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESETHAND;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn install_handler() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Signals cannot be trapped on this system"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use super::*;
+
+    #[test]
+    fn trap_stop_signals_test(){
+        let flag = trap_stop_signals().unwrap();
+        assert!(!flag.load(Ordering::SeqCst));
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+        assert!(flag.load(Ordering::SeqCst));
+        // The handler is reset after the first signal, so install it again for the other tests.
+        assert!(Arc::ptr_eq(&flag, &trap_stop_signals().unwrap()));
+        flag.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::report::{FileReport, FileStatus};
+
+/// Totals of a finished job, kept in the job history to show how much the jobs saved over time.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct JobRecord {
+    /// Unix time in seconds when the job finished.
+    pub finished_at: i64,
+    pub origin: PathBuf,
+    pub file_count: usize,
+    pub failed_count: usize,
+
+    /// Size of the files that were compressed or copied, before and after.
+    /// Failed files are left out, as they have no output.
+    pub original_size: u64,
+    pub compressed_size: u64,
+}
+
+impl JobRecord {
+    /// Make the record of the job from its report.
+    pub fn from_report<P: AsRef<Path>>(origin: P, report: &[FileReport], finished_at: i64) -> Self {
+        let mut record = JobRecord {
+            finished_at,
+            origin: origin.as_ref().to_path_buf(),
+            file_count: report.len(),
+            failed_count: 0,
+            original_size: 0,
+            compressed_size: 0,
+        };
+        for row in report {
+            match (&row.status, row.compressed_size) {
+                (FileStatus::Failed(_), _) | (_, None) => record.failed_count += 1,
+                (_, Some(size)) => {
+                    record.original_size += row.original_size;
+                    record.compressed_size += size;
+                }
+            }
+        }
+        record
+    }
+
+    /// Bytes saved by the job, negative if the outputs are larger.
+    pub fn saved_size(&self) -> i64 {
+        self.original_size as i64 - self.compressed_size as i64
+    }
+
+    /// Compressed size over the original size, or `None` if nothing was compressed.
+    pub fn ratio(&self) -> Option<f64> {
+        match self.original_size {
+            0 => None,
+            s => Some(self.compressed_size as f64 / s as f64),
+        }
+    }
+
+    /// Share of the files that failed, or `None` if the job had no files.
+    pub fn failure_rate(&self) -> Option<f64> {
+        match self.file_count {
+            0 => None,
+            c => Some(self.failed_count as f64 / c as f64),
+        }
+    }
+}
+
+/// Add the record to the end of the history file of JSON lines.
+///
+/// # Error
+/// - When the history file cannot be created or written.
+pub fn append_job_record<P: AsRef<Path>>(history_path: P, record: &JobRecord) -> io::Result<()> {
+    let history_path = history_path.as_ref();
+    if let Some(p) = history_path.parent() {
+        fs::create_dir_all(p)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(history_path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)
+}
+
+/// Load the records of the history file, in the order the jobs finished.
+/// A missing file is an empty history, and lines that cannot be read are skipped.
+///
+/// # Error
+/// - When the history file exists but cannot be read.
+pub fn load_job_history<P: AsRef<Path>>(history_path: P) -> io::Result<Vec<JobRecord>> {
+    let file = match File::open(history_path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut history = Vec::new();
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(r) => history.push(r),
+            Err(e) => warn!("Cannot read a record of the job history: {}", e),
+        }
+    }
+    history.sort_by_key(|r: &JobRecord| r.finished_at);
+    Ok(history)
+}
+
+/// Get the total bytes saved by the jobs up to each job, with the time it finished.
+pub fn get_cumulative_savings(history: &[JobRecord]) -> Vec<(i64, i64)> {
+    history.iter()
+        .scan(0, |total, r| {
+            *total += r.saved_size();
+            Some((r.finished_at, *total))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_record(finished_at: i64, original_size: u64, compressed_size: u64, failed_count: usize) -> JobRecord {
+        JobRecord { finished_at, origin: PathBuf::from("origin"), file_count: 10, failed_count, original_size, compressed_size }
+    }
+
+    #[test]
+    fn job_history_test(){
+        let test_dir = PathBuf::from("test_job_history");
+        let history_path = test_dir.join("data").join("jobs.jsonl");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        assert!(load_job_history(&history_path).unwrap().is_empty());
+
+        let first = make_record(2000, 1000, 400, 0);
+        let second = make_record(1000, 500, 600, 5);
+        append_job_record(&history_path, &first).unwrap();
+        append_job_record(&history_path, &second).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&history_path).unwrap();
+        writeln!(file, "not a record").unwrap();
+        let history = load_job_history(&history_path).unwrap();
+        assert_eq!(history, vec![second.clone(), first.clone()]);
+        assert_eq!(get_cumulative_savings(&history), vec![(1000, -100), (2000, 500)]);
+
+        assert_eq!(first.ratio(), Some(0.4));
+        assert_eq!(second.failure_rate(), Some(0.5));
+        assert_eq!(make_record(0, 0, 0, 0).ratio(), None);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
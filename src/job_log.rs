@@ -0,0 +1,111 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::Local;
+
+/// Number of job logs kept in the logs folder. The oldest ones are removed when a job starts.
+pub const MAX_JOB_LOGS: usize = 50;
+
+/// File with every message of a job, kept after the program is closed to look into the failures.
+///
+/// Each line is written at once, so the log is complete up to a crash.
+#[derive(Debug)]
+pub struct JobLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl JobLog {
+    /// Create a log named by the current time, like `job_20220301_080000.log`, in the folder,
+    /// and remove the oldest logs so that at most `max_logs` are kept with the new one.
+    ///
+    /// # Error
+    /// - When the folder or the log file cannot be created.
+    pub fn create<P: AsRef<Path>>(log_dir: P, max_logs: usize) -> io::Result<Self> {
+        let log_dir = log_dir.as_ref();
+        fs::create_dir_all(log_dir)?;
+        let stem = Local::now().format("job_%Y%m%d_%H%M%S").to_string();
+        // Jobs of several tabs can start in the same second.
+        let mut index = 1;
+        let (path, file) = loop {
+            let path = match index {
+                1 => log_dir.join(format!("{}.log", stem)),
+                i => log_dir.join(format!("{}_{}.log", stem, i)),
+            };
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(f) => break (path, f),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => index += 1,
+                Err(e) => return Err(e),
+            }
+        };
+        rotate_logs(log_dir, max_logs)?;
+        Ok(JobLog { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the message as a line of the log.
+    ///
+    /// # Error
+    /// - When the log file cannot be written.
+    pub fn write_line(&mut self, message: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", message)?;
+        self.file.flush()
+    }
+}
+
+/// Remove the oldest job logs in the folder so that at most `max_logs` are kept.
+/// The names of the logs start with the time, so the oldest ones come first by name.
+///
+/// # Error
+/// - When the folder cannot be read or a log cannot be removed.
+pub fn rotate_logs<P: AsRef<Path>>(log_dir: P, max_logs: usize) -> io::Result<()> {
+    let mut logs = fs::read_dir(log_dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "log"))
+        .filter(|p| p.file_name().is_some_and(|n| n.to_string_lossy().starts_with("job_")))
+        .collect::<Vec<_>>();
+    if logs.len() <= max_logs {
+        return Ok(());
+    }
+    logs.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    for path in &logs[..logs.len() - max_logs] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_log_test(){
+        let test_dir = PathBuf::from("test_job_log");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        fs::create_dir_all(&test_dir).unwrap();
+        for name in ["job_20220101_120000.log", "job_20220201_120000.log", "job_20220301_120000.log", "notes.log"] {
+            File::create(test_dir.join(name)).unwrap();
+        }
+
+        let mut first = JobLog::create(&test_dir, 3).unwrap();
+        first.write_line("[compress] a.jpg").unwrap();
+        first.write_line("[archive] done").unwrap();
+        assert_eq!(fs::read_to_string(first.path()).unwrap(), "[compress] a.jpg\n[archive] done\n");
+        let second = JobLog::create(&test_dir, 3).unwrap();
+        assert_ne!(first.path(), second.path());
+
+        assert!(!test_dir.join("job_20220101_120000.log").exists());
+        assert!(!test_dir.join("job_20220201_120000.log").exists());
+        assert!(test_dir.join("job_20220301_120000.log").exists());
+        assert!(test_dir.join("notes.log").exists());
+        assert!(first.path().exists() && second.path().exists());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
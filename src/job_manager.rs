@@ -0,0 +1,376 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use crate::event::Event;
+use crate::pipeline::Pipeline;
+
+/// Identifier of a job, unique in its [`JobManager`].
+pub type JobId = u64;
+
+/// Niceness of the threads of low priority jobs, when the [`JobManager`] lowers their OS priority.
+pub const LOW_PRIORITY_NICENESS: i32 = 10;
+
+/// Priority of a job in a [`JobManager`]. Queued jobs of higher priority are started first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// Create a [`Priority`] from the str, `low`, `normal` or `high`. Unknown strings fall back to the default.
+    pub fn from(priority_str: &str) -> Self {
+        match priority_str {
+            "low" => Priority::Low,
+            "high" => Priority::High,
+            _ => Priority::default(),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::Low => write!(f, "low"),
+            Priority::Normal => write!(f, "normal"),
+            Priority::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Manager that runs several [`Pipeline`] jobs at once within a shared budget of threads.
+///
+/// Jobs are started by their [`Priority`], and in the order they are submitted among the same priority,
+/// as long as the threads of the running jobs and the next job fit in the budget.
+/// Lower priority jobs wait while a higher priority job waits for threads, so the threads of the finished jobs go to it first.
+/// A job that asks for more threads than the whole budget is run with the whole budget.
+/// Each job sends its events to its own channel, and its result to its [`JobHandle`].
+///
+/// # Examples
+/// ```no_run
+/// use ImageCompressor::{JobManager, Pipeline};
+///
+/// let manager = JobManager::new(8);
+/// let mut handles = Vec::new();
+/// for (origin, dest) in [("album_a", "out_a"), ("album_b", "out_b")] {
+///     let mut pipeline = Pipeline::new(origin, dest);
+///     pipeline.set_thread_count(4);
+///     handles.push(manager.submit(pipeline));
+/// }
+///
+/// for handle in handles {
+///     for event in handle.events() {
+///         println!("job {}: {}", handle.id(), event);
+///     }
+///     if let Err(e) = handle.wait() {
+///         println!("Cannot complete the job: {}", e);
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct JobManager {
+    state: Arc<(Mutex<ManagerState>, Condvar)>,
+}
+
+struct ManagerState {
+    thread_budget: u32,
+    used_threads: u32,
+    to_lower_os_priority: bool,
+    next_id: JobId,
+    queue: VecDeque<QueuedJob>,
+    running: Vec<JobId>,
+}
+
+struct QueuedJob {
+    id: JobId,
+    priority: Priority,
+    pipeline: Pipeline,
+    result_sender: mpsc::Sender<Result<(), String>>,
+}
+
+/// Handle of a job submitted to a [`JobManager`], to receive its events and its result.
+pub struct JobHandle {
+    id: JobId,
+    events: Receiver<Event>,
+    result: Receiver<Result<(), String>>,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// Get the events of the job. Iterating over them ends when the job is done or canceled.
+    pub fn events(&self) -> &Receiver<Event> {
+        &self.events
+    }
+
+    /// Wait until the job is done and return its result.
+    ///
+    /// # Error
+    /// - When the job fails, or it is canceled before it starts.
+    pub fn wait(self) -> Result<(), String> {
+        self.result.recv().unwrap_or_else(|_| Err(String::from("The job was canceled.")))
+    }
+
+    /// Get the result of the job without waiting, or `None` while it is queued or running.
+    ///
+    /// # Error
+    /// - When the job fails, or it is canceled before it starts.
+    pub fn try_result(&self) -> Option<Result<(), String>> {
+        match self.result.try_recv() {
+            Ok(r) => Some(r),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(String::from("The job was canceled."))),
+        }
+    }
+}
+
+impl JobManager {
+    /// Create a new `JobManager` that runs jobs with at most the number of threads in total.
+    pub fn new(thread_budget: u32) -> Self {
+        JobManager {
+            state: Arc::new((Mutex::new(ManagerState {
+                thread_budget: thread_budget.max(1),
+                used_threads: 0,
+                to_lower_os_priority: false,
+                next_id: 0,
+                queue: VecDeque::new(),
+                running: Vec::new(),
+            }), Condvar::new())),
+        }
+    }
+
+    /// Queue the job with the normal priority, and start it when the threads are available.
+    /// The sender of the pipeline is replaced with the channel of the returned handle.
+    pub fn submit(&self, pipeline: Pipeline) -> JobHandle {
+        self.submit_with_priority(pipeline, Priority::Normal)
+    }
+
+    /// Queue the job with the priority, and start it when the threads are available.
+    /// The sender of the pipeline is replaced with the channel of the returned handle.
+    pub fn submit_with_priority(&self, mut pipeline: Pipeline, priority: Priority) -> JobHandle {
+        let (event_sender, events) = mpsc::channel();
+        let (result_sender, result) = mpsc::channel();
+        pipeline.set_sender(event_sender);
+        let id = {
+            let mut state = self.lock();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.queue.push_back(QueuedJob { id, priority, pipeline, result_sender });
+            id
+        };
+        self.start_queued();
+        JobHandle { id, events, result }
+    }
+
+    /// Remove the job from the queue if it has not started. Returns whether it is removed.
+    /// Running jobs are not stopped.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut state = self.lock();
+        let count = state.queue.len();
+        state.queue.retain(|j| j.id != id);
+        let is_removed = state.queue.len() < count;
+        if is_removed {
+            self.state.1.notify_all();
+        }
+        is_removed
+    }
+
+    /// Number of jobs waiting for threads.
+    pub fn queued_count(&self) -> usize {
+        self.lock().queue.len()
+    }
+
+    /// Identifiers of the running jobs.
+    pub fn running_jobs(&self) -> Vec<JobId> {
+        self.lock().running.to_vec()
+    }
+
+    /// Change the budget of threads. Running jobs keep their threads, and queued jobs are started within the new budget.
+    pub fn set_thread_budget(&self, thread_budget: u32) {
+        self.lock().thread_budget = thread_budget.max(1);
+        self.start_queued();
+    }
+
+    /// Set whether to run the threads of low priority jobs with a lower OS priority,
+    /// so that they do not slow down the other programs. It applies to the jobs started after this.
+    /// It is supported only on Linux, and ignored elsewhere.
+    pub fn set_lower_os_priority(&self, to_lower: bool) {
+        self.lock().to_lower_os_priority = to_lower;
+    }
+
+    /// Block until every submitted job is done or canceled.
+    pub fn wait_all(&self) {
+        let (_, condvar) = &*self.state;
+        let state = self.lock();
+        let _state = condvar.wait_while(state, |s| !s.queue.is_empty() || !s.running.is_empty());
+    }
+
+    /// Start the queued jobs of the highest priority while their threads fit in the budget.
+    fn start_queued(&self) {
+        let mut state = self.lock();
+        while let Some(index) = get_next_index(&state.queue) {
+            let job = &state.queue[index];
+            let thread_count = job.pipeline.get_thread_count().clamp(1, state.thread_budget);
+            if state.used_threads + thread_count > state.thread_budget {
+                break;
+            }
+            let QueuedJob { id, priority, mut pipeline, result_sender } = state.queue.remove(index).unwrap();
+            pipeline.set_thread_count(thread_count);
+            state.used_threads += thread_count;
+            state.running.push(id);
+            let to_lower_os_priority = state.to_lower_os_priority && priority == Priority::Low;
+
+            let manager = self.clone();
+            thread::spawn(move || {
+                // The threads of the pipeline are spawned from this thread, so they inherit its niceness.
+                if to_lower_os_priority {
+                    lower_thread_priority();
+                }
+                // Release the threads even when the job panics, or the queue would wait for them forever.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| pipeline.run().map_err(|e| e.to_string())))
+                    .unwrap_or_else(|_| Err(String::from("The job panicked.")));
+                // The handle may be dropped by the caller who does not wait for the result.
+                let _ = result_sender.send(result);
+                manager.finish(id, thread_count);
+            });
+        }
+    }
+
+    /// Release the threads of the finished job and start the next ones.
+    fn finish(&self, id: JobId, thread_count: u32) {
+        {
+            let mut state = self.lock();
+            state.used_threads -= thread_count;
+            state.running.retain(|r| *r != id);
+        }
+        self.start_queued();
+        self.state.1.notify_all();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ManagerState> {
+        // A job never panics while holding the lock, so the state is still consistent when it is poisoned.
+        self.state.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Get the index of the job to start next, the first of the jobs of the highest priority.
+fn get_next_index(queue: &VecDeque<QueuedJob>) -> Option<usize> {
+    // The maximum is the last one of the same priority, so the first one is the maximum in reverse.
+    queue.iter().enumerate().rev().max_by_key(|(_, j)| j.priority).map(|(i, _)| i)
+}
+
+/// Lower the OS priority of the current thread to [`LOW_PRIORITY_NICENESS`].
+/// Threads spawned from it afterwards inherit the priority.
+#[cfg(target_os = "linux")]
+pub(crate) fn lower_thread_priority() {
+    // On Linux, the niceness is of each thread, and 0 is the calling thread.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, LOW_PRIORITY_NICENESS) } != 0 {
+        log::warn!("Cannot lower the priority of the job: {}", std::io::Error::last_os_error());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn lower_thread_priority() {}
+
+impl Default for JobManager {
+    /// Create a `JobManager` with a thread for each core.
+    fn default() -> Self {
+        JobManager::new(thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use super::*;
+
+    #[test]
+    fn job_manager_test(){
+        let test_dir = PathBuf::from("test_job_manager");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        // The first job downloads from this listener, and waits for the response until the queue is checked.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/blocker.png", listener.local_addr().unwrap());
+        let manager = JobManager::new(2);
+        let mut handles = Vec::new();
+        for (name, priority) in [("a", Priority::Normal), ("b", Priority::Low), ("c", Priority::High), ("d", Priority::Normal)] {
+            let origin = test_dir.join("origin").join(name);
+            fs::create_dir_all(&origin).unwrap();
+            image::RgbImage::new(8, 8).save(origin.join(format!("{}.png", name))).unwrap();
+            let mut pipeline = Pipeline::new(&origin, test_dir.join("dest").join(name));
+            pipeline.set_thread_count(2);
+            if name == "a" {
+                pipeline.set_url_list(vec![url.to_string()]);
+            }
+            handles.push(manager.submit_with_priority(pipeline, priority));
+            if name == "a" {
+                assert!(handles[0].events().recv().unwrap().message.starts_with("Downloading 1/1"));
+            }
+        }
+
+        // Each job takes the whole budget, so the others wait in the queue while the first one is blocked.
+        assert_eq!(manager.running_jobs(), [handles[0].id()]);
+        assert_eq!(manager.queued_count(), 3);
+        let canceled = handles.pop().unwrap();
+        assert!(manager.cancel(canceled.id()));
+        assert!(!manager.cancel(canceled.id()));
+        assert!(!manager.cancel(handles[0].id()));
+        assert_eq!(manager.queued_count(), 2);
+        assert_eq!(canceled.try_result(), Some(Err(String::from("The job was canceled."))));
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).unwrap();
+            assert!(read > 0);
+            request.extend_from_slice(&buffer[..read]);
+        }
+        stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+        drop(stream);
+        manager.wait_all();
+        assert_eq!(manager.queued_count(), 0);
+        assert!(manager.running_jobs().is_empty());
+
+        // The waiting jobs start by their priority, and one at a time, so their first events are in the same order.
+        let first_times = handles.iter()
+            .map(|h| h.events().iter().next().unwrap().time)
+            .collect::<Vec<_>>();
+        assert!(first_times[0] < first_times[2] && first_times[2] < first_times[1]);
+        for (handle, name) in handles.into_iter().zip(["a", "b", "c"]) {
+            handle.wait().unwrap();
+            assert!(test_dir.join("dest").join(name).join(format!("{}.jpg", name)).is_file());
+        }
+        assert!(!test_dir.join("dest").join("d").exists());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn priority_test(){
+        let queue = [(0, Priority::Normal), (1, Priority::Low), (2, Priority::High), (3, Priority::High)].into_iter()
+            .map(|(id, priority)| QueuedJob { id, priority, pipeline: Pipeline::new("origin", "dest"), result_sender: mpsc::channel().0 })
+            .collect::<VecDeque<_>>();
+        assert_eq!(get_next_index(&queue), Some(2));
+        assert_eq!(get_next_index(&queue.into_iter().filter(|j| j.priority != Priority::High).collect()), Some(0));
+        assert_eq!(get_next_index(&VecDeque::new()), None);
+        assert_eq!(Priority::default(), Priority::Normal);
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            assert_eq!(Priority::from(&priority.to_string()), priority);
+        }
+        assert_eq!(Priority::from("urgent"), Priority::Normal);
+    }
+}
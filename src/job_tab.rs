@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use chrono::{DateTime, Local};
+use log::{error, warn};
+use crate::event::{Event, FileProgress};
+use crate::file_io::{DataType, ProgramData};
+use crate::job_log::JobLog;
+use crate::job_manager::JobHandle;
+use crate::{format_elapsed, ORIGIN_DIR_KEY, TIME_FORMAT};
+
+/// Tab of the window with its own job, so that several jobs can be set up and run at once.
+///
+/// The settings of the tab shown in the window are kept in the fields of the [`App`](crate::App),
+/// and are moved into [`JobTab::settings`] when another tab is shown.
+#[derive(Default)]
+pub(crate) struct JobTab {
+    /// Settings of the tab in the form of the history file, while the tab is not shown.
+    pub(crate) settings: ProgramData,
+
+    /// URLs of the images to download, which are not saved in the history file.
+    pub(crate) url_list: String,
+
+    /// Messages of the jobs of the tab, in the order they are received.
+    pub(crate) messages: Vec<String>,
+
+    /// Time when the scheduled job of the tab starts.
+    pub(crate) scheduled_start: Option<DateTime<Local>>,
+
+    /// Files done so far by the last job of the tab.
+    pub(crate) progress: Option<FileProgress>,
+
+    handle: Option<JobHandle>,
+    job_start: Option<SystemTime>,
+    log: Option<JobLog>,
+}
+
+impl JobTab {
+    /// Create a tab with a copy of the settings.
+    pub(crate) fn new(settings: ProgramData) -> Self {
+        JobTab {
+            settings,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the job of the tab is queued or running.
+    pub(crate) fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// Keep the handle of the job submitted to the [`JobManager`](crate::JobManager) to receive its messages.
+    /// The messages of the job are also written to the log until it finishes.
+    pub(crate) fn start(&mut self, handle: JobHandle, log: Option<JobLog>) {
+        self.job_start = Some(SystemTime::now());
+        self.progress = None;
+        self.log = log;
+        self.push_message(format!("Job started at {}", Local::now().format(TIME_FORMAT)));
+        if let Some(path) = self.log.as_ref().map(|l| l.path().to_path_buf()) {
+            self.push_message(format!("The messages are also written to {}", path.display()));
+        }
+        self.handle = Some(handle);
+    }
+
+    /// Add the message of the event, stamped with the time from the start of the job to when the event was made.
+    fn receive_event(&mut self, event: Event) {
+        let elapsed = self.job_start.and_then(|t| event.time.duration_since(t).ok()).unwrap_or_default();
+        self.push_message(format!("[{}] {}", format_elapsed(elapsed), event));
+        if event.progress.is_some() {
+            self.progress = event.progress;
+        }
+    }
+
+    fn push_message(&mut self, message: String) {
+        if let Some(log) = &mut self.log {
+            if let Err(e) = log.write_line(&message) {
+                warn!("Cannot write the log of the job to {}: {}", log.path().display(), e);
+                self.log = None;
+            }
+        }
+        self.messages.push(message);
+    }
+
+    /// Receive every pending message of the job.
+    /// When the job is finished, its result is added and the handle is dropped.
+    ///
+    /// Returns whether the job succeeded when it has just finished, or `None` otherwise.
+    pub(crate) fn receive_events(&mut self) -> Option<bool> {
+        let handle = self.handle.take()?;
+        while let Ok(event) = handle.events().try_recv() {
+            self.receive_event(event);
+        }
+        let result = match handle.try_result() {
+            Some(r) => r,
+            None => {
+                self.handle = Some(handle);
+                return None;
+            }
+        };
+        // Messages sent just before the result are not missed.
+        while let Ok(event) = handle.events().try_recv() {
+            self.receive_event(event);
+        }
+        let elapsed = self.job_start.and_then(|t| t.elapsed().ok()).unwrap_or_default();
+        if let Err(e) = &result {
+            error!("Cannot complete the job: {}", e);
+            self.push_message(format!("Cannot complete the job! {}", e));
+        }
+        self.push_message(format!("Job finished at {}, took {}", Local::now().format(TIME_FORMAT), format_elapsed(elapsed)));
+        self.log = None;
+        Some(result.is_ok())
+    }
+
+    /// Original folder in the settings of the tab.
+    pub(crate) fn origin_dir(&self) -> Option<PathBuf> {
+        match self.settings.get_data(ORIGIN_DIR_KEY) {
+            Some(DataType::Directory(Some(p))) => Some(p.to_path_buf()),
+            _ => None,
+        }
+    }
+}
+
+/// Get the title of the tab at the index, the name of its original folder if it is set.
+pub(crate) fn get_tab_title(origin_dir: Option<&Path>, index: usize) -> String {
+    match origin_dir.and_then(|p| p.file_name()) {
+        Some(n) => n.to_string_lossy().to_string(),
+        None => format!("Job {}", index + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::event::Stage;
+    use super::*;
+
+    #[test]
+    fn job_tab_test(){
+        assert_eq!(get_tab_title(Some(Path::new("photos/2022")), 0), "2022");
+        assert_eq!(get_tab_title(Some(Path::new("")), 1), "Job 2");
+        assert_eq!(get_tab_title(None, 2), "Job 3");
+
+        let mut settings = ProgramData::new();
+        settings.set_data(ORIGIN_DIR_KEY, DataType::Directory(Some(PathBuf::from("photos"))));
+        let mut tab = JobTab::new(settings);
+        assert_eq!(tab.origin_dir(), Some(PathBuf::from("photos")));
+        assert!(!tab.is_running());
+        assert_eq!(tab.receive_events(), None);
+        assert!(tab.messages.is_empty());
+
+        // Messages are stamped with the time when their events were made, not when they are received.
+        let start = SystemTime::now();
+        tab.job_start = Some(start);
+        let progress = FileProgress { done: 1, total: 2, file: PathBuf::from("a.png"), elapsed: Duration::from_millis(300) };
+        let event = Event {
+            time: start + Duration::from_secs(65),
+            ..Event::with_progress(Stage::Compress, "Compress complete! File: a.jpg", progress.clone())
+        };
+        tab.receive_event(event);
+        assert_eq!(tab.messages, ["[00:01:05] [compress] Compress complete! File: a.jpg"]);
+        assert_eq!(tab.progress, Some(progress));
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// State of a source file recorded in the [`Journal`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileState {
+    Started,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    source: PathBuf,
+    state: FileState,
+}
+
+/// Journal of the files compressed by a run, to resume the run where it stopped when it is interrupted.
+///
+/// Each state change is appended as a JSON line and written immediately, so the journal is complete up to the last file
+/// even when the program is killed. A broken last line, written when the program was killed, is ignored.
+/// Files are recorded by their paths relative to the original directory.
+pub struct Journal {
+    origin: PathBuf,
+    writer: Mutex<File>,
+    done: HashSet<PathBuf>,
+    interrupted: Vec<PathBuf>,
+}
+
+impl Journal {
+    /// Open the journal at the path and read the states recorded by an earlier run, or create a new one.
+    ///
+    /// # Error
+    /// - When the journal cannot be read or written.
+    pub fn open<J: AsRef<Path>, O: AsRef<Path>>(journal_path: J, origin: O) -> io::Result<Self> {
+        let mut states = HashMap::new();
+        if journal_path.as_ref().is_file() {
+            for line in fs::read_to_string(journal_path.as_ref())?.lines() {
+                match serde_json::from_str::<JournalEntry>(line) {
+                    Ok(entry) => {
+                        states.insert(entry.source, entry.state);
+                    }
+                    Err(e) => warn!("Skipped a broken line of the journal {}: {}", journal_path.as_ref().display(), e),
+                }
+            }
+        }
+        let origin = origin.as_ref().to_path_buf();
+        let done = states.iter()
+            .filter(|(_, s)| **s == FileState::Done)
+            .map(|(f, _)| origin.join(f))
+            .collect();
+        let mut interrupted = states.iter()
+            .filter(|(_, s)| **s == FileState::Started)
+            .map(|(f, _)| origin.join(f))
+            .collect::<Vec<_>>();
+        interrupted.sort();
+        let writer = OpenOptions::new().create(true).append(true).open(journal_path)?;
+        Ok(Journal { origin, writer: Mutex::new(writer), done, interrupted })
+    }
+
+    /// Whether the file was completed by an earlier run.
+    pub fn is_done<P: AsRef<Path>>(&self, file: P) -> bool {
+        self.done.contains(file.as_ref())
+    }
+
+    /// Number of the files completed by earlier runs.
+    pub fn done_count(&self) -> usize {
+        self.done.len()
+    }
+
+    /// Files that an earlier run started but did not complete, so their outputs may be broken.
+    pub fn interrupted(&self) -> &[PathBuf] {
+        &self.interrupted
+    }
+
+    /// Append the state of the file to the journal.
+    ///
+    /// # Error
+    /// - When the file is not in the original directory.
+    /// - When the journal cannot be written.
+    pub fn record<P: AsRef<Path>>(&self, file: P, state: FileState) -> io::Result<()> {
+        let source = file.as_ref().strip_prefix(&self.origin)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "The file is not in the original folder!"))?
+            .to_path_buf();
+        let mut line = serde_json::to_string(&JournalEntry { source, state })?;
+        line.push('\n');
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer.write_all(line.as_bytes())
+    }
+}
+
+/// Get the path of the journal of the destination, like `.dest.journal.jsonl` beside it,
+/// so that the journal is not archived with the destination. It is in the destination when it has no parent.
+pub fn get_journal_path<D: AsRef<Path>>(dest: D) -> PathBuf {
+    let dest = dest.as_ref();
+    match (dest.parent(), dest.file_name()) {
+        (Some(parent), Some(name)) => parent.join(format!(".{}.journal.jsonl", name.to_string_lossy())),
+        _ => dest.join(".journal.jsonl"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_test(){
+        let test_dir = PathBuf::from("test_journal");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let origin = test_dir.join("origin");
+        fs::create_dir_all(&test_dir).unwrap();
+        let journal_path = get_journal_path(test_dir.join("dest"));
+        assert_eq!(journal_path, test_dir.join(".dest.journal.jsonl"));
+
+        let journal = Journal::open(&journal_path, &origin).unwrap();
+        for (file, state) in [("a.png", FileState::Started), ("a.png", FileState::Done), ("b.png", FileState::Started), ("c.png", FileState::Started), ("c.png", FileState::Failed)] {
+            journal.record(origin.join(file), state).unwrap();
+        }
+        assert!(journal.record(test_dir.join("d.png"), FileState::Done).is_err());
+        drop(journal);
+        // A line cut off when the program was killed.
+        OpenOptions::new().append(true).open(&journal_path).unwrap().write_all(b"{\"source\":\"d.pn").unwrap();
+
+        let journal = Journal::open(&journal_path, &origin).unwrap();
+        assert!(journal.is_done(origin.join("a.png")));
+        assert!(!journal.is_done(origin.join("c.png")));
+        assert_eq!(journal.done_count(), 1);
+        assert_eq!(journal.interrupted(), [origin.join("b.png")]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
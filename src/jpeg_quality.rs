@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Luminance quantization table of quality 50 from the JPEG standard, in zigzag order like the DQT segments.
+const STANDARD_LUMINANCE_TABLE: [u16; 64] = [
+    16, 11, 12, 14, 12, 10, 16, 14,
+    13, 14, 18, 17, 16, 19, 24, 40,
+    26, 24, 22, 22, 24, 49, 35, 37,
+    29, 40, 58, 51, 61, 60, 57, 51,
+    56, 55, 64, 72, 92, 78, 64, 68,
+    87, 69, 55, 56, 80, 109, 81, 87,
+    95, 98, 103, 104, 103, 62, 77, 113,
+    121, 112, 100, 103, 92, 101, 101, 99,
+];
+
+/// Estimate the quality (1-100) that the JPEG file was encoded with, from its luminance quantization table.
+///
+/// The table is compared with the standard table scaled the way libjpeg scales it for a quality,
+/// so the estimate is exact for the files encoded with the standard tables, like those of libjpeg and most cameras.
+/// Encoders with other tables, like mozjpeg, are estimated a little lower than their quality setting.
+/// Returns `None` if the file is not a JPEG file or has no luminance table.
+///
+/// # Error
+/// - When the file cannot be read.
+pub fn estimate_jpeg_quality<P: AsRef<Path>>(path: P) -> io::Result<Option<u8>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut marker = [0u8; 2];
+    if reader.read_exact(&mut marker).is_err() || marker != [0xFF, 0xD8] {
+        return Ok(None);
+    }
+    loop {
+        if reader.read_exact(&mut marker).is_err() || marker[0] != 0xFF {
+            return Ok(None);
+        }
+        // Start of scan and end of image come after every table.
+        if marker[1] == 0xDA || marker[1] == 0xD9 {
+            return Ok(None);
+        }
+        // Standalone markers and fill bytes have no length.
+        if (0xD0..=0xD7).contains(&marker[1]) || marker[1] == 0x01 || marker[1] == 0xFF {
+            continue;
+        }
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length)?;
+        let length = (u16::from_be_bytes(length) as usize).saturating_sub(2);
+        let mut segment = vec![0u8; length];
+        reader.read_exact(&mut segment)?;
+        if marker[1] == 0xDB {
+            if let Some(table) = get_luminance_table(&segment) {
+                return Ok(Some(get_quality(&table)));
+            }
+        }
+    }
+}
+
+/// Get the table of ID 0, the luminance table, from the tables of the DQT segment.
+fn get_luminance_table(segment: &[u8]) -> Option<[u16; 64]> {
+    let mut rest = segment;
+    while let Some((&info, data)) = rest.split_first() {
+        let is_16_bit = info >> 4 == 1;
+        let size = if is_16_bit { 128 } else { 64 };
+        let values = data.get(..size)?;
+        if info & 0x0F == 0 {
+            let mut table = [0u16; 64];
+            for (i, value) in table.iter_mut().enumerate() {
+                *value = match is_16_bit {
+                    true => u16::from_be_bytes([values[i * 2], values[i * 2 + 1]]),
+                    false => values[i] as u16,
+                };
+            }
+            return Some(table);
+        }
+        rest = &data[size..];
+    }
+    None
+}
+
+/// Get the quality whose scaled standard table is closest to the table.
+fn get_quality(table: &[u16; 64]) -> u8 {
+    (1..=100u8)
+        .min_by_key(|q| {
+            get_scaled_table(*q).iter()
+                .zip(table)
+                .map(|(a, b)| (*a as i64 - *b as i64).abs())
+                .sum::<i64>()
+        })
+        .unwrap_or(100)
+}
+
+/// Scale the standard luminance table for the quality, as libjpeg does.
+fn get_scaled_table(quality: u8) -> [u16; 64] {
+    let scale = match quality as u32 {
+        q if q < 50 => 5000 / q,
+        q => 200 - q * 2,
+    };
+    STANDARD_LUMINANCE_TABLE.map(|v| ((v as u32 * scale + 50) / 100).clamp(1, 255) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::RgbImage;
+    use super::*;
+
+    #[test]
+    fn estimate_jpeg_quality_test(){
+        let test_dir = PathBuf::from("test_estimate_jpeg_quality");
+        fs::create_dir_all(&test_dir).unwrap();
+        let image = RgbImage::from_fn(16, 16, |x, y| image::Rgb([(x * 16) as u8, (y * 16) as u8, 0]));
+        for quality in [30, 60, 85, 100] {
+            let path = test_dir.join(format!("{}.jpg", quality));
+            JpegEncoder::new_with_quality(File::create(&path).unwrap(), quality).encode_image(&image).unwrap();
+            let estimated = estimate_jpeg_quality(&path).unwrap().unwrap();
+            assert!((estimated as i32 - quality as i32).abs() <= 1, "{} estimated as {}", quality, estimated);
+        }
+        image.save(test_dir.join("a.png")).unwrap();
+        assert_eq!(estimate_jpeg_quality(test_dir.join("a.png")).unwrap(), None);
+        assert!(estimate_jpeg_quality(test_dir.join("missing.jpg")).is_err());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
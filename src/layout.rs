@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Datelike, Local};
+use exif::{In, Reader, Tag, Value};
+use image::ImageFormat;
+use crate::path_util::sanitize_path;
+
+/// How the output files are placed in the destination directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Layout {
+    /// Mirror the original directories.
+    #[default]
+    Mirrored,
+    /// Put the files into numbered folders of a fixed size, regardless of the original directories.
+    Buckets,
+    /// Put the files into `YYYY/MM` folders by the date the photos were taken.
+    ByDate,
+    /// Put every file directly into the destination directory.
+    Flat,
+}
+
+impl Layout {
+    /// Create a [`Layout`] from the str. Unknown strings fall back to the default.
+    pub fn from(layout_str: &str) -> Self {
+        match layout_str {
+            "buckets" => Layout::Buckets,
+            "date" => Layout::ByDate,
+            "flat" => Layout::Flat,
+            _ => Layout::default(),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Mirrored => write!(f, "mirrored"),
+            Layout::Buckets => write!(f, "buckets"),
+            Layout::ByDate => write!(f, "date"),
+            Layout::Flat => write!(f, "flat"),
+        }
+    }
+}
+
+/// Get the output path of every file relative to the output directory, mirroring the original directory.
+///
+/// The paths keep the original extension, which the compressor changes to `.jpg` for images.
+pub fn get_mirrored_paths<O: AsRef<Path>>(origin: O, file_list: &[PathBuf]) -> BTreeMap<PathBuf, PathBuf> {
+    file_list.iter()
+        .filter_map(|f| Some((f.to_path_buf(), f.strip_prefix(origin.as_ref()).ok()?.to_path_buf())))
+        .collect()
+}
+
+/// Get the output path of every file relative to the output directory,
+/// putting at most `bucket_size` files into each numbered folder regardless of the original directories.
+///
+/// The files are sorted by path, and the folders are numbered from `0001`
+/// with enough digits that they also sort by their number.
+pub fn get_bucket_paths(file_list: &[PathBuf], bucket_size: usize) -> BTreeMap<PathBuf, PathBuf> {
+    let bucket_size = bucket_size.max(1);
+    let mut sorted_list = file_list.to_vec();
+    sorted_list.sort();
+    let bucket_count = sorted_list.len().div_ceil(bucket_size);
+    let width = bucket_count.to_string().len().max(4);
+    sorted_list.into_iter()
+        .enumerate()
+        .filter_map(|(i, f)| {
+            let bucket = format!("{:0width$}", i / bucket_size + 1, width = width);
+            let name = f.file_name()?.to_os_string();
+            Some((f, Path::new(&bucket).join(name)))
+        })
+        .collect()
+}
+
+/// Get the output path of every file relative to the output directory, directly in the output directory.
+///
+/// Files with the same name in different directories collide, so the names should be numbered with [`number_duplicates`].
+pub fn get_flat_paths(file_list: &[PathBuf]) -> BTreeMap<PathBuf, PathBuf> {
+    file_list.iter()
+        .filter_map(|f| Some((f.to_path_buf(), PathBuf::from(f.file_name()?))))
+        .collect()
+}
+
+/// Get the output path of every file relative to the output directory, in the `YYYY/MM` folder of the date it was taken.
+///
+/// The date is read from the EXIF data, or the modified time of the file when there is none.
+/// Files without either date are put into the `unknown` folder.
+pub fn get_date_paths(file_list: &[PathBuf]) -> BTreeMap<PathBuf, PathBuf> {
+    file_list.iter()
+        .filter_map(|f| {
+            let dir = match get_taken_month(f) {
+                Some((year, month)) => Path::new(&format!("{:04}", year)).join(format!("{:02}", month)),
+                None => PathBuf::from("unknown"),
+            };
+            Some((f.to_path_buf(), dir.join(f.file_name()?)))
+        })
+        .collect()
+}
+
+/// Get the year and month the photo was taken, falling back to the modified time of the file.
+fn get_taken_month(file_path: &Path) -> Option<(u16, u8)> {
+    get_exif_month(file_path).or_else(|| {
+        let modified: DateTime<Local> = file_path.metadata().ok()?.modified().ok()?.into();
+        Some((modified.year() as u16, modified.month() as u8))
+    })
+}
+
+/// Get the year and month of the original date and time in the EXIF data of the image.
+fn get_exif_month(file_path: &Path) -> Option<(u16, u8)> {
+    let exif = Reader::new().read_from_container(&mut BufReader::new(File::open(file_path).ok()?)).ok()?;
+    let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY)
+        .or_else(|| exif.get_field(Tag::DateTime, In::PRIMARY))?;
+    match &field.value {
+        Value::Ascii(v) => {
+            let date_time = exif::DateTime::from_ascii(v.first()?).ok()?;
+            Some((date_time.year, date_time.month)).filter(|(_, m)| (1..=12).contains(m))
+        }
+        _ => None,
+    }
+}
+
+/// Default pattern of the numbered names given to duplicate outputs.
+pub const DEFAULT_DUPLICATE_PATTERN: &str = "{name} ({n})";
+
+/// Pattern of duplicate names that keeps the original extension in the name, e.g. `photo_png.jpg`.
+pub const EXTENSION_DUPLICATE_PATTERN: &str = "{name}_{ext}";
+
+/// Give every colliding source file except the first one of its group a new output name, and return the renamed files.
+///
+/// In the pattern, `{name}` is the output name without its extension, `{ext}` is the extension of the original file,
+/// and `{n}` is the number, counted from 1. A number is skipped when its name is already used in the same output directory.
+/// Names are compared the way the compressor names the outputs, so `a.2021` and `a` are both `a.jpg`.
+///
+/// # Error
+/// - When a new name is already used and the pattern has no `{n}` to try another one,
+///   e.g. `{name}_{ext}` for files of the same name and extension in different folders.
+pub fn number_duplicates(output_paths: &mut BTreeMap<PathBuf, PathBuf>, collisions: &[Vec<PathBuf>], pattern: &str) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut used = output_paths.values()
+        .filter_map(|p| Some((p.parent()?.to_path_buf(), Path::new(p.file_stem()?).with_extension("jpg").into_os_string())))
+        .collect::<HashSet<_>>();
+    let mut renamed = Vec::new();
+    for source in collisions.iter().flat_map(|files| files.iter().skip(1)) {
+        let output = match output_paths.get(source) {
+            Some(o) => o.to_path_buf(),
+            None => continue,
+        };
+        let (parent, stem) = match (output.parent(), output.file_stem()) {
+            (Some(p), Some(s)) => (p.to_path_buf(), s.to_string_lossy().to_string()),
+            _ => continue,
+        };
+        let extension = source.extension().unwrap_or_default().to_string_lossy().to_string();
+        let named = pattern.replace("{name}", &stem).replace("{ext}", &extension);
+        let mut n = 1;
+        let mut name = loop {
+            let new_stem = OsString::from(named.replace("{n}", &n.to_string()));
+            let mut key = new_stem.clone();
+            key.push(".jpg");
+            if used.insert((parent.to_path_buf(), key)) {
+                break new_stem;
+            }
+            if !named.contains("{n}") {
+                return Err(format!("{} would still have the same name as another file with the pattern \"{}\". Add {{n}} to the pattern.", source.display(), pattern));
+            }
+            n += 1;
+        };
+        if let Some(e) = output.extension() {
+            name.push(".");
+            name.push(e);
+        }
+        output_paths.insert(source.to_path_buf(), parent.join(&name));
+        renamed.push((source.to_path_buf(), parent.join(name)));
+    }
+    Ok(renamed)
+}
+
+/// Check that the pattern of duplicate names makes a different file name in the same directory for each duplicate.
+///
+/// # Error
+/// - When the pattern contains neither `{n}` nor `{ext}`.
+/// - When the pattern contains a path separator or `..`, which would write the output into another directory.
+pub fn check_duplicate_pattern(pattern: &str) -> Result<(), String> {
+    if !pattern.contains("{n}") && !pattern.contains("{ext}") {
+        return Err(String::from("The pattern of duplicate names must contain {n} or {ext}."));
+    }
+    if pattern.contains(['/', '\\']) || pattern.contains("..") {
+        return Err(String::from("The pattern of duplicate names must not contain a path separator or \"..\"."));
+    }
+    Ok(())
+}
+
+/// Get the output paths of an example file relative to the output directory with the settings, to preview them before a job.
+/// Returns the output of the file, and the output of another file of the same name when duplicate names are renamed.
+/// The other file is in another folder in flat folders, and has another extension in the same folder otherwise.
+/// Images are written as `.jpg` files.
+///
+/// # Error
+/// - When the pattern of duplicate names is invalid, as checked by [`check_duplicate_pattern`].
+pub fn preview_output_paths<O: AsRef<Path>, E: AsRef<Path>>(origin: O, example: E, layout: Layout, bucket_size: usize, to_sanitize: bool, duplicate_pattern: Option<&str>) -> Result<(PathBuf, Option<PathBuf>), String> {
+    let example = example.as_ref().to_path_buf();
+    let file_list = [example.to_path_buf()];
+    let output_paths = match layout {
+        Layout::Mirrored => get_mirrored_paths(origin, &file_list),
+        Layout::Buckets => get_bucket_paths(&file_list, bucket_size),
+        Layout::ByDate => get_date_paths(&file_list),
+        Layout::Flat => get_flat_paths(&file_list),
+    };
+    let mut output = output_paths.get(&example).cloned().unwrap_or_else(|| PathBuf::from(example.file_name().unwrap_or_default()));
+    if to_sanitize {
+        output = sanitize_path(&output);
+    }
+    output.set_extension("jpg");
+
+    // Flat folders always number the duplicates, as their names collide across the original folders.
+    let pattern = match (duplicate_pattern, layout) {
+        (None, Layout::Flat) => Some(DEFAULT_DUPLICATE_PATTERN),
+        (p, _) => p,
+    };
+    let pattern = match pattern {
+        Some(p) => p,
+        None => return Ok((output, None)),
+    };
+    check_duplicate_pattern(pattern)?;
+    let duplicate = match layout {
+        Layout::Flat => example.with_file_name(".duplicate").join(example.file_name().unwrap_or_default()),
+        _ if example.extension().map(|e| e.eq_ignore_ascii_case("png")).unwrap_or(false) => example.with_extension("jpg"),
+        _ => example.with_extension("png"),
+    };
+    let mut output_paths = BTreeMap::from([(example.to_path_buf(), output.to_path_buf()), (duplicate.to_path_buf(), output.to_path_buf())]);
+    let renamed = number_duplicates(&mut output_paths, &[vec![example, duplicate]], pattern)?;
+    Ok((output, renamed.into_iter().next().map(|(_, o)| o)))
+}
+
+/// Find an image in the directory to preview the output names with, the first one in the order of the paths.
+pub fn find_example_image<D: AsRef<Path>>(dir: D) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(dir).ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    entries.sort();
+    entries.iter()
+        .find(|p| p.is_file() && ImageFormat::from_path(p).is_ok())
+        .cloned()
+        .or_else(|| entries.iter().filter(|p| p.is_dir()).find_map(find_example_image))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use chrono::TimeZone;
+    use exif::Field;
+    use exif::experimental::Writer;
+    use super::*;
+
+    #[test]
+    fn mirrored_paths_test(){
+        let origin = PathBuf::from("origin");
+        let file_list = vec![origin.join("a.png"), origin.join("sub").join("b.png")];
+        let output_paths = get_mirrored_paths(&origin, &file_list);
+        assert_eq!(output_paths[&file_list[0]], PathBuf::from("a.png"));
+        assert_eq!(output_paths[&file_list[1]], PathBuf::from("sub/b.png"));
+    }
+
+    #[test]
+    fn bucket_paths_test(){
+        let origin = PathBuf::from("origin");
+        let file_list = vec![origin.join("c.png"), origin.join("sub").join("a.png"), origin.join("b.png")];
+        let output_paths = get_bucket_paths(&file_list, 2);
+        assert_eq!(output_paths[&origin.join("b.png")], PathBuf::from("0001/b.png"));
+        assert_eq!(output_paths[&origin.join("c.png")], PathBuf::from("0001/c.png"));
+        assert_eq!(output_paths[&origin.join("sub").join("a.png")], PathBuf::from("0002/a.png"));
+    }
+
+    #[test]
+    fn flat_paths_test(){
+        let origin = PathBuf::from("origin");
+        let file_list = vec![origin.join("a.png"), origin.join("sub").join("b.png")];
+        let output_paths = get_flat_paths(&file_list);
+        assert_eq!(output_paths[&file_list[0]], PathBuf::from("a.png"));
+        assert_eq!(output_paths[&file_list[1]], PathBuf::from("b.png"));
+    }
+
+    #[test]
+    fn date_paths_test(){
+        let test_dir = PathBuf::from("test_date_paths");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        fs::create_dir_all(&test_dir).unwrap();
+        let taken = test_dir.join("taken.tif");
+        let field = Field {
+            tag: Tag::DateTimeOriginal,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"2021:03:04 05:06:07".to_vec()]),
+        };
+        let mut writer = Writer::new();
+        writer.push_field(&field);
+        writer.write(&mut File::create(&taken).unwrap(), true).unwrap();
+        let modified = test_dir.join("modified.png");
+        fs::write(&modified, [0u8; 10]).unwrap();
+        File::options().write(true).open(&modified).unwrap()
+            .set_modified(Local.with_ymd_and_hms(2019, 12, 31, 12, 0, 0).unwrap().into()).unwrap();
+
+        let output_paths = get_date_paths(&[taken.to_path_buf(), modified.to_path_buf()]);
+        assert_eq!(output_paths[&taken], PathBuf::from("2021/03/taken.tif"));
+        assert_eq!(output_paths[&modified], PathBuf::from("2019/12/modified.png"));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn layout_test(){
+        for layout in [Layout::Mirrored, Layout::Buckets, Layout::ByDate, Layout::Flat] {
+            assert_eq!(Layout::from(&layout.to_string()), layout);
+        }
+        assert_eq!(Layout::from("unknown"), Layout::Mirrored);
+    }
+
+    #[test]
+    fn preview_output_paths_test(){
+        let origin = PathBuf::from("origin");
+        let example = origin.join("trip").join("a?.png");
+        assert_eq!(preview_output_paths(&origin, &example, Layout::Mirrored, 100, false, None), Ok((PathBuf::from("trip/a?.jpg"), None)));
+        assert_eq!(preview_output_paths(&origin, &example, Layout::Buckets, 100, true, Some("{name}_{n}")),
+                   Ok((PathBuf::from("0001/a_.jpg"), Some(PathBuf::from("0001/a__1.jpg")))));
+        assert_eq!(preview_output_paths(&origin, &example, Layout::Flat, 100, false, None),
+                   Ok((PathBuf::from("a?.jpg"), Some(PathBuf::from("a? (1).jpg")))));
+        assert!(preview_output_paths(&origin, &example, Layout::Flat, 100, false, Some("{name} copy")).is_err());
+        for pattern in ["../{name}_{n}", "{n}/{name}", "{name}\\{n}", "{name}..{n}"] {
+            assert!(preview_output_paths(&origin, &example, Layout::Flat, 100, false, Some(pattern)).is_err(), "{}", pattern);
+        }
+        assert_eq!(preview_output_paths(&origin, &example, Layout::Mirrored, 100, false, Some("{name}_{ext}")),
+                   Ok((PathBuf::from("trip/a?.jpg"), Some(PathBuf::from("trip/a?_jpg.jpg")))));
+        assert_eq!(preview_output_paths(&origin, &example, Layout::Flat, 100, false, Some("{name}_{ext}")),
+                   Ok((PathBuf::from("a?.jpg"), Some(PathBuf::from("a?_png.jpg")))));
+        assert_eq!(check_duplicate_pattern("{name}.{n}"), Ok(()));
+
+        let test_dir = PathBuf::from("test_find_example_image");
+        fs::create_dir_all(test_dir.join("b")).unwrap();
+        fs::write(test_dir.join("note.txt"), "").unwrap();
+        assert_eq!(find_example_image(&test_dir), None);
+        fs::write(test_dir.join("b").join("c.png"), "").unwrap();
+        assert_eq!(find_example_image(&test_dir), Some(test_dir.join("b").join("c.png")));
+        fs::write(test_dir.join("d.jpg"), "").unwrap();
+        assert_eq!(find_example_image(&test_dir), Some(test_dir.join("d.jpg")));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn number_duplicates_test(){
+        let origin = PathBuf::from("origin");
+        let file_list = vec![origin.join("a.jpg"), origin.join("a.png"), origin.join("a.webp"), origin.join("a (1).gif")];
+        let mut output_paths = get_mirrored_paths(&origin, &file_list);
+        let collisions = vec![vec![origin.join("a.jpg"), origin.join("a.png"), origin.join("a.webp")]];
+        let renamed = number_duplicates(&mut output_paths, &collisions, DEFAULT_DUPLICATE_PATTERN).unwrap();
+        assert_eq!(renamed, vec![
+            (origin.join("a.png"), PathBuf::from("a (2).png")),
+            (origin.join("a.webp"), PathBuf::from("a (3).webp")),
+        ]);
+        assert_eq!(output_paths[&origin.join("a.jpg")], PathBuf::from("a.jpg"));
+        assert_eq!(output_paths[&origin.join("a.png")], PathBuf::from("a (2).png"));
+    }
+
+    #[test]
+    fn extension_duplicates_test(){
+        let origin = PathBuf::from("origin");
+        let file_list = vec![origin.join("trip.2021.png"), origin.join("trip.jpg"), origin.join("trip.png"), origin.join("trip_png.gif")];
+        let mut output_paths = get_mirrored_paths(&origin, &file_list);
+        let collisions = vec![vec![origin.join("trip.2021.png"), origin.join("trip.jpg"), origin.join("trip.png")]];
+        let renamed = number_duplicates(&mut output_paths, &collisions, "{name}_{ext}{n}").unwrap();
+        assert_eq!(renamed, vec![
+            (origin.join("trip.jpg"), PathBuf::from("trip_jpg1.jpg")),
+            (origin.join("trip.png"), PathBuf::from("trip_png1.png")),
+        ]);
+
+        let mut output_paths = get_mirrored_paths(&origin, &file_list);
+        let renamed = number_duplicates(&mut output_paths, &collisions[..1], "{name}_{ext}");
+        assert!(renamed.is_err());
+        let mut output_paths = get_mirrored_paths(&origin, &file_list[..3]);
+        let renamed = number_duplicates(&mut output_paths, &collisions, "{name}_{ext}").unwrap();
+        assert_eq!(renamed, vec![
+            (origin.join("trip.jpg"), PathBuf::from("trip_jpg.jpg")),
+            (origin.join("trip.png"), PathBuf::from("trip_png.png")),
+        ]);
+    }
+}
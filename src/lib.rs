@@ -1,70 +1,1401 @@
+mod archive_filter;
+mod archive_progress;
+mod archive_root;
+mod archive_state;
+mod candidate;
+mod cli;
+mod content_class;
+mod decode;
+mod dest_lock;
+mod download;
+mod encoder;
+mod estimate;
+mod event;
+mod extension_rule;
 mod file_io;
+mod file_select;
+mod filter;
+mod free_space;
+mod image_step;
+mod in_place;
+mod interrupt;
+mod job_history;
+mod job_log;
+mod job_manager;
+mod job_tab;
+mod journal;
+mod jpeg_quality;
+mod layout;
+mod metadata;
+mod output_spec;
+mod pdf;
+mod path_util;
+mod pipeline;
+mod portable;
+mod power;
+mod preflight;
+mod quality_metric;
+mod quality_table;
+mod remote;
+mod report;
+#[cfg(feature = "s3")]
+mod s3;
+mod saliency;
+mod schedule;
+mod seven_zip;
+mod seven_zip_download;
+mod shortcut;
+mod sidecar;
+mod tar_writer;
+mod thumbnail;
+mod update;
+mod verify;
+mod webdav;
+mod zip_writer;
 
 use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::error::Error;
+use std::io;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 use eframe::{epi, egui};
-use egui::{Context, Slider, TextEdit, Vec2};
+use egui::plot::{Bar, BarChart, Line, Plot, Value, Values};
+use egui::{Checkbox, Color32, Context, DragValue, Slider, TextEdit, Ui, Vec2, WidgetInfo, WidgetType};
 use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{Local, NaiveTime, TimeZone};
 use std::sync::mpsc;
-use image_compressor::FolderCompressor;
-use zip_archive::{Archiver, get_dir_list_with_depth, Format};
+use log::{error, warn};
+use image_compressor::compressor::Factor;
+use image_compressor::crawler::get_file_list;
+use zip_archive::Format;
 
 use crate::epi::{Frame, Storage};
+use crate::archive_filter::ArchiveFilter;
+use crate::archive_root::ArchiveRoot;
+use crate::extension_rule::ExtensionRules;
 use crate::file_io::{ProgramData, DataType};
+use crate::candidate::{Candidates, DEFAULT_CANDIDATE_QUALITIES};
+use crate::estimate::{estimate_output_size, SizeEstimate};
+use crate::file_select::{is_excluded, FileSelection};
+use crate::free_space::FreeSpaceMonitor;
+use crate::image_step::DEFAULT_THUMBNAIL_SIZE;
+use crate::job_history::{get_cumulative_savings, load_job_history, JobRecord};
+use crate::job_log::{JobLog, MAX_JOB_LOGS};
+use crate::job_manager::lower_thread_priority;
+use crate::job_tab::{get_tab_title, JobTab};
+use crate::download::parse_url_list;
+use crate::layout::{find_example_image, preview_output_paths, DEFAULT_DUPLICATE_PATTERN, EXTENSION_DUPLICATE_PATTERN};
+use crate::metadata::{read_metadata, ImageMetadata};
+use crate::output_spec::{OutputSpec, DEFAULT_OUTPUT_WIDTHS, DEFAULT_SUFFIX_PATTERN};
+use crate::path_util::open_with_default_app;
+use crate::portable::{get_data_path, is_portable};
+use crate::power::{run_power_action, PowerAction, POWER_COUNTDOWN};
+use crate::preflight::{format_size, is_in_place};
+use crate::quality_table::QualityTable;
+use crate::report::find_latest_report;
+use crate::schedule::Schedule;
+use crate::seven_zip::{get_7z_executable_path, SevenZipMethod, SevenZipOptions};
+use crate::seven_zip_download::{download_7z, is_7z_available};
+use crate::shortcut::{filter_commands, Command, Keymap, Shortcuts};
+use crate::tar_writer::{ZstdOptions, DEFAULT_WINDOW_LOG, MAX_WINDOW_LOG};
+use crate::thumbnail::{ThumbnailLoader, MAX_THUMBNAIL_COUNT};
+use crate::update::{fetch_latest_release, is_newer, Release};
+use crate::webdav::{WebDavTarget, WEBDAV_PASSWORD_VAR};
+#[cfg(feature = "s3")]
+use crate::s3::S3Target;
+
+pub use crate::cli::{CliArgs, USAGE};
+pub use crate::decode::{DecodeOptions, ToneMap, DEFAULT_SVG_DPI};
+use crate::encoder::EncoderBackend;
+pub use crate::event::{Event, EventSink, FileProgress, Severity, Stage, Verbosity};
+pub use crate::image_step::ImageStep;
+pub use crate::job_manager::{JobHandle, JobId, JobManager, Priority};
+pub use crate::layout::Layout;
+pub use crate::pipeline::{ArchiveOutput, Pipeline};
+pub use crate::portable::{enable_portable_mode, has_portable_flag};
 
 const ORIGIN_DIR_KEY: &str = "origin_dir";
 const DESTINATION_DIR_KEY: &str = "destination_dir";
 const ARCHIVE_DIR_KEY: &str = "archive_dir";
 const TO_ZIP_KEY: &str = "to_zip";
 const THREAD_COUNT_KEY: &str = "thread_count";
+const PRIORITY_KEY: &str = "priority";
+const QUALITY_KEY: &str = "quality";
+const RESIZE_PERCENT_KEY: &str = "resize_percent";
+const AUTO_BY_SIZE_KEY: &str = "auto_by_size";
+const LIMIT_DIMENSION_KEY: &str = "limit_dimension";
+const MAX_DIMENSION_KEY: &str = "max_dimension";
+const CAP_SOURCE_QUALITY_KEY: &str = "cap_source_quality";
+const COPY_LOW_QUALITY_KEY: &str = "copy_low_quality";
+const CONTENT_AWARE_KEY: &str = "content_aware";
+const DENOISE_KEY: &str = "denoise";
+const DENOISE_STRENGTH_KEY: &str = "denoise_strength";
+const AUTO_ENHANCE_KEY: &str = "auto_enhance";
+const BLUR_BACKGROUND_KEY: &str = "blur_background";
+const BLUR_STRENGTH_KEY: &str = "blur_strength";
+const SHARPEN_KEY: &str = "sharpen";
+const SHARPEN_AMOUNT_KEY: &str = "sharpen_amount";
+const SHARPEN_RADIUS_KEY: &str = "sharpen_radius";
+const TONE_MAP_KEY: &str = "tone_map";
+const EXPOSURE_KEY: &str = "exposure";
+const SVG_DPI_KEY: &str = "svg_dpi";
+const SAVE_THUMBNAILS_KEY: &str = "save_thumbnails";
+const THUMBNAIL_SIZE_KEY: &str = "thumbnail_size";
+const SAVE_SIZES_KEY: &str = "save_sizes";
+const OUTPUT_WIDTHS_KEY: &str = "output_widths";
+const SUFFIX_PATTERN_KEY: &str = "suffix_pattern";
+const LAYOUT_KEY: &str = "layout";
+const CARRY_SIDECARS_KEY: &str = "carry_sidecars";
+const SANITIZE_NAMES_KEY: &str = "sanitize_names";
+const BUCKET_SIZE_KEY: &str = "bucket_size";
+const TO_NUMBER_DUPLICATES_KEY: &str = "to_number_duplicates";
+const DUPLICATE_PATTERN_KEY: &str = "duplicate_pattern";
+const QUALITY_TABLE_KEY: &str = "quality_table";
+const TO_TRY_CANDIDATES_KEY: &str = "to_try_candidates";
+const CANDIDATE_QUALITIES_KEY: &str = "candidate_qualities";
+const MAX_SIZE_PERCENT_KEY: &str = "max_size_percent";
+const TO_USE_EXTENSION_RULES_KEY: &str = "to_use_extension_rules";
+const EXTENSION_RULES_KEY: &str = "extension_rules";
+const GROUP_DEPTH_KEY: &str = "group_depth";
+const REMOVE_INTERMEDIATE_KEY: &str = "remove_intermediate";
+const VERIFY_ARCHIVES_KEY: &str = "verify_archives";
+const REARCHIVE_CHANGED_KEY: &str = "rearchive_changed";
+const WRITE_CHECKSUMS_KEY: &str = "write_checksums";
+const ARCHIVE_EXCLUDED_KEY: &str = "archive_excluded";
+const KEEP_SYMLINKS_KEY: &str = "keep_symlinks";
+const ARCHIVE_ROOT_KEY: &str = "archive_root";
+const ARCHIVE_PREFIX_KEY: &str = "archive_prefix";
+const SEVEN_ZIP_METHOD_KEY: &str = "seven_zip_method";
+const SEVEN_ZIP_DICTIONARY_KEY: &str = "seven_zip_dictionary";
+const SEVEN_ZIP_SOLID_KEY: &str = "seven_zip_solid";
+const ZSTD_LEVEL_KEY: &str = "zstd_level";
+const ZSTD_WINDOW_LOG_KEY: &str = "zstd_window_log";
+const ZSTD_WORKER_COUNT_KEY: &str = "zstd_worker_count";
+const UPLOAD_TO_WEBDAV_KEY: &str = "upload_to_webdav";
+const WEBDAV_URL_KEY: &str = "webdav_url";
+const WEBDAV_USERNAME_KEY: &str = "webdav_username";
+const REMOVE_UPLOADED_KEY: &str = "remove_uploaded";
+#[cfg(feature = "s3")]
+const UPLOAD_TO_S3_KEY: &str = "upload_to_s3";
+#[cfg(feature = "s3")]
+const S3_ENDPOINT_KEY: &str = "s3_endpoint";
+#[cfg(feature = "s3")]
+const S3_REGION_KEY: &str = "s3_region";
+#[cfg(feature = "s3")]
+const S3_BUCKET_KEY: &str = "s3_bucket";
+#[cfg(feature = "s3")]
+const S3_PREFIX_KEY: &str = "s3_prefix";
 const DELETE_ORIGIN_KEY: &str = "delete_origin";
 const ARCHIVE_FORMAT_KEY: &str = "archive_format";
+const KEEP_BACKUP_KEY: &str = "keep_backup";
+const RESUME_KEY: &str = "resume";
+const SAVE_REPORT_KEY: &str = "save_report";
+const MEASURE_QUALITY_KEY: &str = "measure_quality";
+const ENCODER_KEY: &str = "encoder";
+const VERBOSITY_KEY: &str = "verbosity";
+const CHECK_UPDATE_KEY: &str = "check_update";
+const SHORTCUTS_KEY: &str = "shortcuts";
 
 pub const DEFAULT_SAVE_FILE_PATH: &str = "data/history.json";
+pub const DEFAULT_REPORT_DIR: &str = "data/reports";
+pub const DEFAULT_JOB_HISTORY_PATH: &str = "data/jobs.jsonl";
+pub const DEFAULT_LOG_DIR: &str = "data/logs";
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Settings that the layout preview is made from: the original folder, the example image, the layout,
+/// the bucket size, whether names are sanitized and the pattern of duplicate names.
+type PreviewKey = (PathBuf, PathBuf, Layout, usize, bool, Option<String>);
+
+/// Output paths of the layout preview, or the error of the settings.
+type LayoutPreview = Result<(PathBuf, Option<PathBuf>), String>;
+
+/// Create the log file of a job in the logs folder, or `None` if it cannot be created,
+/// as the job can run without it.
+fn create_job_log() -> Option<JobLog> {
+    match JobLog::create(get_data_path(DEFAULT_LOG_DIR), MAX_JOB_LOGS) {
+        Ok(l) => Some(l),
+        Err(e) => {
+            warn!("Cannot create the log file of the job: {}", e);
+            None
+        }
+    }
+}
+
+/// Format a duration as `hh:mm:ss`.
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Directory value to store in the history file.
+/// Paths that are not valid UTF-8 cannot be written to JSON, so they are not remembered.
+fn history_dir(dir: &Option<PathBuf>) -> Option<PathBuf> {
+    match dir {
+        Some(p) if p.to_str().is_some() => Some(p.to_path_buf()),
+        Some(_) => None,
+        None => Some(PathBuf::from("")),
+    }
+}
+
+/// Show the free space of a volume, as a warning when the estimated output size exceeds it.
+fn free_space_label(ui: &mut Ui, free_space: Option<u64>, estimated_size: Option<u64>) {
+    match (free_space, estimated_size) {
+        (Some(f), Some(e)) if e > f => {
+            ui.colored_label(Color32::RED, format!("Free space: {}, less than the estimated output {}!", format_size(f), format_size(e)));
+        }
+        (Some(f), _) => {
+            ui.label(format!("Free space: {}", format_size(f)));
+        }
+        (None, _) => {}
+    }
+}
+
+/// Longest side in pixels of the images by default when their dimension is limited.
+const DEFAULT_MAX_DIMENSION: u32 = 1920;
+
+/// Factor of the quality and the resize percentage, both clamped to the range `Factor` accepts.
+fn get_factor(quality: u32, resize_percent: u32) -> Factor {
+    Factor::new(quality.clamp(1, 100) as f32, resize_percent.clamp(1, 100) as f32 / 100.)
+}
 
 #[derive(Default)]
 pub struct App{
     program_data: ProgramData,
     origin_dir: Arc<Option<PathBuf>>,
+    url_list: String,
     dest_dir: Arc<Option<PathBuf>>,
     archive_dir: Arc<Option<PathBuf>>,
-    is_ui_enable: Arc<AtomicBool>,
     thread_count: u32,
+    priority: Priority,
+    quality: u32,
+    resize_percent: u32,
+    auto_by_size: bool,
+    limit_dimension: bool,
+    max_dimension: u32,
+    cap_source_quality: bool,
+    copy_low_quality: bool,
+    content_aware: bool,
+    denoise: bool,
+    denoise_strength: u32,
+    auto_enhance: bool,
+    blur_background: bool,
+    blur_strength: u32,
+    sharpen: bool,
+    sharpen_amount: u32,
+    sharpen_radius: f32,
+    tone_map: ToneMap,
+    exposure: f32,
+    svg_dpi: u32,
+    save_thumbnails: bool,
+    thumbnail_size: u32,
+    save_sizes: bool,
+    output_widths: String,
+    suffix_pattern: String,
+    quality_table: Option<(PathBuf, QualityTable)>,
+    to_try_candidates: bool,
+    candidate_qualities: String,
+    max_size_percent: u32,
+    to_use_extension_rules: bool,
+    extension_rules: String,
+    layout: Layout,
+    carry_sidecars: bool,
+    sanitize_names: bool,
+    bucket_size: u32,
+    to_number_duplicates: bool,
+    duplicate_pattern: String,
     to_zip: bool,
+    group_depth: u32,
+    remove_intermediate: bool,
+    verify_archives: bool,
+    rearchive_changed: bool,
+    write_checksums: bool,
+    archive_excluded: String,
+    keep_symlinks: bool,
+    archive_root: ArchiveRoot,
+    archive_prefix: String,
+    seven_zip_options: SevenZipOptions,
+    zstd_options: ZstdOptions,
+    upload_to_webdav: bool,
+    webdav_url: String,
+    webdav_username: String,
+    remove_uploaded: bool,
+    #[cfg(feature = "s3")]
+    upload_to_s3: bool,
+    #[cfg(feature = "s3")]
+    s3_endpoint: String,
+    #[cfg(feature = "s3")]
+    s3_region: String,
+    #[cfg(feature = "s3")]
+    s3_bucket: String,
+    #[cfg(feature = "s3")]
+    s3_prefix: String,
     to_del_origin_files: bool,
-    complete_file_list: Vec<String>,
-    tr: Option<mpsc::Receiver<String>>,
-    tx: Option<mpsc::Sender<String>>,
-    archive_format: Format,
+    keep_backup: bool,
+    to_resume: bool,
+    save_report: bool,
+    measure_quality: bool,
+    encoder_backend: EncoderBackend,
+    verbosity: Verbosity,
+    archive_format: ArchiveOutput,
+    thumbnail_loader: Option<ThumbnailLoader>,
+    inspected_file: Option<(PathBuf, io::Result<ImageMetadata>)>,
+    example_image: Option<(PathBuf, Option<PathBuf>)>,
+    layout_preview: Option<(PreviewKey, LayoutPreview)>,
+    in_place: RefCell<Option<(PathBuf, PathBuf, bool)>>,
+    file_selection: Option<FileSelection>,
+    size_estimate: Option<(PathBuf, Result<SizeEstimate, String>)>,
+    estimate_receiver: Option<mpsc::Receiver<(PathBuf, Result<SizeEstimate, String>)>>,
+    to_schedule: bool,
+    schedule_by_time: bool,
+    schedule_hour: u32,
+    schedule_minute: u32,
+    countdown_minutes: u32,
+    check_update: bool,
+    shortcut_settings: String,
+    keymap: Keymap,
+    palette_query: Option<String>,
+    job_history: Option<Result<Vec<JobRecord>, String>>,
+    update_receiver: Option<mpsc::Receiver<Release>>,
+    new_release: Option<Release>,
+    seven_zip_available: Option<bool>,
+    seven_zip_download: Option<mpsc::Receiver<Result<(PathBuf, String), String>>>,
+    seven_zip_message: Option<String>,
+    power_action: PowerAction,
+    power_deadline: Option<Instant>,
+    free_space_monitor: FreeSpaceMonitor,
+    job_manager: JobManager,
+    tabs: Vec<JobTab>,
+    active_tab: usize,
+}
+
+impl App {
+    /// Tab shown in the window.
+    fn tab(&mut self) -> &mut JobTab {
+        &mut self.tabs[self.active_tab]
+    }
+
+    /// Load the settings saved in the history file.
+    fn load_settings(&mut self) {
+        self.program_data = match ProgramData::load(get_data_path(DEFAULT_SAVE_FILE_PATH)){
+            Ok(dir_set) => {
+                self.tab().messages.push(String::from("Loading directory history complete!"));
+                dir_set
+            },
+            Err(e) => {
+                warn!("Cannot load the directory history: {}", e);
+                self.tab().messages.push(String::from("Cannot load directory save file!\nSet save file path with default."));
+                ProgramData::new()
+            }
+        };
+        self.apply_settings();
+    }
+
+    /// Set the fields from the settings in `program_data`.
+    fn apply_settings(&mut self) {
+        self.origin_dir = match self.program_data.get_data(ORIGIN_DIR_KEY){
+            Some(DataType::Directory(Some(p))) => Arc::new(Some(p.to_path_buf())),
+            _ => Arc::new(Some(PathBuf::from(""))),
+        };
+        self.dest_dir = match self.program_data.get_data(DESTINATION_DIR_KEY) {
+            Some(DataType::Directory(Some(p))) => Arc::new(Some(p.to_path_buf())),
+            _ => Arc::new(Some(PathBuf::from(""))),
+        };
+        self.archive_dir = match self.program_data.get_data(ARCHIVE_DIR_KEY){
+            Some(DataType::Directory(Some(p))) => Arc::new(Some(p.to_path_buf())),
+            _ => Arc::new(Some(PathBuf::from(""))),
+        };
+
+        self.to_zip = match self.program_data.get_data(TO_ZIP_KEY) {
+            Some(DataType::Boolean(Some(z))) => z.clone(),
+            _ => false,
+        };
+
+        self.thread_count = match self.program_data.get_data(THREAD_COUNT_KEY) {
+            Some(DataType::Number(Some(n))) => n.clone(),
+            _ => 1,
+        } as u32;
+
+        self.priority = match self.program_data.get_data(PRIORITY_KEY) {
+            Some(DataType::String(Some(p))) => Priority::from(p),
+            _ => Priority::default(),
+        };
+
+        self.quality = match self.program_data.get_data(QUALITY_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(1, 100) as u32,
+            _ => Factor::default().quality() as u32,
+        };
+
+        self.resize_percent = match self.program_data.get_data(RESIZE_PERCENT_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(1, 100) as u32,
+            _ => (Factor::default().size_ratio() * 100.) as u32,
+        };
+
+        self.auto_by_size = match self.program_data.get_data(AUTO_BY_SIZE_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.limit_dimension = match self.program_data.get_data(LIMIT_DIMENSION_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.max_dimension = match self.program_data.get_data(MAX_DIMENSION_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(64, 8192) as u32,
+            _ => DEFAULT_MAX_DIMENSION,
+        };
+
+        self.cap_source_quality = match self.program_data.get_data(CAP_SOURCE_QUALITY_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.copy_low_quality = match self.program_data.get_data(COPY_LOW_QUALITY_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.content_aware = match self.program_data.get_data(CONTENT_AWARE_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.denoise = match self.program_data.get_data(DENOISE_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.denoise_strength = match self.program_data.get_data(DENOISE_STRENGTH_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(1, 100) as u32,
+            _ => 30,
+        };
+
+        self.auto_enhance = match self.program_data.get_data(AUTO_ENHANCE_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.blur_background = match self.program_data.get_data(BLUR_BACKGROUND_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.blur_strength = match self.program_data.get_data(BLUR_STRENGTH_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(1, 100) as u32,
+            _ => 50,
+        };
+
+        self.sharpen = match self.program_data.get_data(SHARPEN_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.sharpen_amount = match self.program_data.get_data(SHARPEN_AMOUNT_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(1, 300) as u32,
+            _ => 50,
+        };
+
+        // The radius is saved in tenths of a pixel.
+        self.sharpen_radius = match self.program_data.get_data(SHARPEN_RADIUS_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(3, 50) as f32 / 10.,
+            _ => 1.,
+        };
+
+        self.tone_map = match self.program_data.get_data(TONE_MAP_KEY) {
+            Some(DataType::String(Some(t))) => ToneMap::from(t),
+            _ => ToneMap::default(),
+        };
+
+        // The exposure is saved in tenths of a stop.
+        self.exposure = match self.program_data.get_data(EXPOSURE_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(-100, 100) as f32 / 10.,
+            _ => 0.,
+        };
+
+        self.svg_dpi = match self.program_data.get_data(SVG_DPI_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(24, 1200) as u32,
+            _ => DEFAULT_SVG_DPI as u32,
+        };
+
+        self.save_thumbnails = match self.program_data.get_data(SAVE_THUMBNAILS_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.thumbnail_size = match self.program_data.get_data(THUMBNAIL_SIZE_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(16, 4096) as u32,
+            _ => DEFAULT_THUMBNAIL_SIZE,
+        };
+
+        self.save_sizes = match self.program_data.get_data(SAVE_SIZES_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.output_widths = match self.program_data.get_data(OUTPUT_WIDTHS_KEY) {
+            Some(DataType::String(Some(s))) => s.to_string(),
+            _ => String::from(DEFAULT_OUTPUT_WIDTHS),
+        };
+
+        self.suffix_pattern = match self.program_data.get_data(SUFFIX_PATTERN_KEY) {
+            Some(DataType::String(Some(s))) => s.to_string(),
+            _ => String::from(DEFAULT_SUFFIX_PATTERN),
+        };
+
+        self.quality_table = match self.program_data.get_data(QUALITY_TABLE_KEY) {
+            Some(DataType::Directory(Some(p))) if !p.as_os_str().is_empty() => match QualityTable::load(p) {
+                Ok(t) => Some((p.to_path_buf(), t)),
+                Err(e) => {
+                    self.tabs[self.active_tab].messages.push(format!("Cannot load the quality rules {}: {}", p.display(), e));
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        self.to_try_candidates = match self.program_data.get_data(TO_TRY_CANDIDATES_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.candidate_qualities = match self.program_data.get_data(CANDIDATE_QUALITIES_KEY) {
+            Some(DataType::String(Some(q))) => q.to_string(),
+            _ => DEFAULT_CANDIDATE_QUALITIES.to_string(),
+        };
+
+        self.max_size_percent = match self.program_data.get_data(MAX_SIZE_PERCENT_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).max(1) as u32,
+            _ => 50,
+        };
+
+        self.to_use_extension_rules = match self.program_data.get_data(TO_USE_EXTENSION_RULES_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.extension_rules = match self.program_data.get_data(EXTENSION_RULES_KEY) {
+            Some(DataType::String(Some(r))) => r.to_string(),
+            _ => String::new(),
+        };
+
+        self.layout = match self.program_data.get_data(LAYOUT_KEY) {
+            Some(DataType::String(Some(l))) => Layout::from(l),
+            _ => Layout::default(),
+        };
+
+        self.carry_sidecars = match self.program_data.get_data(CARRY_SIDECARS_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.sanitize_names = match self.program_data.get_data(SANITIZE_NAMES_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.bucket_size = match self.program_data.get_data(BUCKET_SIZE_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).max(1) as u32,
+            _ => 1000,
+        };
+
+        self.to_number_duplicates = match self.program_data.get_data(TO_NUMBER_DUPLICATES_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.duplicate_pattern = match self.program_data.get_data(DUPLICATE_PATTERN_KEY) {
+            Some(DataType::String(Some(p))) => p.to_string(),
+            _ => DEFAULT_DUPLICATE_PATTERN.to_string(),
+        };
+
+        self.group_depth = match self.program_data.get_data(GROUP_DEPTH_KEY) {
+            Some(DataType::Number(Some(n))) => *n as u32,
+            _ => 1,
+        };
+
+        self.remove_intermediate = match self.program_data.get_data(REMOVE_INTERMEDIATE_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.verify_archives = match self.program_data.get_data(VERIFY_ARCHIVES_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.rearchive_changed = match self.program_data.get_data(REARCHIVE_CHANGED_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.write_checksums = match self.program_data.get_data(WRITE_CHECKSUMS_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.archive_excluded = match self.program_data.get_data(ARCHIVE_EXCLUDED_KEY) {
+            Some(DataType::String(Some(e))) => e.to_string(),
+            _ => String::new(),
+        };
+
+        self.keep_symlinks = match self.program_data.get_data(KEEP_SYMLINKS_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.archive_root = match self.program_data.get_data(ARCHIVE_ROOT_KEY) {
+            Some(DataType::String(Some(r))) => ArchiveRoot::from(r),
+            _ => ArchiveRoot::default(),
+        };
+
+        self.archive_prefix = match self.program_data.get_data(ARCHIVE_PREFIX_KEY) {
+            Some(DataType::String(Some(p))) => p.to_string(),
+            _ => String::new(),
+        };
+
+        self.seven_zip_options.method = match self.program_data.get_data(SEVEN_ZIP_METHOD_KEY) {
+            Some(DataType::String(Some(m))) => SevenZipMethod::from(m),
+            _ => SevenZipMethod::default(),
+        };
+
+        self.seven_zip_options.dictionary_mb = match self.program_data.get_data(SEVEN_ZIP_DICTIONARY_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).max(0) as u32,
+            _ => 0,
+        };
+
+        self.seven_zip_options.solid = match self.program_data.get_data(SEVEN_ZIP_SOLID_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => true,
+        };
+
+        self.zstd_options.level = match self.program_data.get_data(ZSTD_LEVEL_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(1, 22),
+            _ => ZstdOptions::default().level,
+        };
+
+        self.zstd_options.window_log = match self.program_data.get_data(ZSTD_WINDOW_LOG_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).clamp(0, MAX_WINDOW_LOG as i32) as u32,
+            _ => 0,
+        };
+
+        self.zstd_options.worker_count = match self.program_data.get_data(ZSTD_WORKER_COUNT_KEY) {
+            Some(DataType::Number(Some(n))) => (*n).max(0) as u32,
+            _ => 0,
+        };
+
+        self.upload_to_webdav = match self.program_data.get_data(UPLOAD_TO_WEBDAV_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.webdav_url = match self.program_data.get_data(WEBDAV_URL_KEY) {
+            Some(DataType::String(Some(u))) => u.to_string(),
+            _ => String::new(),
+        };
+
+        self.webdav_username = match self.program_data.get_data(WEBDAV_USERNAME_KEY) {
+            Some(DataType::String(Some(u))) => u.to_string(),
+            _ => String::new(),
+        };
+
+        self.remove_uploaded = match self.program_data.get_data(REMOVE_UPLOADED_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        #[cfg(feature = "s3")]
+        {
+            self.upload_to_s3 = match self.program_data.get_data(UPLOAD_TO_S3_KEY) {
+                Some(DataType::Boolean(Some(b))) => *b,
+                _ => false,
+            };
+            for (key, value) in [(S3_ENDPOINT_KEY, &mut self.s3_endpoint), (S3_REGION_KEY, &mut self.s3_region), (S3_BUCKET_KEY, &mut self.s3_bucket), (S3_PREFIX_KEY, &mut self.s3_prefix)] {
+                *value = match self.program_data.get_data(key) {
+                    Some(DataType::String(Some(s))) => s.to_string(),
+                    _ => String::new(),
+                };
+            }
+        }
+
+        self.to_del_origin_files = match self.program_data.get_data(DELETE_ORIGIN_KEY) {
+            Some(DataType::Boolean(Some(b))) => b.clone(),
+            _ => false,
+        };
+
+        self.keep_backup = match self.program_data.get_data(KEEP_BACKUP_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.to_resume = match self.program_data.get_data(RESUME_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.save_report = match self.program_data.get_data(SAVE_REPORT_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.measure_quality = match self.program_data.get_data(MEASURE_QUALITY_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.encoder_backend = match self.program_data.get_data(ENCODER_KEY) {
+            Some(DataType::String(Some(b))) => EncoderBackend::from(b),
+            _ => EncoderBackend::default(),
+        };
+
+        self.verbosity = match self.program_data.get_data(VERBOSITY_KEY) {
+            Some(DataType::String(Some(v))) => Verbosity::from(v),
+            _ => Verbosity::default(),
+        };
+
+        self.check_update = match self.program_data.get_data(CHECK_UPDATE_KEY) {
+            Some(DataType::Boolean(Some(b))) => *b,
+            _ => false,
+        };
+
+        self.shortcut_settings = match self.program_data.get_data(SHORTCUTS_KEY) {
+            Some(DataType::String(Some(s))) => s.to_string(),
+            _ => String::new(),
+        };
+        self.keymap = Keymap::parse(&self.shortcut_settings).unwrap_or_default();
+
+        self.archive_format = match self.program_data.get_data(ARCHIVE_FORMAT_KEY){
+            Some(DataType::String(Some(b))) => ArchiveOutput::from(b),
+            _ => ArchiveOutput::default(),
+        };
+    }
+
+    /// Set the settings in `program_data` from the fields.
+    fn store_settings(&mut self) {
+        self.program_data.set_data(ORIGIN_DIR_KEY, DataType::Directory(history_dir(&self.origin_dir)));
+        self.program_data.set_data(DESTINATION_DIR_KEY, DataType::Directory(history_dir(&self.dest_dir)));
+        self.program_data.set_data(ARCHIVE_DIR_KEY, DataType::Directory(history_dir(&self.archive_dir)));
+        self.program_data.set_data(TO_ZIP_KEY, DataType::Boolean(Some(self.to_zip)));
+        self.program_data.set_data(THREAD_COUNT_KEY, DataType::Number(Some(self.thread_count as i32)));
+        self.program_data.set_data(PRIORITY_KEY, DataType::String(Some(self.priority.to_string())));
+        self.program_data.set_data(QUALITY_KEY, DataType::Number(Some(self.quality as i32)));
+        self.program_data.set_data(RESIZE_PERCENT_KEY, DataType::Number(Some(self.resize_percent as i32)));
+        self.program_data.set_data(AUTO_BY_SIZE_KEY, DataType::Boolean(Some(self.auto_by_size)));
+        self.program_data.set_data(LIMIT_DIMENSION_KEY, DataType::Boolean(Some(self.limit_dimension)));
+        self.program_data.set_data(MAX_DIMENSION_KEY, DataType::Number(Some(self.max_dimension as i32)));
+        self.program_data.set_data(CAP_SOURCE_QUALITY_KEY, DataType::Boolean(Some(self.cap_source_quality)));
+        self.program_data.set_data(COPY_LOW_QUALITY_KEY, DataType::Boolean(Some(self.copy_low_quality)));
+        self.program_data.set_data(CONTENT_AWARE_KEY, DataType::Boolean(Some(self.content_aware)));
+        self.program_data.set_data(DENOISE_KEY, DataType::Boolean(Some(self.denoise)));
+        self.program_data.set_data(DENOISE_STRENGTH_KEY, DataType::Number(Some(self.denoise_strength as i32)));
+        self.program_data.set_data(AUTO_ENHANCE_KEY, DataType::Boolean(Some(self.auto_enhance)));
+        self.program_data.set_data(BLUR_BACKGROUND_KEY, DataType::Boolean(Some(self.blur_background)));
+        self.program_data.set_data(BLUR_STRENGTH_KEY, DataType::Number(Some(self.blur_strength as i32)));
+        self.program_data.set_data(SHARPEN_KEY, DataType::Boolean(Some(self.sharpen)));
+        self.program_data.set_data(SHARPEN_AMOUNT_KEY, DataType::Number(Some(self.sharpen_amount as i32)));
+        self.program_data.set_data(SHARPEN_RADIUS_KEY, DataType::Number(Some((self.sharpen_radius * 10.).round() as i32)));
+        self.program_data.set_data(TONE_MAP_KEY, DataType::String(Some(self.tone_map.to_string())));
+        self.program_data.set_data(EXPOSURE_KEY, DataType::Number(Some((self.exposure * 10.).round() as i32)));
+        self.program_data.set_data(SVG_DPI_KEY, DataType::Number(Some(self.svg_dpi as i32)));
+        self.program_data.set_data(SAVE_THUMBNAILS_KEY, DataType::Boolean(Some(self.save_thumbnails)));
+        self.program_data.set_data(THUMBNAIL_SIZE_KEY, DataType::Number(Some(self.thumbnail_size as i32)));
+        self.program_data.set_data(SAVE_SIZES_KEY, DataType::Boolean(Some(self.save_sizes)));
+        self.program_data.set_data(OUTPUT_WIDTHS_KEY, DataType::String(Some(self.output_widths.to_string())));
+        self.program_data.set_data(SUFFIX_PATTERN_KEY, DataType::String(Some(self.suffix_pattern.to_string())));
+        self.program_data.set_data(QUALITY_TABLE_KEY, DataType::Directory(history_dir(&self.quality_table.as_ref().map(|(p, _)| p.to_path_buf()))));
+        self.program_data.set_data(TO_TRY_CANDIDATES_KEY, DataType::Boolean(Some(self.to_try_candidates)));
+        self.program_data.set_data(CANDIDATE_QUALITIES_KEY, DataType::String(Some(self.candidate_qualities.to_string())));
+        self.program_data.set_data(MAX_SIZE_PERCENT_KEY, DataType::Number(Some(self.max_size_percent as i32)));
+        self.program_data.set_data(TO_USE_EXTENSION_RULES_KEY, DataType::Boolean(Some(self.to_use_extension_rules)));
+        self.program_data.set_data(EXTENSION_RULES_KEY, DataType::String(Some(self.extension_rules.to_string())));
+        self.program_data.set_data(CARRY_SIDECARS_KEY, DataType::Boolean(Some(self.carry_sidecars)));
+        self.program_data.set_data(SANITIZE_NAMES_KEY, DataType::Boolean(Some(self.sanitize_names)));
+        self.program_data.set_data(LAYOUT_KEY, DataType::String(Some(self.layout.to_string())));
+        self.program_data.set_data(BUCKET_SIZE_KEY, DataType::Number(Some(self.bucket_size as i32)));
+        self.program_data.set_data(TO_NUMBER_DUPLICATES_KEY, DataType::Boolean(Some(self.to_number_duplicates)));
+        self.program_data.set_data(DUPLICATE_PATTERN_KEY, DataType::String(Some(self.duplicate_pattern.to_string())));
+        self.program_data.set_data(GROUP_DEPTH_KEY, DataType::Number(Some(self.group_depth as i32)));
+        self.program_data.set_data(REMOVE_INTERMEDIATE_KEY, DataType::Boolean(Some(self.remove_intermediate)));
+        self.program_data.set_data(VERIFY_ARCHIVES_KEY, DataType::Boolean(Some(self.verify_archives)));
+        self.program_data.set_data(REARCHIVE_CHANGED_KEY, DataType::Boolean(Some(self.rearchive_changed)));
+        self.program_data.set_data(WRITE_CHECKSUMS_KEY, DataType::Boolean(Some(self.write_checksums)));
+        self.program_data.set_data(ARCHIVE_EXCLUDED_KEY, DataType::String(Some(self.archive_excluded.to_string())));
+        self.program_data.set_data(KEEP_SYMLINKS_KEY, DataType::Boolean(Some(self.keep_symlinks)));
+        self.program_data.set_data(ARCHIVE_ROOT_KEY, DataType::String(Some(self.archive_root.to_string())));
+        self.program_data.set_data(ARCHIVE_PREFIX_KEY, DataType::String(Some(self.archive_prefix.to_string())));
+        self.program_data.set_data(SEVEN_ZIP_METHOD_KEY, DataType::String(Some(self.seven_zip_options.method.to_string())));
+        self.program_data.set_data(SEVEN_ZIP_DICTIONARY_KEY, DataType::Number(Some(self.seven_zip_options.dictionary_mb as i32)));
+        self.program_data.set_data(SEVEN_ZIP_SOLID_KEY, DataType::Boolean(Some(self.seven_zip_options.solid)));
+        self.program_data.set_data(ZSTD_LEVEL_KEY, DataType::Number(Some(self.zstd_options.level)));
+        self.program_data.set_data(ZSTD_WINDOW_LOG_KEY, DataType::Number(Some(self.zstd_options.window_log as i32)));
+        self.program_data.set_data(ZSTD_WORKER_COUNT_KEY, DataType::Number(Some(self.zstd_options.worker_count as i32)));
+        self.program_data.set_data(UPLOAD_TO_WEBDAV_KEY, DataType::Boolean(Some(self.upload_to_webdav)));
+        self.program_data.set_data(WEBDAV_URL_KEY, DataType::String(Some(self.webdav_url.to_string())));
+        self.program_data.set_data(WEBDAV_USERNAME_KEY, DataType::String(Some(self.webdav_username.to_string())));
+        self.program_data.set_data(REMOVE_UPLOADED_KEY, DataType::Boolean(Some(self.remove_uploaded)));
+        #[cfg(feature = "s3")]
+        {
+            self.program_data.set_data(UPLOAD_TO_S3_KEY, DataType::Boolean(Some(self.upload_to_s3)));
+            self.program_data.set_data(S3_ENDPOINT_KEY, DataType::String(Some(self.s3_endpoint.to_string())));
+            self.program_data.set_data(S3_REGION_KEY, DataType::String(Some(self.s3_region.to_string())));
+            self.program_data.set_data(S3_BUCKET_KEY, DataType::String(Some(self.s3_bucket.to_string())));
+            self.program_data.set_data(S3_PREFIX_KEY, DataType::String(Some(self.s3_prefix.to_string())));
+        }
+        self.program_data.set_data(DELETE_ORIGIN_KEY, DataType::Boolean(Some(self.to_del_origin_files)));
+        self.program_data.set_data(KEEP_BACKUP_KEY, DataType::Boolean(Some(self.keep_backup)));
+        self.program_data.set_data(RESUME_KEY, DataType::Boolean(Some(self.to_resume)));
+        self.program_data.set_data(SAVE_REPORT_KEY, DataType::Boolean(Some(self.save_report)));
+        self.program_data.set_data(MEASURE_QUALITY_KEY, DataType::Boolean(Some(self.measure_quality)));
+        self.program_data.set_data(ENCODER_KEY, DataType::String(Some(self.encoder_backend.to_string())));
+        self.program_data.set_data(VERBOSITY_KEY, DataType::String(Some(self.verbosity.to_string())));
+        self.program_data.set_data(CHECK_UPDATE_KEY, DataType::Boolean(Some(self.check_update)));
+        self.program_data.set_data(SHORTCUTS_KEY, DataType::String(Some(self.shortcut_settings.to_string())));
+        self.program_data.set_data(ARCHIVE_FORMAT_KEY, DataType::String(Some(self.archive_format.to_string())));
+    }
+
+    /// Show the tab at the index, keeping the settings of the current tab in it.
+    fn switch_tab(&mut self, index: usize) {
+        if index == self.active_tab || index >= self.tabs.len() {
+            return;
+        }
+        self.store_settings();
+        let tab = &mut self.tabs[self.active_tab];
+        tab.settings = self.program_data.clone();
+        tab.url_list = std::mem::take(&mut self.url_list);
+        self.active_tab = index;
+        self.program_data = self.tabs[index].settings.clone();
+        self.url_list = std::mem::take(&mut self.tabs[index].url_list);
+        self.apply_settings();
+    }
+
+    /// Add a tab with a copy of the current settings and show it.
+    fn add_tab(&mut self) {
+        self.store_settings();
+        self.tabs.push(JobTab::new(self.program_data.clone()));
+        self.switch_tab(self.tabs.len() - 1);
+    }
+
+    /// Close the tab shown in the window, unless its job is running or it is the last tab.
+    fn close_tab(&mut self) {
+        if self.tabs.len() <= 1 || self.tab().is_running() {
+            return;
+        }
+        let closed = self.active_tab;
+        self.switch_tab(if closed == 0 { 1 } else { closed - 1 });
+        self.tabs.remove(closed);
+        if self.active_tab > closed {
+            self.active_tab -= 1;
+        }
+    }
+
+    /// Show the command palette while it is open, and set the command chosen in it to run in this frame.
+    fn show_palette(&mut self, ctx: &Context, shortcuts: &mut Shortcuts) {
+        if shortcuts.open_palette {
+            self.palette_query = Some(String::new());
+        }
+        let query = match &mut self.palette_query {
+            Some(q) => q,
+            None => return,
+        };
+        // Escape closes the palette instead of canceling the scheduled job.
+        let mut is_open = !std::mem::take(&mut shortcuts.cancel);
+        let mut chosen = None;
+        egui::Window::new("Commands").collapsible(false).resizable(false).open(&mut is_open).show(ctx, |ui| {
+            let response = ui.add(TextEdit::singleline(query).hint_text("Type to search"));
+            response.widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Search commands"));
+            response.request_focus();
+            let commands = filter_commands(query);
+            if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                chosen = commands.first().copied();
+            }
+            for command in commands {
+                let shortcut = self.keymap.get_text(command);
+                if ui.selectable_label(false, format!("{}    {}", command.description(), shortcut)).clicked() {
+                    chosen = Some(command);
+                }
+            }
+        });
+        if let Some(command) = chosen {
+            shortcuts.set(command);
+            is_open = false;
+        }
+        if !is_open {
+            self.palette_query = None;
+        }
+    }
+
+    /// Show the savings dashboard while it is open, with charts of the job history.
+    fn show_dashboard(&mut self, ctx: &Context) {
+        let history = match &self.job_history {
+            Some(h) => h,
+            None => return,
+        };
+        let mut is_open = true;
+        egui::Window::new("Savings dashboard").open(&mut is_open).show(ctx, |ui| {
+            let history = match history {
+                Ok(h) if !h.is_empty() => h,
+                Ok(_) => {
+                    ui.label("No job has finished yet.");
+                    return;
+                }
+                Err(e) => {
+                    ui.colored_label(Color32::RED, format!("Cannot read the job history: {}", e));
+                    return;
+                }
+            };
+            let original_size = history.iter().map(|r| r.original_size).sum::<u64>();
+            let compressed_size = history.iter().map(|r| r.compressed_size).sum::<u64>();
+            let file_count = history.iter().map(|r| r.file_count).sum::<usize>();
+            let failed_count = history.iter().map(|r| r.failed_count).sum::<usize>();
+            ui.label(format!("{} jobs, {} files: {} -> {}, {} saved. {} files failed.",
+                             history.len(), file_count, format_size(original_size), format_size(compressed_size),
+                             format_size(original_size.saturating_sub(compressed_size)), failed_count));
+
+            // The times are Unix seconds, labeled with the local date.
+            let format_date = |x: f64, _: &std::ops::RangeInclusive<f64>| Local.timestamp_opt(x as i64, 0).single()
+                .map(|t| t.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            ui.label("Total saved (MB)");
+            let savings = get_cumulative_savings(history).into_iter()
+                .map(|(t, s)| Value::new(t as f64, s as f64 / 1_000_000.));
+            Plot::new("cumulative_savings").height(120.).x_axis_formatter(format_date).show(ui, |plot_ui| {
+                plot_ui.line(Line::new(Values::from_values_iter(savings)).name("Saved"));
+            });
+
+            // Bars of each job are numbered in the order the jobs finished.
+            ui.label("Compressed size of each job (% of the original)");
+            let ratio_bars = history.iter().enumerate()
+                .filter_map(|(i, r)| Some(Bar::new(i as f64 + 1., r.ratio()? * 100.).name(r.origin.display())))
+                .collect();
+            Plot::new("job_ratios").height(120.).include_y(0.).include_y(100.).show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new(ratio_bars).name("Ratio"));
+            });
+            ui.label("Failed files of each job (%)");
+            let failure_bars = history.iter().enumerate()
+                .filter_map(|(i, r)| Some(Bar::new(i as f64 + 1., r.failure_rate()? * 100.).name(r.origin.display())))
+                .collect();
+            Plot::new("job_failures").height(120.).include_y(0.).show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new(failure_bars).color(Color32::RED).name("Failed"));
+            });
+        });
+        if !is_open {
+            self.job_history = None;
+        }
+    }
+
+    /// Show the metadata of the file clicked in the preview, in a panel at the right of the window.
+    fn show_metadata(&mut self, ctx: &Context) {
+        let (path, metadata) = match &self.inspected_file {
+            Some(f) => f,
+            None => return,
+        };
+        let mut is_open = true;
+        egui::Window::new("Metadata").anchor(egui::Align2::RIGHT_TOP, Vec2::new(-10., 60.)).resizable(false).open(&mut is_open).show(ctx, |ui| {
+            ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+            match metadata {
+                Ok(m) => {
+                    egui::Grid::new("metadata").num_columns(2).show(ui, |ui| {
+                        for (label, value) in m.to_lines() {
+                            ui.label(label);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+                }
+                Err(e) => {
+                    ui.colored_label(Color32::RED, format!("Cannot read the file: {}", e));
+                }
+            }
+        });
+        if !is_open {
+            self.inspected_file = None;
+        }
+    }
+
+    /// Show the countdown of the power action after the jobs, and run the action when it ends unless it is canceled.
+    fn show_power_countdown(&mut self, ctx: &Context) {
+        let deadline = match self.power_deadline {
+            Some(d) => d,
+            None => return,
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        let mut to_cancel = false;
+        let mut to_run = left.is_zero();
+        egui::Window::new("After the jobs").anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(format!("The jobs are done. {} in {} seconds.", self.power_action, left.as_secs() + 1));
+            ui.horizontal(|ui| {
+                to_cancel = ui.button("Cancel").clicked();
+                to_run |= ui.button(format!("{} now", self.power_action)).clicked();
+            });
+        });
+        if to_cancel {
+            self.power_deadline = None;
+            let message = format!("{} after the jobs is canceled.", self.power_action);
+            self.tab().messages.push(message);
+        } else if to_run {
+            self.power_deadline = None;
+            let action = self.power_action;
+            // The action is done once, so that the computer is not shut down again after the next job.
+            self.power_action = PowerAction::Nothing;
+            if let Err(e) = run_power_action(action) {
+                error!("Cannot run the power action: {}", e);
+                self.tab().messages.push(format!("Cannot {} the computer! {}", action.to_string().to_lowercase(), e));
+            }
+        }
+    }
+
+    /// Open the CSV file of the latest report with the default program.
+    fn open_latest_report(&mut self) {
+        let message = match find_latest_report(get_data_path(DEFAULT_REPORT_DIR)) {
+            Some(path) => match open_with_default_app(&path) {
+                Ok(_) => return,
+                Err(e) => format!("Cannot open the report {}: {}", path.display(), e),
+            },
+            None => String::from("No report is saved yet. Check \"Save a report of compressed files\" to save one for each job."),
+        };
+        self.tab().messages.push(message);
+    }
+
+    /// Build a pipeline with the current settings.
+    fn build_pipeline(&self) -> Pipeline {
+        let mut pipeline = Pipeline::new((*self.origin_dir).clone().unwrap_or_default(), (*self.dest_dir).clone().unwrap_or_default());
+        pipeline.set_factor(get_factor(self.quality, self.resize_percent));
+        if self.limit_dimension {
+            pipeline.set_max_dimension(self.max_dimension);
+        }
+        pipeline.set_cap_source_quality(self.cap_source_quality);
+        pipeline.set_copy_low_quality(self.copy_low_quality);
+        pipeline.set_content_aware(self.content_aware);
+        let mut image_steps = Vec::new();
+        if self.denoise {
+            image_steps.push(ImageStep::Denoise(self.denoise_strength as f32 / 100.));
+        }
+        if self.auto_enhance {
+            image_steps.push(ImageStep::AutoEnhance);
+        }
+        if self.blur_background {
+            image_steps.push(ImageStep::BlurBackground(self.blur_strength as f32 / 100.));
+        }
+        if self.sharpen {
+            image_steps.push(ImageStep::Sharpen { amount: self.sharpen_amount as f32 / 100., radius: self.sharpen_radius });
+        }
+        pipeline.set_image_steps(image_steps);
+        pipeline.set_decode_options(DecodeOptions { tone_map: self.tone_map, exposure: self.exposure, svg_dpi: self.svg_dpi as f32 });
+        if self.save_thumbnails {
+            pipeline.set_thumbnail_size(self.thumbnail_size);
+        }
+        if let Some(specs) = OutputSpec::parse_list(&self.output_widths, &self.suffix_pattern).ok().filter(|_| self.save_sizes) {
+            pipeline.set_output_specs(specs);
+        }
+        // A loaded table replaces the built-in one.
+        match &self.quality_table {
+            Some((_, table)) => pipeline.set_quality_table(table.clone()),
+            None if self.auto_by_size => pipeline.set_quality_table(QualityTable::by_file_size()),
+            None => {}
+        }
+        if let Some(candidates) = Candidates::parse(&self.candidate_qualities, self.max_size_percent).filter(|_| self.to_try_candidates) {
+            pipeline.set_candidates(candidates);
+        }
+        if let Some(rules) = ExtensionRules::parse(&self.extension_rules).ok().filter(|r| self.to_use_extension_rules && !r.is_empty()) {
+            pipeline.set_extension_rules(rules);
+        }
+        if !self.is_in_place() {
+            pipeline.set_layout(self.layout);
+            pipeline.set_carry_sidecars(self.carry_sidecars);
+            pipeline.set_sanitize_names(self.sanitize_names);
+            pipeline.set_bucket_size(self.bucket_size as usize);
+        }
+        if self.to_number_duplicates && !self.is_in_place() {
+            pipeline.set_duplicate_pattern(&self.duplicate_pattern);
+        }
+        pipeline.set_thread_count(self.thread_count);
+        pipeline.set_delete_source(self.to_del_origin_files);
+        pipeline.set_keep_backup(self.keep_backup);
+        pipeline.set_resume(self.to_resume);
+        if let Some(selection) = self.file_selection.as_ref().filter(|s| Some(s.dir()) == (*self.origin_dir).as_deref()) {
+            pipeline.set_excluded(selection.excluded());
+        }
+        pipeline.set_url_list(parse_url_list(&self.url_list));
+        if self.to_zip {
+            pipeline.set_archive((*self.archive_dir).clone().unwrap_or_default(), self.archive_format.clone());
+            pipeline.set_group_depth(self.group_depth);
+            pipeline.set_remove_intermediate(self.remove_intermediate);
+            pipeline.set_verify_archives(self.verify_archives);
+            pipeline.set_rearchive_changed(self.rearchive_changed);
+            pipeline.set_write_checksums(self.write_checksums);
+            let mut filter = ArchiveFilter::parse(&self.archive_excluded);
+            filter.set_keep_symlinks(self.keep_symlinks);
+            pipeline.set_archive_filter(filter);
+            pipeline.set_archive_root(self.archive_root, &self.archive_prefix);
+            if self.seven_zip_options != SevenZipOptions::default() {
+                pipeline.set_seven_zip_options(self.seven_zip_options);
+            }
+            pipeline.set_zstd_options(self.zstd_options);
+        }
+        if self.save_report {
+            pipeline.set_report_dir(get_data_path(DEFAULT_REPORT_DIR));
+        }
+        pipeline.set_history_path(get_data_path(DEFAULT_JOB_HISTORY_PATH));
+        pipeline.set_measure_quality(self.measure_quality);
+        pipeline.set_encoder_backend(self.encoder_backend);
+        pipeline.set_verbosity(self.verbosity);
+        if self.upload_to_webdav {
+            match WebDavTarget::from_env(&self.webdav_url, &self.webdav_username) {
+                Ok(target) => pipeline.set_webdav_target(target),
+                Err(e) => error!("Cannot upload to the WebDAV server: {}", e),
+            }
+        }
+        #[cfg(feature = "s3")]
+        if self.upload_to_s3 {
+            match S3Target::from_env(&self.s3_endpoint, &self.s3_region, &self.s3_bucket, &self.s3_prefix) {
+                Ok(target) => pipeline.set_s3_target(target),
+                Err(e) => error!("Cannot upload to S3: {}", e),
+            }
+        }
+        pipeline.set_remove_uploaded(self.remove_uploaded);
+        pipeline
+    }
+
+    /// Whether the images are compressed in place, as the original and destination folders are the same folder,
+    /// however their paths are written. The pipeline decides it the same way.
+    /// Resolving the paths reads the file system, so the answer is kept until either folder changes.
+    fn is_in_place(&self) -> bool {
+        let (origin, dest) = match (&*self.origin_dir, &*self.dest_dir) {
+            (Some(origin), Some(dest)) => (origin, dest),
+            _ => return false,
+        };
+        let mut cache = self.in_place.borrow_mut();
+        match &*cache {
+            Some((o, d, in_place)) if o == origin && d == dest => *in_place,
+            _ => {
+                let in_place = is_in_place(origin, dest);
+                *cache = Some((origin.to_path_buf(), dest.to_path_buf(), in_place));
+                in_place
+            }
+        }
+    }
+
+    /// Whether the outputs are uploaded to any remote server.
+    fn is_uploading(&self) -> bool {
+        #[cfg(feature = "s3")]
+        if self.upload_to_s3 {
+            return true;
+        }
+        self.upload_to_webdav
+    }
+
+    /// Submit the job of the current tab with its settings to the shared `JobManager`,
+    /// which runs it along with the jobs of the other tabs.
+    fn start_job(&mut self) {
+        let handle = self.job_manager.submit_with_priority(self.build_pipeline(), self.priority);
+        self.tab().start(handle, create_job_log());
+    }
+
+    /// Start the scheduled jobs of every tab whose time has come.
+    fn start_scheduled_jobs(&mut self) {
+        let now = Local::now();
+        for index in 0..self.tabs.len() {
+            let tab = &mut self.tabs[index];
+            if tab.is_running() || !matches!(tab.scheduled_start, Some(s) if now >= s) {
+                continue;
+            }
+            tab.scheduled_start = None;
+            // The pipeline is built from the settings of the tab, so it is shown while the job is submitted.
+            let shown_tab = self.active_tab;
+            self.switch_tab(index);
+            self.start_job();
+            self.switch_tab(shown_tab);
+        }
+    }
+
+    /// Run a job without opening the window, with the saved settings overridden by the command line options.
+    /// The messages of the job are printed to the standard output, and the settings are not saved.
+    ///
+    /// # Error
+    /// - When the original or destination folder is not set.
+    /// - When archiving is set but the archive folder is not.
+    /// - When the job cannot be completed.
+    pub fn run_headless(mut self, cli_args: CliArgs) -> Result<(), Box<dyn Error>> {
+        self.tabs = vec![JobTab::default()];
+        self.load_settings();
+        if let Some(p) = cli_args.origin {
+            self.origin_dir = Arc::new(Some(p));
+        }
+        self.url_list = cli_args.urls.join("\n");
+        if let Some(p) = cli_args.dest {
+            self.dest_dir = Arc::new(Some(p));
+        }
+        if let Some(p) = cli_args.archive {
+            self.archive_dir = Arc::new(Some(p));
+            self.to_zip = true;
+        }
+        if let Some(f) = cli_args.format {
+            self.archive_format = ArchiveOutput::from(&f);
+        }
+        self.thread_count = cli_args.threads.unwrap_or(self.thread_count);
+        self.quality = cli_args.quality.unwrap_or(self.quality);
+        self.resize_percent = cli_args.resize.unwrap_or(self.resize_percent);
+        self.priority = cli_args.priority.unwrap_or(self.priority);
+        self.verbosity = cli_args.verbosity.unwrap_or(self.verbosity);
+        self.to_resume |= cli_args.resume;
+
+        let mut required = vec![("original", &self.origin_dir), ("destination", &self.dest_dir)];
+        if self.to_zip {
+            required.push(("archive", &self.archive_dir));
+        }
+        for (name, dir) in required {
+            if !matches!(&**dir, Some(d) if !d.as_os_str().is_empty()) {
+                return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, format!("The {} folder is not set!", name))));
+            }
+        }
+
+        let mut pipeline = self.build_pipeline();
+        // There is no other job to yield to, so only the OS priority is lowered, before the threads of the job are spawned.
+        if self.priority == Priority::Low {
+            lower_thread_priority();
+        }
+        // Ctrl-C finishes the files in progress, and with resuming the journal lets the next run continue from there.
+        match interrupt::trap_stop_signals() {
+            Ok(flag) => pipeline.set_stop_flag(flag),
+            Err(e) => warn!("Cannot handle Ctrl-C, so the files in progress are cut off when the job is interrupted: {}", e),
+        }
+        let (tx, tr) = mpsc::channel::<Event>();
+        pipeline.set_sender(tx);
+        let mut log = create_job_log();
+        let printer = thread::spawn(move || {
+            for event in tr {
+                if let Some(l) = &mut log {
+                    if let Err(e) = l.write_line(&event.to_string()) {
+                        warn!("Cannot write the log of the job to {}: {}", l.path().display(), e);
+                        log = None;
+                    }
+                }
+                println!("{}", event);
+            }
+        });
+        let result = pipeline.run();
+        if printer.join().is_err() {
+            warn!("Cannot print the messages of the job");
+        }
+        result
+    }
 }
 
 impl epi::App for App {
     fn update(&mut self, ctx: &egui::Context, frame: &epi::Frame) {
         egui::CentralPanel::default().show(ctx, |ui| {
 
-            match &self.tr {
-                Some(tr) => match tr.try_recv() {
-                    Ok(s) => self.complete_file_list.push(s),
-                    Err(_) => {}
-                },
-                None => {}
+            // Receive the messages of the jobs of every tab, including the hidden ones.
+            let mut has_succeeded = false;
+            for tab in &mut self.tabs {
+                has_succeeded |= tab.receive_events() == Some(true);
+            }
+            // The power action waits until every job is done, including the scheduled ones.
+            if has_succeeded && self.power_action != PowerAction::Nothing && !self.tabs.iter().any(|t| t.is_running() || t.scheduled_start.is_some()) {
+                self.power_deadline = Some(Instant::now() + POWER_COUNTDOWN);
             }
+            self.show_power_countdown(ctx);
+            self.start_scheduled_jobs();
 
             let version = env!("CARGO_PKG_VERSION");
 
             // Title
             ui.vertical_centered(|ui| ui.heading(format!("Image Compress and Archive Program     v{}", version)));
+            if is_portable() {
+                ui.vertical_centered(|ui| ui.label("Portable mode: the settings and reports are kept next to the program."));
+            }
             ui.add_space(10.);
 
+            // Banner for a newer release
+            if let Some(Ok(release)) = self.update_receiver.as_ref().map(|r| r.try_recv()) {
+                self.new_release = Some(release);
+                self.update_receiver = None;
+            }
+            if let Some(release) = &self.new_release {
+                let mut to_dismiss = false;
+                ui.group(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Version {} is available.", release.tag_name));
+                        ui.hyperlink_to("Download", &release.html_url);
+                        to_dismiss = ui.button("Dismiss").clicked();
+                    });
+                    if let Some(notes) = release.body.as_ref().filter(|b| !b.is_empty()) {
+                        ui.collapsing("Release notes", |ui| {
+                            egui::ScrollArea::vertical().id_source("release_notes").max_height(150.).show(ui, |ui| {
+                                ui.label(notes);
+                            });
+                        });
+                    }
+                });
+                if to_dismiss {
+                    self.new_release = None;
+                }
+                ui.add_space(10.);
+            }
+
+            let mut shortcuts = self.keymap.consume(ctx);
+            self.show_palette(ctx, &mut shortcuts);
+            if shortcuts.new_tab {
+                self.add_tab();
+            }
+            if shortcuts.close_tab {
+                self.close_tab();
+            }
+            if shortcuts.open_report {
+                self.open_latest_report();
+            }
+            if shortcuts.open_dashboard {
+                self.job_history = Some(load_job_history(get_data_path(DEFAULT_JOB_HISTORY_PATH)).map_err(|e| e.to_string()));
+            }
+            self.show_dashboard(ctx);
+            self.show_metadata(ctx);
+
+            // Tabs of the jobs
+            let mut selected_tab = None;
+            let mut to_close_tab = false;
+            ui.horizontal_wrapped(|ui| {
+                for (index, tab) in self.tabs.iter().enumerate() {
+                    let origin_dir = match index == self.active_tab {
+                        true => (*self.origin_dir).clone(),
+                        false => tab.origin_dir(),
+                    };
+                    let mut title = get_tab_title(origin_dir.as_deref(), index);
+                    if tab.is_running() {
+                        title.push_str(" (running)");
+                    } else if tab.scheduled_start.is_some() {
+                        title.push_str(" (scheduled)");
+                    }
+                    if ui.selectable_label(index == self.active_tab, title).clicked() {
+                        selected_tab = Some(index);
+                    }
+                }
+                if ui.button("+").on_hover_text("New tab with the current settings".to_string()).clicked() {
+                    self.add_tab();
+                }
+                let can_close = self.tabs.len() > 1 && !self.tab().is_running() && self.tab().scheduled_start.is_none();
+                if ui.add_enabled(can_close, egui::Button::new("Close tab")).clicked() {
+                    to_close_tab = true;
+                }
+                if ui.button("Dashboard").on_hover_text("How much the past jobs saved".to_string()).clicked() {
+                    self.job_history = Some(load_job_history(get_data_path(DEFAULT_JOB_HISTORY_PATH)).map_err(|e| e.to_string()));
+                }
+            });
+            if let Some(index) = selected_tab {
+                self.switch_tab(index);
+            }
+            if to_close_tab {
+                self.close_tab();
+            }
+            ui.add_space(5.);
+
             // UI group
             ui.group(|ui| {
-                ui.set_enabled((*self.is_ui_enable).load(Ordering::Relaxed));
+                ui.set_enabled(!self.tab().is_running());
 
                 // Original folder selector
                 ui.heading("Original folder");
-                if ui.button("select").clicked() {
+                if ui.button("select").on_hover_text(self.keymap.get_text(Command::SelectOrigin)).clicked() || (shortcuts.select_origin && ui.is_enabled()) {
                     if let Some(path) = rfd::FileDialog::new().pick_folder() {
                         self.origin_dir = Arc::new(Some(path));
                     }
@@ -75,18 +1406,141 @@ impl epi::App for App {
                 };
                 ui.horizontal(|ui| {
                     ui.label("Path:");
-                    ui.add_sized(ui.available_size(), egui::TextEdit::multiline(&mut match origin_dir.to_str() {
-                            Some(s) => s,
-                            None => "",
+                    ui.add_sized(ui.available_size(), egui::TextEdit::multiline(&mut origin_dir.to_string_lossy().as_ref()).interactive(false)
+                        .hint_text("Original folder"))
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Original folder path"));
+                });
+
+                // Thumbnails of the original folder, loaded when the preview is opened
+                ui.collapsing("Preview", |ui| {
+                    if origin_dir.as_os_str().is_empty() {
+                        self.thumbnail_loader = None;
+                        return;
+                    }
+                    if self.thumbnail_loader.as_ref().map(|l| l.dir() != origin_dir).unwrap_or(true) {
+                        self.thumbnail_loader = Some(ThumbnailLoader::new(&origin_dir));
+                    }
+                    let loader = self.thumbnail_loader.as_mut().unwrap();
+                    loader.receive(ctx);
+                    match loader.image_count() {
+                        Some(n) if n > MAX_THUMBNAIL_COUNT => ui.label(format!("{} images, showing the first {}", n, MAX_THUMBNAIL_COUNT)),
+                        Some(n) => ui.label(format!("{} images", n)),
+                        None => ui.label("Reading the folder..."),
+                    };
+                    egui::ScrollArea::vertical().id_source("preview").max_height(200.).show(ui, |ui| {
+                        ui.horizontal_wrapped(|ui| {
+                            for (path, texture) in loader.thumbnails() {
+                                let response = ui.add(egui::ImageButton::new(texture, texture.size_vec2()))
+                                    .on_hover_text(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                if response.clicked() {
+                                    self.inspected_file = Some((path.to_path_buf(), read_metadata(path)));
+                                }
+                            }
+                            if loader.is_loading() {
+                                ui.label("Loading...");
+                            }
+                        });
+                    });
+                });
+
+                // Files and subfolders to leave out of the job
+                ui.collapsing("Exclude files", |ui| {
+                    if origin_dir.as_os_str().is_empty() {
+                        self.file_selection = None;
+                        return;
+                    }
+                    if self.file_selection.as_ref().map(|s| s.dir() != origin_dir).unwrap_or(true) {
+                        match FileSelection::new(&origin_dir) {
+                            Ok(s) => self.file_selection = Some(s),
+                            Err(e) => {
+                                ui.label(format!("Cannot read the folder: {}", e));
+                                return;
+                            }
+                        }
+                    }
+                    let selection = self.file_selection.as_mut().unwrap();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} excluded", selection.excluded().len()));
+                        if ui.button("Include all").clicked() {
+                            selection.clear();
+                        }
+                    });
+                    let row_height = ui.spacing().interact_size.y;
+                    let entry_count = selection.entries().len();
+                    egui::ScrollArea::vertical().id_source("file_selection").max_height(200.).show_rows(ui, row_height, entry_count, |ui, range| {
+                        for i in range {
+                            let entry = &selection.entries()[i];
+                            let path = entry.path.to_path_buf();
+                            let depth = entry.depth();
+                            let mut name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                            if entry.is_dir {
+                                name.push('/');
+                            }
+                            let is_parent_excluded = selection.is_parent_excluded(&path);
+                            let mut is_included = !is_parent_excluded && !selection.is_excluded(&path);
+                            ui.horizontal(|ui| {
+                                ui.add_space(depth as f32 * 16.);
+                                ui.add_enabled_ui(!is_parent_excluded, |ui| {
+                                    if ui.checkbox(&mut is_included, name).changed() {
+                                        selection.set_excluded(&path, !is_included);
+                                    }
+                                });
+                            });
                         }
-                    ).interactive(false)
-                        .hint_text("Original folder"));
+                    });
+                });
+
+                // Output size estimated by compressing a few sample images
+                if let Some(Ok(estimate)) = self.estimate_receiver.as_ref().map(|r| r.try_recv()) {
+                    self.size_estimate = Some(estimate);
+                    self.estimate_receiver = None;
+                }
+                ui.horizontal(|ui| {
+                    let is_estimating = self.estimate_receiver.is_some();
+                    let estimate_button = egui::Button::new("Estimate output size");
+                    if ui.add_enabled(!is_estimating && !origin_dir.as_os_str().is_empty(), estimate_button).clicked() {
+                        let origin = origin_dir.to_path_buf();
+                        let excluded = match &self.file_selection {
+                            Some(s) if s.dir() == origin => s.excluded().iter().map(|p| origin.join(p)).collect(),
+                            _ => Vec::new(),
+                        };
+                        let factor = get_factor(self.quality, self.resize_percent);
+                        let (tx, tr) = mpsc::channel();
+                        self.estimate_receiver = Some(tr);
+                        thread::spawn(move || {
+                            let result = get_file_list(&origin)
+                                .map(|l| l.into_iter().filter(|f| !is_excluded(f, &excluded)).collect::<Vec<_>>())
+                                .and_then(|l| estimate_output_size(&l, factor))
+                                .map_err(|e| e.to_string());
+                            if tx.send((origin, result)).is_err() {
+                                warn!("Cannot send the estimated output size");
+                            }
+                        });
+                    }
+                    match &self.size_estimate {
+                        _ if is_estimating => ui.label("Estimating..."),
+                        Some((dir, Ok(e))) if *dir == origin_dir => ui.label(format!("About {} of {} ({:.0}% saved, {} samples)", format_size(e.estimated_size), format_size(e.original_size), e.savings_percent(), e.sample_count)),
+                        Some((dir, Err(e))) if *dir == origin_dir => ui.label(format!("Cannot estimate: {}", e)),
+                        _ => ui.label(""),
+                    };
+                });
+
+                // Images to download into the original folder before compressing
+                ui.collapsing("Download from URLs", |ui| {
+                    ui.label("Images at these URLs are downloaded into the original folder and compressed with it.");
+                    ui.add(TextEdit::multiline(&mut self.url_list).desired_rows(3).hint_text("https://example.com/a.png"))
+                        .on_hover_text("One URL per line.".to_string())
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "URLs of images to download"));
+                    let url_count = parse_url_list(&self.url_list).len();
+                    if url_count > 0 {
+                        ui.label(format!("{} URLs", url_count));
+                    }
                 });
                 ui.separator();
 
                 // Destination folder selector
                 ui.heading("Destination folder");
-                if ui.button("select").clicked() {
+                if ui.button("select").on_hover_text(self.keymap.get_text(Command::SelectDest)).clicked() || (shortcuts.select_dest && ui.is_enabled()) {
                     if let Some(path) = rfd::FileDialog::new().pick_folder() {
                         self.dest_dir = Arc::new(Some(path));
                     }
@@ -97,17 +1551,227 @@ impl epi::App for App {
                 };
                 ui.horizontal(|ui| {
                     ui.label("Path:");
-                    ui.add_sized(ui.available_size(), egui::TextEdit::multiline(&mut match dest_dir.to_str() {
-                            Some(s) => s,
-                            None => "",
-                        }).interactive(false)
-                        .hint_text("Destination folder"));
+                    ui.add_sized(ui.available_size(), egui::TextEdit::multiline(&mut dest_dir.to_string_lossy().as_ref()).interactive(false)
+                        .hint_text("Destination folder"))
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Destination folder path"));
                 });
+                let estimated_size = match &self.size_estimate {
+                    Some((dir, Ok(e))) if *dir == origin_dir => Some(e.estimated_size),
+                    _ => None,
+                };
+                if !dest_dir.as_os_str().is_empty() {
+                    free_space_label(ui, self.free_space_monitor.get(&dest_dir), estimated_size);
+                }
+
+                // Folders in the destination
+                if !self.is_in_place() {
+                    ui.horizontal(|ui| {
+                        ui.label("Folders:");
+                        ui.selectable_value(&mut self.layout, Layout::Mirrored, "Same as the original");
+                        ui.selectable_value(&mut self.layout, Layout::Buckets, "Numbered");
+                        ui.selectable_value(&mut self.layout, Layout::ByDate, "By date taken")
+                            .on_hover_text("Year and month folders by the EXIF date, or the modified date of the file.".to_string());
+                        ui.selectable_value(&mut self.layout, Layout::Flat, "None")
+                            .on_hover_text("Every file in the destination folder. Duplicate names are always numbered.".to_string());
+                    });
+                    if self.layout == Layout::Buckets {
+                        ui.horizontal(|ui| {
+                            ui.label("Put at most");
+                            ui.add(DragValue::new(&mut self.bucket_size).clamp_range(1..=100000))
+                                .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Files in each numbered folder"));
+                            ui.label("files in each numbered folder");
+                        });
+                    }
+                    ui.checkbox(&mut self.carry_sidecars, "Carry .xmp, .json and .aae files with their images")
+                        .on_hover_text("Sidecar files are renamed after the compressed image.".to_string());
+                    ui.checkbox(&mut self.sanitize_names, "Make names valid on Windows and FAT drives")
+                        .on_hover_text("Replace the characters <>:\"/\\|?* and trailing dots or spaces.".to_string());
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.to_number_duplicates, "Rename duplicate names as");
+                        ui.add_enabled(self.to_number_duplicates, TextEdit::singleline(&mut self.duplicate_pattern))
+                            .on_hover_text("{name} is the file name, {ext} is the original extension and {n} is the number.".to_string())
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Pattern of duplicate names"));
+                        ui.add_enabled_ui(self.to_number_duplicates, |ui| {
+                            ui.selectable_value(&mut self.duplicate_pattern, DEFAULT_DUPLICATE_PATTERN.to_string(), "Number");
+                            ui.selectable_value(&mut self.duplicate_pattern, EXTENSION_DUPLICATE_PATTERN.to_string(), "Add extension")
+                                .on_hover_text("photo.png is written as photo_png.jpg when photo.jpg is also there.".to_string());
+                        });
+                    });
+
+                    // Where an image of the original folder goes with these settings
+                    if self.example_image.as_ref().map(|(d, _)| *d != origin_dir).unwrap_or(true) {
+                        self.example_image = Some((origin_dir.to_path_buf(), find_example_image(&origin_dir)));
+                    }
+                    let example = match &self.example_image {
+                        Some((_, Some(e))) => e.to_path_buf(),
+                        _ => origin_dir.join("Album").join("Photo.png"),
+                    };
+                    // The date layout reads the file, so the preview is made again only when its settings change.
+                    let duplicate_pattern = Some(self.duplicate_pattern.to_string()).filter(|_| self.to_number_duplicates);
+                    let key = (origin_dir.to_path_buf(), example.to_path_buf(), self.layout, self.bucket_size as usize, self.sanitize_names, duplicate_pattern);
+                    let preview = match &self.layout_preview {
+                        Some((k, p)) if *k == key => p.clone(),
+                        _ => {
+                            let p = preview_output_paths(&origin_dir, &example, key.2, key.3, key.4, key.5.as_deref());
+                            self.layout_preview = Some((key, p.clone()));
+                            p
+                        }
+                    };
+                    match preview {
+                        Ok((output, duplicate)) => {
+                            let source = example.strip_prefix(&origin_dir).unwrap_or(&example);
+                            ui.label(format!("Example: {} -> {}", source.display(), dest_dir.join(&output).display()));
+                            if let Some(d) = duplicate {
+                                ui.label(format!("Another {} -> {}", example.file_name().unwrap_or_default().to_string_lossy(), dest_dir.join(d).display()));
+                            }
+                        }
+                        Err(e) => {
+                            ui.colored_label(Color32::RED, e);
+                        }
+                    }
+                }
                 ui.separator();
 
                 // Thread count slider
                 ui.heading("Thread count");
                 ui.add(Slider::new(&mut self.thread_count, 1..=16).text("thread"));
+                ui.horizontal(|ui| {
+                    ui.label("Priority:");
+                    for priority in [Priority::Low, Priority::Normal, Priority::High] {
+                        ui.selectable_value(&mut self.priority, priority, priority.to_string())
+                            .on_hover_text("Queued jobs of a higher priority get the threads first. Low priority jobs also run at a lower OS priority on Linux.".to_string());
+                    }
+                });
+                ui.separator();
+
+                // Quality and resize sliders
+                ui.heading("Compression");
+                ui.add(Slider::new(&mut self.quality, 1..=100).text("quality"));
+                ui.add(Slider::new(&mut self.resize_percent, 1..=100).text("% of the original width and height"));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.limit_dimension, "Max dimension");
+                    ui.add_enabled(self.limit_dimension, Slider::new(&mut self.max_dimension, 64..=8192).logarithmic(true).suffix(" px"))
+                        .on_hover_text("Images whose longer side is still larger after resizing are shrunk to it.".to_string());
+                });
+                ui.add_enabled(self.quality_table.is_none(), Checkbox::new(&mut self.auto_by_size, "Auto by file size"))
+                    .on_hover_text("Files of 1 MB or more get a lower quality and size the larger they are, and smaller files use the sliders.".to_string())
+                    .on_disabled_hover_text("The loaded quality rules are used instead.".to_string());
+                ui.checkbox(&mut self.cap_source_quality, "Never raise the quality of JPEG files")
+                    .on_hover_text("JPEG files are compressed with at most the quality they were saved with, which is estimated from the file.".to_string());
+                ui.checkbox(&mut self.copy_low_quality, "Copy JPEG files already below the quality")
+                    .on_hover_text("They are copied as they are unless they are resized.".to_string());
+                ui.checkbox(&mut self.content_aware, "Raise the quality of screenshots and text")
+                    .on_hover_text("Each image is analyzed first. Graphics get at least quality 85 and text at least 90, while photos use the slider.".to_string());
+
+                // Steps that change the images before encoding
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.denoise, "Reduce noise")
+                        .on_hover_text("Noise of high ISO photos is smoothed while keeping the edges, which makes the files much smaller.".to_string());
+                    ui.add_enabled(self.denoise, Slider::new(&mut self.denoise_strength, 1..=100).text("%"))
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::Slider, "Strength of the noise reduction"));
+                });
+                ui.checkbox(&mut self.auto_enhance, "Auto levels and white balance")
+                    .on_hover_text("Faded scans and old photos are cleaned up, so the compressed copies need no separate editing.".to_string());
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.blur_background, "Blur the background")
+                        .on_hover_text("The areas around the subjects and faces are softened, so more of the file size goes to the subjects.".to_string());
+                    ui.add_enabled(self.blur_background, Slider::new(&mut self.blur_strength, 1..=100).text("%"))
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::Slider, "Strength of the background blur"));
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.sharpen, "Sharpen after resizing")
+                        .on_hover_text("Resizing softens the edges. An unsharp mask brings them back.".to_string());
+                    ui.add_enabled(self.sharpen, DragValue::new(&mut self.sharpen_amount).clamp_range(1..=300).suffix("%"))
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Amount of sharpening"));
+                    ui.add_enabled(self.sharpen, DragValue::new(&mut self.sharpen_radius).clamp_range(0.3..=5.).speed(0.1).suffix(" px"))
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Radius of sharpening"));
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.save_thumbnails, "Save thumbnails")
+                        .on_hover_text("A small copy of each image is saved in the thumbs folder next to it, made while the image is compressed.".to_string());
+                    ui.add_enabled(self.save_thumbnails, DragValue::new(&mut self.thumbnail_size).clamp_range(16..=4096).suffix(" px"))
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Longest side of the thumbnails"));
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.save_sizes, "Also save the widths");
+                    ui.add_enabled(self.save_sizes, TextEdit::singleline(&mut self.output_widths).desired_width(100.))
+                        .on_hover_text("Widths in pixels separated by commas. Each image is decoded once for all of them.".to_string())
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Widths of the extra outputs"));
+                    ui.label("named with");
+                    ui.add_enabled(self.save_sizes, TextEdit::singleline(&mut self.suffix_pattern).desired_width(60.))
+                        .on_hover_text("{w} is replaced by the width, like photo-480w.jpg for -{w}w.".to_string())
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Suffix of the extra outputs"));
+                });
+                if self.save_sizes {
+                    if let Err(e) = OutputSpec::parse_list(&self.output_widths, &self.suffix_pattern) {
+                        ui.colored_label(Color32::RED, e.to_string());
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("EXR and HDR images:");
+                    for tone_map in [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces] {
+                        ui.selectable_value(&mut self.tone_map, tone_map, tone_map.to_string());
+                    }
+                    ui.add(DragValue::new(&mut self.exposure).clamp_range(-10.0..=10.0).speed(0.1).suffix(" EV"))
+                        .on_hover_text("Each stop doubles the light before it is mapped to the range of a JPEG file.".to_string())
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Exposure of EXR and HDR images"));
+                });
+                #[cfg(feature = "svg")]
+                ui.horizontal(|ui| {
+                    ui.label("SVG images at");
+                    ui.add(DragValue::new(&mut self.svg_dpi).clamp_range(24..=1200).suffix(" dpi"))
+                        .on_hover_text("96 dpi keeps the size written in the file, and 192 dpi doubles it.".to_string())
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Resolution of SVG images"));
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Quality rules").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() {
+                            match QualityTable::load(&path) {
+                                Ok(t) => self.quality_table = Some((path, t)),
+                                Err(e) => self.tab().messages.push(format!("Cannot load the quality rules {}: {}", path.display(), e)),
+                            }
+                        }
+                    }
+                    let mut to_clear = false;
+                    match &self.quality_table {
+                        Some((path, table)) => {
+                            ui.label(format!("{} rules from {}", table.rules.len(), path.file_name().unwrap_or_default().to_string_lossy()))
+                                .on_hover_text("Images that match no rule use the sliders.");
+                            to_clear = ui.button("clear").clicked();
+                        }
+                        None => {
+                            ui.label("The sliders are used for every image.");
+                        }
+                    }
+                    if to_clear {
+                        self.quality_table = None;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.to_try_candidates, "Try the qualities");
+                    ui.add_enabled(self.to_try_candidates, TextEdit::singleline(&mut self.candidate_qualities).desired_width(100.))
+                        .on_hover_text("Qualities separated by commas. They are used instead of the quality slider.".to_string())
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Candidate qualities"));
+                });
+                if self.to_try_candidates {
+                    ui.horizontal(|ui| {
+                        ui.label("and keep the highest one at most");
+                        ui.add(DragValue::new(&mut self.max_size_percent).clamp_range(1..=1000).suffix("%"))
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Maximum size of the kept quality"));
+                        ui.label("of the original size");
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.to_use_extension_rules, "By extension");
+                    ui.add_enabled(self.to_use_extension_rules, TextEdit::singleline(&mut self.extension_rules).hint_text("png=copy, jpg=80"))
+                        .on_hover_text("Copy the files of an extension, or compress them with their own quality.".to_string())
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Rules by extension"));
+                });
+                if self.to_use_extension_rules {
+                    if let Err(e) = ExtensionRules::parse(&self.extension_rules) {
+                        ui.colored_label(Color32::RED, e.to_string());
+                    }
+                }
                 ui.separator();
 
                 // Checkbox for archiving
@@ -115,7 +1779,7 @@ impl epi::App for App {
                 ui.checkbox(&mut self.to_zip, "Archive subdirectories");
                 if self.to_zip {
                     ui.heading("Archive folder");
-                    if ui.button("select").clicked() {
+                    if ui.button("select").on_hover_text(self.keymap.get_text(Command::SelectArchive)).clicked() || (shortcuts.select_archive && ui.is_enabled()) {
                         if let Some(path) = rfd::FileDialog::new().pick_folder() {
                             self.archive_dir = Arc::new(Some(path));
                         }
@@ -126,23 +1790,270 @@ impl epi::App for App {
                     };
                     ui.horizontal(|ui| {
                         ui.label("Path:");
-                        ui.add_sized(ui.available_size(), egui::TextEdit::multiline(&mut match archive_dir.to_str() {
-                                Some(s) => s,
-                                None => "",
-                            }).interactive(false)
-                            .hint_text("Archive folder"));
+                        ui.add_sized(ui.available_size(), egui::TextEdit::multiline(&mut archive_dir.to_string_lossy().as_ref()).interactive(false)
+                            .hint_text("Archive folder"))
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Archive folder path"));
                     });
+                    if !archive_dir.as_os_str().is_empty() {
+                        free_space_label(ui, self.free_space_monitor.get(&archive_dir), estimated_size);
+                    }
                     ui.label("Archive format: ");
                     ui.horizontal(|ui|{
-                        ui.selectable_value(&mut self.archive_format, Format::Zip, "Zip");
-                        ui.selectable_value(&mut self.archive_format, Format::Xz, "Xz");
-                        ui.selectable_value(&mut self.archive_format, Format::_7z, "7z");
+                        ui.selectable_value(&mut self.archive_format, ArchiveOutput::Archive(Format::Zip), "Zip");
+                        ui.selectable_value(&mut self.archive_format, ArchiveOutput::Archive(Format::Xz), "Xz");
+                        ui.selectable_value(&mut self.archive_format, ArchiveOutput::Archive(Format::_7z), "7z");
+                        ui.selectable_value(&mut self.archive_format, ArchiveOutput::Tar, "Tar")
+                            .on_hover_text("Uncompressed, for backups that compress or deduplicate by themselves.");
+                        ui.selectable_value(&mut self.archive_format, ArchiveOutput::TarZstd, "Zstd");
+                        ui.selectable_value(&mut self.archive_format, ArchiveOutput::Pdf, "PDF");
                     });
+                    if self.archive_format == ArchiveOutput::TarZstd {
+                        let options = &mut self.zstd_options;
+                        ui.horizontal(|ui| {
+                            ui.add(DragValue::new(&mut options.level).clamp_range(1..=22))
+                                .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Zstd level"));
+                            ui.label("level");
+                            ui.add(DragValue::new(&mut options.worker_count).clamp_range(0..=256))
+                                .on_hover_text("0 uses every core.".to_string())
+                                .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Zstd thread count"));
+                            ui.label("threads");
+                        });
+                        ui.horizontal(|ui| {
+                            let mut is_long = options.window_log > 0;
+                            if ui.checkbox(&mut is_long, "Long-range matching").changed() {
+                                options.window_log = if is_long { DEFAULT_WINDOW_LOG } else { 0 };
+                            }
+                            if is_long {
+                                ui.add(DragValue::new(&mut options.window_log).clamp_range(10..=MAX_WINDOW_LOG).prefix("window 2^"))
+                                    .on_hover_text(format!("Windows above {} need zstd -d --long=31 to extract.", DEFAULT_WINDOW_LOG))
+                                    .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Zstd window size"));
+                            }
+                        });
+                    }
+                    if self.archive_format == ArchiveOutput::Archive(Format::_7z) {
+                        // 7z archives need the console 7-Zip, which can be downloaded into the data folder.
+                        if let Some(Ok(result)) = self.seven_zip_download.as_ref().map(|r| r.try_recv()) {
+                            self.seven_zip_message = Some(match result {
+                                Ok((path, version)) => format!("7-Zip {} is downloaded to {}.", version, path.display()),
+                                Err(e) => format!("Cannot download 7-Zip: {}", e),
+                            });
+                            self.seven_zip_download = None;
+                            self.seven_zip_available = None;
+                        }
+                        let is_available = *self.seven_zip_available.get_or_insert_with(|| get_7z_executable_path().is_ok_and(is_7z_available));
+                        if !is_available {
+                            ui.horizontal(|ui| {
+                                ui.label("7-Zip is not found, so the 7z archives cannot be made.");
+                                let is_downloading = self.seven_zip_download.is_some();
+                                let download_button = ui.add_enabled(!is_downloading, egui::Button::new(if is_downloading { "Downloading..." } else { "Download 7-Zip" }))
+                                    .on_hover_text("Download the official console 7-Zip from GitHub and check its checksum.");
+                                if download_button.clicked() {
+                                    let (tx, tr) = mpsc::channel();
+                                    self.seven_zip_download = Some(tr);
+                                    self.seven_zip_message = None;
+                                    thread::spawn(move || {
+                                        if tx.send(download_7z().map_err(|e| e.to_string())).is_err() {
+                                            warn!("Cannot send the result of the 7-Zip download");
+                                        }
+                                    });
+                                }
+                            });
+                        }
+                        if let Some(message) = &self.seven_zip_message {
+                            ui.label(message);
+                        }
+                        let options = &mut self.seven_zip_options;
+                        ui.horizontal(|ui| {
+                            ui.label("7z method:");
+                            ui.selectable_value(&mut options.method, SevenZipMethod::Lzma2, "LZMA2");
+                            ui.selectable_value(&mut options.method, SevenZipMethod::Ppmd, "PPMd")
+                                .on_hover_text("Better for documents.".to_string());
+                            ui.selectable_value(&mut options.method, SevenZipMethod::Copy, "Copy")
+                                .on_hover_text("Compressed images barely shrink, so this is much faster.".to_string());
+                        });
+                        ui.horizontal(|ui| {
+                            ui.add_enabled(options.method != SevenZipMethod::Copy, DragValue::new(&mut options.dictionary_mb).clamp_range(0..=1536).suffix(" MB"))
+                                .on_hover_text("0 uses the default of 7z.".to_string())
+                                .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Dictionary size of 7z"));
+                            ui.label("dictionary");
+                            ui.checkbox(&mut options.solid, "Solid archive");
+                        });
+                    }
+                    match self.layout {
+                        Layout::Buckets if !self.is_in_place() => {
+                            ui.label("Each numbered folder is archived.");
+                        }
+                        Layout::ByDate if !self.is_in_place() => {
+                            ui.label("Each month folder is archived.");
+                        }
+                        Layout::Flat if !self.is_in_place() => {
+                            ui.label("The destination folder is archived.");
+                        }
+                        _ => {
+                            ui.add(Slider::new(&mut self.group_depth, 0..=5).text("depth of archived folders"));
+                        }
+                    }
+                    if !self.is_in_place() {
+                        ui.checkbox(&mut self.remove_intermediate, "Remove compressed folders after archiving")
+                            .on_hover_text("Folders are kept while files are left out of the archives.".to_string());
+                    }
+                    if self.archive_format != ArchiveOutput::Pdf {
+                        ui.checkbox(&mut self.verify_archives, "Verify archives by extracting them");
+                        ui.checkbox(&mut self.write_checksums, "Write a SHA256SUMS file beside each archive");
+                        ui.horizontal(|ui| {
+                            ui.label("Leave out of archives:");
+                            ui.add(TextEdit::singleline(&mut self.archive_excluded).hint_text("raw, dng"))
+                                .on_hover_text("Extensions of the files that stay only in the destination folders.".to_string())
+                                .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Extensions left out of archives"));
+                        });
+                        ui.checkbox(&mut self.keep_symlinks, "Store symbolic links as links")
+                            .on_hover_text("Otherwise the linked files and folders are copied into the archives.".to_string());
+                        ui.horizontal(|ui| {
+                            ui.label("Paths in archives:");
+                            ui.selectable_value(&mut self.archive_root, ArchiveRoot::Dir, "Folder name")
+                                .on_hover_text("album/a.jpg".to_string());
+                            ui.selectable_value(&mut self.archive_root, ArchiveRoot::Contents, "None")
+                                .on_hover_text("a.jpg".to_string());
+                            ui.selectable_value(&mut self.archive_root, ArchiveRoot::JobRoot, "From the destination")
+                                .on_hover_text("2021/album/a.jpg".to_string());
+                            ui.selectable_value(&mut self.archive_root, ArchiveRoot::Prefix, "Prefix");
+                        });
+                        if self.archive_root == ArchiveRoot::Prefix {
+                            ui.add(TextEdit::singleline(&mut self.archive_prefix).hint_text("photos/2021"))
+                                .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Prefix of the paths in archives"));
+                        }
+                        if self.archive_root != ArchiveRoot::Dir && self.archive_format == ArchiveOutput::Archive(Format::_7z) {
+                            ui.colored_label(Color32::RED, "7z archives always have the folder at their root.");
+                        }
+                    }
+                    ui.checkbox(&mut self.rearchive_changed, "Archive again the folders that changed since the last run")
+                        .on_hover_text("Folders archived in an earlier run are skipped. This reads every folder to find the changed ones.");
                 }
                 ui.separator();
 
                 // Checkbox for deleting original files
-                ui.checkbox(&mut self.to_del_origin_files, "Delete original files");
+                // Checkbox for keeping backups when compressing in place
+                if self.is_in_place() {
+                    ui.label("The destination is the original folder. Original files will be replaced.");
+                    ui.checkbox(&mut self.keep_backup, "Keep original files as .bak");
+                } else {
+                    ui.checkbox(&mut self.to_del_origin_files, "Delete original files");
+                    ui.checkbox(&mut self.to_resume, "Resume an interrupted job")
+                        .on_hover_text("Each compressed file is recorded in a journal beside the destination, so a job that was stopped skips the files it has done.".to_string());
+                }
+                ui.separator();
+
+                // Upload to remote servers
+                ui.checkbox(&mut self.upload_to_webdav, "Upload to a WebDAV server after the job")
+                    .on_hover_text("The archives when archiving, otherwise the compressed files, once they are written to the destination folder.".to_string());
+                if self.upload_to_webdav {
+                    ui.horizontal(|ui| {
+                        ui.label("URL:");
+                        ui.add(TextEdit::singleline(&mut self.webdav_url).hint_text("https://nas.local/dav/photos"))
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "URL of the WebDAV folder"));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Username:");
+                        ui.add(TextEdit::singleline(&mut self.webdav_username))
+                            .on_hover_text(format!("The password is read from the {} environment variable.", WEBDAV_PASSWORD_VAR))
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Username of the WebDAV server"));
+                    });
+                    if let Err(e) = WebDavTarget::from_env(&self.webdav_url, &self.webdav_username) {
+                        ui.colored_label(Color32::RED, e.to_string());
+                    }
+                }
+                #[cfg(feature = "s3")]
+                {
+                    ui.checkbox(&mut self.upload_to_s3, "Upload to an S3 bucket")
+                        .on_hover_text("The archives when archiving, otherwise the compressed files.".to_string());
+                    if self.upload_to_s3 {
+                        for (label, value, hint) in [("Bucket:", &mut self.s3_bucket, "my-photos"), ("Prefix:", &mut self.s3_prefix, "photos/2021"),
+                                                     ("Region:", &mut self.s3_region, "us-east-1"), ("Endpoint:", &mut self.s3_endpoint, "https://s3.us-east-1.amazonaws.com")] {
+                            ui.horizontal(|ui| {
+                                ui.label(label);
+                                ui.add(TextEdit::singleline(value).hint_text(hint))
+                                    .widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, format!("S3 {}", label.trim_end_matches(':'))));
+                            });
+                        }
+                        if let Err(e) = S3Target::from_env(&self.s3_endpoint, &self.s3_region, &self.s3_bucket, &self.s3_prefix) {
+                            ui.colored_label(Color32::RED, e.to_string());
+                        }
+                    }
+                }
+                if self.is_uploading() && !self.is_in_place() {
+                    ui.checkbox(&mut self.remove_uploaded, "Keep only the uploaded copies")
+                        .on_hover_text("Files are removed from the destination and archive folders once uploaded.".to_string());
+                }
+                ui.separator();
+
+                // Checkbox for saving a report
+                ui.checkbox(&mut self.save_report, "Save a report of compressed files (JSON, CSV)");
+                ui.checkbox(&mut self.measure_quality, "Measure the quality of compressed images (PSNR, SSIM)")
+                    .on_hover_text("Each image is compared with its original after compressing, which takes about as long as compressing it.".to_string());
+                ui.horizontal(|ui| {
+                    ui.label("JPEG encoder:");
+                    ui.selectable_value(&mut self.encoder_backend, EncoderBackend::MozJpeg, "mozjpeg")
+                        .on_hover_text("Smaller files. image-rs is used instead if mozjpeg cannot run on this system.".to_string());
+                    ui.selectable_value(&mut self.encoder_backend, EncoderBackend::ImageRs, "image-rs")
+                        .on_hover_text("Pure Rust encoder, which makes larger files.".to_string());
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Messages:");
+                    ui.selectable_value(&mut self.verbosity, Verbosity::ErrorsOnly, "errors only")
+                        .on_hover_text("Only the failures are listed, and the progress bar does not move.".to_string());
+                    ui.selectable_value(&mut self.verbosity, Verbosity::Normal, "normal");
+                    ui.selectable_value(&mut self.verbosity, Verbosity::Debug, "debug")
+                        .on_hover_text("Also the progress of each download, upload and archive.".to_string());
+                });
+                ui.checkbox(&mut self.check_update, "Check for updates on startup");
+                ui.collapsing("Keyboard shortcuts", |ui| {
+                    ui.label(format!("Commands: {}", Command::ALL.map(|c| c.name()).join(", ")));
+                    let response = ui.add(TextEdit::multiline(&mut self.shortcut_settings).hint_text("open_report = Ctrl+Shift+R\nstart = none").desired_rows(3))
+                        .on_hover_text("One command on each line with its shortcut, which replaces the default one.".to_string());
+                    response.widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, "Keyboard shortcuts"));
+                    match Keymap::parse(&self.shortcut_settings) {
+                        Ok(keymap) if response.changed() => self.keymap = keymap,
+                        Ok(_) => {}
+                        Err(e) => {
+                            ui.colored_label(Color32::RED, e);
+                        }
+                    }
+                });
+                ui.separator();
+
+                // Schedule for starting the job later
+                ui.checkbox(&mut self.to_schedule, "Start the job later");
+                if self.to_schedule {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.schedule_by_time, true, "At");
+                        ui.add(DragValue::new(&mut self.schedule_hour).clamp_range(0..=23))
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Start hour"));
+                        ui.label(":");
+                        ui.add(DragValue::new(&mut self.schedule_minute).clamp_range(0..=59))
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Start minute"));
+                        ui.radio_value(&mut self.schedule_by_time, false, "After");
+                        ui.add(DragValue::new(&mut self.countdown_minutes).clamp_range(1..=1440))
+                            .widget_info(|| WidgetInfo::labeled(WidgetType::DragValue, "Minutes until the start"));
+                        ui.label("minutes");
+                    });
+                }
+                if let Some(start) = self.tab().scheduled_start {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("The job starts at {}", start.format(TIME_FORMAT)));
+                        if ui.button("Cancel").on_hover_text(self.keymap.get_text(Command::Cancel)).clicked() || shortcuts.cancel {
+                            self.tab().scheduled_start = None;
+                            self.tab().messages.push(String::from("Scheduled job is canceled."));
+                        }
+                    });
+                }
+
+                // Power action after every job completes
+                ui.horizontal(|ui| {
+                    ui.label("After the jobs:");
+                    for action in PowerAction::ALL.into_iter().filter(PowerAction::is_supported) {
+                        ui.selectable_value(&mut self.power_action, action, action.to_string());
+                    }
+                }).response.on_hover_text(format!("Runs only when the jobs succeed, after a countdown of {} seconds that can be canceled. It is not saved.", POWER_COUNTDOWN.as_secs()));
                 ui.separator();
 
                 // Compress button group
@@ -168,67 +2079,39 @@ impl epi::App for App {
                         },
                         _ => ui.set_enabled(false),
                     }
+                    if self.tab().scheduled_start.is_some() {
+                        ui.set_enabled(false);
+                    }
 
                     // Compress button
                     let compress_button = egui::Button::new("Compress");
-                    if ui.add_sized(Vec2::new(ui.available_width(), 40.), compress_button).clicked() {
-                        self.is_ui_enable.swap(false, Ordering::Relaxed);
-                        let origin = Arc::clone(&self.origin_dir);
-                        let dest = Arc::clone(&self.dest_dir);
-                        let archive = Arc::clone(&self.archive_dir);
-                        let is_ui_enable = Arc::clone(&self.is_ui_enable);
-                        let compressor_tx = self.tx.clone();
-                        let archive_tx = self.tx.clone();
-                        let th_count = self.thread_count;
-                        let z = self.to_zip;
-                        let to_del_origin = self.to_del_origin_files;
-                        let origin_dir_list = get_dir_list_with_depth((*origin).as_ref().unwrap().to_path_buf(), 1).unwrap();
-                        let archive_format = self.archive_format.clone();
-                        
-                        thread::spawn(move || {
-                            let mut compressor = FolderCompressor::new((*origin).as_ref().unwrap().to_path_buf(), (*dest).as_ref().unwrap().to_path_buf());
-                            compressor.set_thread_count(th_count);
-                            compressor.set_delete_source(to_del_origin);
-                            compressor.set_sender(compressor_tx.unwrap());
-                            match compressor.compress() {
-                                Ok(_) => {
-                                    if !z {
-                                        is_ui_enable.swap(true, Ordering::Relaxed);
-                                    }
-                                },
-                                Err(e) => {
-                                    println!("Cannot compress the folder!: {}", e);
-                                }
-                            };
-                            if z {
-                                let mut archive_dir_list = Vec::new();
-                                let dest_dir_list = get_dir_list_with_depth((*dest).as_ref().unwrap(), 1).unwrap();
-                                for o_dir in origin_dir_list{
-                                    for d_dir in &dest_dir_list{
-                                        if o_dir.file_name().unwrap().eq(d_dir.file_name().unwrap()){
-                                            archive_dir_list.push(d_dir.to_path_buf());
-                                        }
-                                    }
-                                }
-                                let mut archiver = Archiver::new();
-                                archiver.set_destination((*archive).as_ref().unwrap().to_path_buf());
-                                archiver.set_thread_count(th_count);
-                                archiver.push_from_iter(archive_dir_list.iter());
-                                archiver.set_sender(archive_tx.unwrap());
-                                archiver.set_format(archive_format);
-                                match archiver.archive() {
-                                    Ok(_) => { is_ui_enable.swap(true, Ordering::Relaxed); }
-                                    Err(e) => {
-                                        println!("Cannot archive the folder!: {}", e);
-                                    }
-                                }
+                    let compress_response = ui.add_sized(Vec2::new(ui.available_width(), 40.), compress_button).on_hover_text(self.keymap.get_text(Command::Start));
+                    if compress_response.clicked() || (shortcuts.start && ui.is_enabled()) {
+                        match self.to_schedule {
+                            true => {
+                                let schedule = match self.schedule_by_time {
+                                    true => Schedule::At(NaiveTime::from_hms_opt(self.schedule_hour, self.schedule_minute, 0).unwrap_or_default()),
+                                    false => Schedule::After(self.countdown_minutes),
+                                };
+                                let start = schedule.start_time(Local::now());
+                                self.tab().messages.push(format!("Job scheduled {}, starts at {}", schedule, start.format(TIME_FORMAT)));
+                                self.tab().scheduled_start = Some(start);
                             }
-                        });
+                            false => self.start_job(),
+                        }
                     }
                 });
             });
             ui.add_space(10.);
 
+            // Files done so far by the job of the tab
+            if let Some(progress) = &self.tabs[self.active_tab].progress {
+                let file_name = progress.file.file_name().unwrap_or_default().to_string_lossy();
+                ui.label(format!("{} of {} files    {}", progress.done, progress.total, file_name));
+                ui.add(egui::ProgressBar::new(progress.fraction()).show_percentage());
+                ui.add_space(10.);
+            }
+
             // TextEdit for status dialog
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.horizontal_wrapped(|ui| {
@@ -236,7 +2119,7 @@ impl epi::App for App {
 
                     let mut complete_files_string = String::new();
 
-                    for line in self.complete_file_list.iter().rev(){
+                    for line in self.tabs[self.active_tab].messages.iter().rev(){
                         complete_files_string.push_str(&format!("{}\n", line));
                     }
 
@@ -249,85 +2132,43 @@ impl epi::App for App {
     }
 
     fn setup(&mut self, _ctx: &Context, _frame: &Frame, _storage: Option<&dyn Storage>) {
-        let (tx, tr) = mpsc::channel();
-        self.tr = Some(tr);
-        self.tx = Some(tx);
+        // Speak the focused widgets when built with the screen reader.
+        #[cfg(feature = "screen_reader")]
+        {
+            _ctx.memory().options.screen_reader = true;
+        }
+        self.tabs = vec![JobTab::default()];
         self.thread_count = 1;
-        self.is_ui_enable = Arc::new(AtomicBool::new(true));
-        let tx = self.tx.clone();
-        self.program_data = match ProgramData::load(DEFAULT_SAVE_FILE_PATH){
-            Ok(dir_set) => {
-                if let Err(e) = tx.unwrap().send(String::from("Loading directory history complete!")) {
-                    println!("Message passing error!: {}", e);
-                }
-                dir_set
-            },
-            Err(_) => {
-                match tx.unwrap().send(String::from("Cannot load directory save file!\nSet save file path with default.")) {
-                    Ok(_) => ProgramData::new(),
-                    Err(e) => {
-                        println!("Message passing error!: {}", e);
-                        ProgramData::new()
-                    },
-                }
-            }
-        };
-
-        self.origin_dir = match self.program_data.get_data(ORIGIN_DIR_KEY){
-            Some(DataType::Directory(Some(p))) => Arc::new(Some(p.to_path_buf())),
-            _ => Arc::new(Some(PathBuf::from(""))),
-        };
-        self.dest_dir = match self.program_data.get_data(DESTINATION_DIR_KEY) {
-            Some(DataType::Directory(Some(p))) => Arc::new(Some(p.to_path_buf())),
-            _ => Arc::new(Some(PathBuf::from(""))),
-        };
-        self.archive_dir = match self.program_data.get_data(ARCHIVE_DIR_KEY){
-            Some(DataType::Directory(Some(p))) => Arc::new(Some(p.to_path_buf())),
-            _ => Arc::new(Some(PathBuf::from(""))),
-        };
-
-        self.to_zip = match self.program_data.get_data(TO_ZIP_KEY) {
-            Some(DataType::Boolean(Some(z))) => z.clone(),
-            _ => false,
-        };
-
-        self.thread_count = match self.program_data.get_data(THREAD_COUNT_KEY) {
-            Some(DataType::Number(Some(n))) => n.clone(),
-            _ => 1,
-        } as u32;
-
-        self.to_del_origin_files = match self.program_data.get_data(DELETE_ORIGIN_KEY) {
-            Some(DataType::Boolean(Some(b))) => b.clone(),
-            _ => false,
-        };
+        self.job_manager.set_lower_os_priority(true);
+        self.schedule_by_time = true;
+        self.schedule_hour = 2;
+        self.countdown_minutes = 60;
+        self.load_settings();
 
-        self.archive_format = match self.program_data.get_data(ARCHIVE_FORMAT_KEY){
-            Some(DataType::String(Some(b))) => Format::from(b),
-            _ => Format::Zip,
-        };
+        // Check for a newer release without blocking the window.
+        if self.check_update {
+            let (tx, tr) = mpsc::channel();
+            self.update_receiver = Some(tr);
+            thread::spawn(move || {
+                match fetch_latest_release() {
+                    Ok(r) if is_newer(&r.tag_name, env!("CARGO_PKG_VERSION")) => {
+                        if tx.send(r).is_err() {
+                            warn!("Cannot send the latest release");
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Cannot check for updates: {}", e),
+                }
+            });
+        }
     }
 
     fn on_exit_event(&mut self) -> bool {
-        self.program_data.set_data(ORIGIN_DIR_KEY, DataType::Directory(Some(match &(*self.origin_dir) {
-            Some(p) => p.to_path_buf(),
-            None => PathBuf::from(""),
-        })));
-        self.program_data.set_data(DESTINATION_DIR_KEY, DataType::Directory(Some(match &(*self.dest_dir) {
-            Some(p) => p.to_path_buf(),
-            None => PathBuf::from(""),
-        })));
-        self.program_data.set_data(ARCHIVE_DIR_KEY, DataType::Directory(Some(match &(*self.archive_dir) {
-            Some(p) => p.to_path_buf(),
-            None => PathBuf::from(""),
-        })));
-        self.program_data.set_data(TO_ZIP_KEY, DataType::Boolean(Some(self.to_zip)));
-        self.program_data.set_data(THREAD_COUNT_KEY, DataType::Number(Some(self.thread_count as i32)));
-        self.program_data.set_data(DELETE_ORIGIN_KEY, DataType::Boolean(Some(self.to_del_origin_files)));
-        self.program_data.set_data(ARCHIVE_FORMAT_KEY, DataType::String(Some(self.archive_format.to_string())));
-
-        match self.program_data.save(DEFAULT_SAVE_FILE_PATH){
+        // The settings of the tab shown in the window are saved for the next start.
+        self.store_settings();
+        match self.program_data.save(get_data_path(DEFAULT_SAVE_FILE_PATH)){
             Ok(_) => {}
-            Err(e) => println!("Cannot save the directory history! : {}", e),
+            Err(e) => error!("Cannot save the directory history: {}", e),
         }
         return true;
     }
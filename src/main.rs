@@ -1,10 +1,48 @@
 #![windows_subsystem = "windows"]
 
+use std::env;
+use std::process;
 use eframe::{NativeOptions, run_native};
 use egui::Vec2;
-use ImageCompressor::App;
+use ImageCompressor::{enable_portable_mode, has_portable_flag, App, CliArgs, USAGE};
 
 fn main() {
+    // Variables that are not valid Unicode are skipped, as env::vars would panic on them.
+    let env_vars = env::vars_os().filter_map(|(n, v)| Some((n.into_string().ok()?, v.into_string().ok()?)));
+    let cli_args = match CliArgs::parse(env::args().skip(1)).and_then(|a| Ok(a.or(CliArgs::parse_env(env_vars)?))) {
+        Ok(a) => a,
+        Err(e) => {
+            attach_console();
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    // The verbosity sets the level of the log unless RUST_LOG is set.
+    match cli_args.verbosity {
+        Some(v) => env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(v.log_level().as_str())).init(),
+        None => env_logger::init(),
+    }
+    if cli_args.help || cli_args.no_gui {
+        attach_console();
+    }
+    if cli_args.help {
+        println!("{}", USAGE);
+        return;
+    }
+    // Before the app is made, since it loads the settings.
+    if cli_args.portable || has_portable_flag() {
+        if let Err(e) = enable_portable_mode() {
+            eprintln!("Cannot use the portable mode! {}", e);
+        }
+    }
+    if cli_args.no_gui {
+        if let Err(e) = App::default().run_headless(cli_args) {
+            eprintln!("Cannot complete the job! {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     let app = App::default();
     let mut win_option = NativeOptions::default();
     win_option.initial_window_size = Some(Vec2::new(480., 850.));
@@ -12,3 +50,21 @@ fn main() {
     win_option.resizable = false;
     run_native(Box::new(app), win_option);
 }
+
+/// Attach to the console of the shell that started the program, so that the usage and the messages of a headless job are shown.
+/// The windows subsystem starts the program without a console of its own, so nothing is printed otherwise.
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // It fails when the program is not started from a console, and there is nowhere to print then anyway.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::Path;
+use exif::{Exif, In, Reader, Tag};
+use image::{ImageDecoder, ImageReader};
+use crate::jpeg_quality::estimate_jpeg_quality;
+use crate::preflight::format_size;
+
+/// Metadata of an image shown in the inspector. Fields that the file does not have are `None`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImageMetadata {
+    pub file_size: u64,
+    pub dimensions: Option<(u32, u32)>,
+
+    /// Date and time the photo was taken, as written in the EXIF data.
+    pub taken_at: Option<String>,
+
+    /// Maker and model of the camera.
+    pub camera: Option<String>,
+
+    /// Description of the embedded ICC profile, or the EXIF color space.
+    pub color_profile: Option<String>,
+
+    /// Estimated quality of a JPEG file.
+    pub jpeg_quality: Option<u8>,
+}
+
+impl ImageMetadata {
+    /// Lines of the metadata to show, with a label on each.
+    pub fn to_lines(&self) -> Vec<(&'static str, String)> {
+        let unknown = || String::from("-");
+        vec![
+            ("Size", format_size(self.file_size)),
+            ("Dimensions", self.dimensions.map(|(w, h)| format!("{} x {}", w, h)).unwrap_or_else(unknown)),
+            ("Taken", self.taken_at.clone().unwrap_or_else(unknown)),
+            ("Camera", self.camera.clone().unwrap_or_else(unknown)),
+            ("Color profile", self.color_profile.clone().unwrap_or_else(unknown)),
+            ("JPEG quality", self.jpeg_quality.map(|q| format!("about {}", q)).unwrap_or_else(unknown)),
+        ]
+    }
+}
+
+/// Read the metadata of the image file. Metadata that cannot be read is left out.
+///
+/// # Error
+/// - When the file cannot be read.
+pub fn read_metadata<P: AsRef<Path>>(path: P) -> io::Result<ImageMetadata> {
+    let path = path.as_ref();
+    let file_size = fs::metadata(path)?.len();
+    let exif = Reader::new().read_from_container(&mut BufReader::new(File::open(path)?)).ok();
+    let get_text = |tag| exif.as_ref()
+        .and_then(|e| e.get_field(tag, In::PRIMARY))
+        .map(|f| f.display_value().to_string().trim_matches('"').trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    let camera = match (get_text(Tag::Make), get_text(Tag::Model)) {
+        // Many models already start with the maker.
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    };
+    let (dimensions, icc_profile) = match ImageReader::open(path).and_then(|r| r.with_guessed_format()) {
+        Ok(reader) => match reader.into_decoder() {
+            Ok(mut decoder) => (Some(decoder.dimensions()), decoder.icc_profile().ok().flatten()),
+            Err(_) => (None, None),
+        },
+        Err(_) => (None, None),
+    };
+    let color_profile = icc_profile.map(|p| get_icc_description(&p).unwrap_or_else(|| String::from("Embedded ICC profile")))
+        .or_else(|| get_exif_color_space(exif.as_ref()?));
+
+    Ok(ImageMetadata {
+        file_size,
+        dimensions,
+        taken_at: get_text(Tag::DateTimeOriginal).or_else(|| get_text(Tag::DateTime)),
+        camera,
+        color_profile,
+        jpeg_quality: estimate_jpeg_quality(path).ok().flatten(),
+    })
+}
+
+fn get_exif_color_space(exif: &Exif) -> Option<String> {
+    match exif.get_field(Tag::ColorSpace, In::PRIMARY)?.value.get_uint(0)? {
+        1 => Some(String::from("sRGB")),
+        _ => Some(String::from("Uncalibrated")),
+    }
+}
+
+/// Get the description of the ICC profile from its `desc` tag, like `sRGB IEC61966-2.1`.
+fn get_icc_description(profile: &[u8]) -> Option<String> {
+    let read_u32 = |offset: usize| Some(u32::from_be_bytes(profile.get(offset..offset + 4)?.try_into().ok()?) as usize);
+    // The tag table follows the header of 128 bytes, with 12 bytes for each tag.
+    let tag_count = read_u32(128)?;
+    let (offset, size) = (0..tag_count.min(100))
+        .map(|i| 132 + i * 12)
+        .find(|t| profile.get(*t..*t + 4) == Some(b"desc"))
+        .and_then(|t| Some((read_u32(t + 4)?, read_u32(t + 8)?)))?;
+    let tag = profile.get(offset..offset.checked_add(size)?)?;
+    let description = match tag.get(0..4)? {
+        // ICC v2 text description: the length with the terminating null, then the ASCII text.
+        b"desc" => {
+            let length = u32::from_be_bytes(tag.get(8..12)?.try_into().ok()?) as usize;
+            String::from_utf8_lossy(tag.get(12..12 + length)?).trim_end_matches('\0').to_string()
+        }
+        // ICC v4 multi-localized text: the first record, in UTF-16BE.
+        b"mluc" => {
+            let length = u32::from_be_bytes(tag.get(20..24)?.try_into().ok()?) as usize;
+            let start = u32::from_be_bytes(tag.get(24..28)?.try_into().ok()?) as usize;
+            let units = tag.get(start..start + length)?.chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    Some(description.trim().to_string()).filter(|d| !d.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::RgbImage;
+    use super::*;
+
+    /// An ICC profile with only a header and a v2 `desc` tag.
+    fn make_icc_profile(description: &str) -> Vec<u8> {
+        let mut profile = vec![0u8; 128];
+        profile.extend(1u32.to_be_bytes());
+        profile.extend(b"desc");
+        profile.extend(144u32.to_be_bytes());
+        profile.extend((12 + description.len() as u32 + 1).to_be_bytes());
+        profile.extend(b"desc\0\0\0\0");
+        profile.extend((description.len() as u32 + 1).to_be_bytes());
+        profile.extend(description.as_bytes());
+        profile.push(0);
+        profile
+    }
+
+    #[test]
+    fn icc_description_test(){
+        assert_eq!(get_icc_description(&make_icc_profile("sRGB IEC61966-2.1")), Some(String::from("sRGB IEC61966-2.1")));
+        assert_eq!(get_icc_description(&make_icc_profile("")), None);
+        assert_eq!(get_icc_description(&[0; 64]), None);
+        let mut broken = make_icc_profile("Display P3");
+        broken.truncate(150);
+        assert_eq!(get_icc_description(&broken), None);
+    }
+
+    #[test]
+    fn read_metadata_test(){
+        let test_dir = PathBuf::from("test_read_metadata");
+        fs::create_dir_all(&test_dir).unwrap();
+        let image = RgbImage::from_fn(40, 30, |x, y| image::Rgb([x as u8 * 6, y as u8 * 8, 100]));
+        let jpeg_path = test_dir.join("a.jpg");
+        JpegEncoder::new_with_quality(File::create(&jpeg_path).unwrap(), 75).encode_image(&image).unwrap();
+        let metadata = read_metadata(&jpeg_path).unwrap();
+        assert_eq!(metadata.dimensions, Some((40, 30)));
+        assert!(matches!(metadata.jpeg_quality, Some(74..=76)));
+        assert_eq!(metadata.camera, None);
+        assert_eq!(metadata.file_size, fs::metadata(&jpeg_path).unwrap().len());
+
+        fs::write(test_dir.join("b.txt"), "not an image").unwrap();
+        let metadata = read_metadata(test_dir.join("b.txt")).unwrap();
+        assert_eq!(metadata.dimensions, None);
+        assert_eq!(metadata.to_lines()[1], ("Dimensions", String::from("-")));
+        assert!(read_metadata(test_dir.join("missing.png")).is_err());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+use std::error::Error;
+use std::io;
+
+/// Widths of the extra outputs by default, for the `srcset` of web pages.
+pub const DEFAULT_OUTPUT_WIDTHS: &str = "480, 960, 1920";
+
+/// Pattern of the suffix of the extra outputs by default, where `{w}` is the width.
+pub const DEFAULT_SUFFIX_PATTERN: &str = "-{w}w";
+
+/// Extra output of each image at another width, saved next to the output as `{stem}{suffix}.jpg`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSpec {
+    /// Width in pixels. Images narrower than the width are not enlarged.
+    pub width: u32,
+    /// Suffix of the file stem, like `-480w` for `photo-480w.jpg`.
+    pub suffix: String,
+}
+
+impl OutputSpec {
+    /// Parse the widths separated by commas, such as `480, 960, 1920`, with the suffix pattern where `{w}` is the width.
+    /// Duplicate widths are removed.
+    ///
+    /// # Error
+    /// - When a width is not a positive number.
+    /// - When the pattern has no `{w}`, so the outputs would have the same name,
+    ///   or when it has a path separator or `..`, so the outputs would leave the output directory.
+    pub fn parse_list(widths: &str, pattern: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        if !pattern.contains("{w}") {
+            return Err(invalid_spec(format!("The suffix \"{}\" has no {{w}} for the width", pattern)));
+        }
+        if pattern.contains(['/', '\\']) || pattern.contains("..") {
+            return Err(invalid_spec(format!("The suffix \"{}\" cannot have a path separator or \"..\"", pattern)));
+        }
+        let mut width_list = Vec::new();
+        for width in widths.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+            match width.parse::<u32>() {
+                Ok(w) if w > 0 => width_list.push(w),
+                _ => return Err(invalid_spec(format!("\"{}\" is not a width in pixels", width))),
+            }
+        }
+        width_list.sort_unstable();
+        width_list.dedup();
+        Ok(width_list.into_iter()
+            .map(|width| OutputSpec { width, suffix: pattern.replace("{w}", &width.to_string()) })
+            .collect())
+    }
+}
+
+fn invalid_spec(message: String) -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_list_test(){
+        let specs = OutputSpec::parse_list("960, 480,,480", DEFAULT_SUFFIX_PATTERN).unwrap();
+        assert_eq!(specs, vec![
+            OutputSpec { width: 480, suffix: String::from("-480w") },
+            OutputSpec { width: 960, suffix: String::from("-960w") },
+        ]);
+        assert_eq!(OutputSpec::parse_list(DEFAULT_OUTPUT_WIDTHS, "@{w}").unwrap().len(), 3);
+        assert!(OutputSpec::parse_list("", DEFAULT_SUFFIX_PATTERN).unwrap().is_empty());
+        assert!(OutputSpec::parse_list("480, wide", DEFAULT_SUFFIX_PATTERN).is_err());
+        assert!(OutputSpec::parse_list("0", DEFAULT_SUFFIX_PATTERN).is_err());
+        assert!(OutputSpec::parse_list("480", "-small").is_err());
+        assert!(OutputSpec::parse_list("480", "/{w}").is_err());
+        assert!(OutputSpec::parse_list("480", "..{w}").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters that cannot be in a file name on Windows, FAT or exFAT.
+const ILLEGAL_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names of devices that cannot be used as a file name on Windows, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Convert a path to the Windows extended-length form (`\\?\C:\...` or `\\?\UNC\server\share\...`).
+///
+/// Paths in this form are not limited to 260 characters,
+/// and every path the compressor and archiver join onto it keeps the prefix.
+/// On other operating systems the path is returned unchanged.
+#[cfg(windows)]
+pub fn to_long_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{absolute, Component, Prefix};
+
+    let path = path.as_ref();
+    // `canonicalize` already returns the extended-length form, but only for existing paths.
+    if let Ok(p) = std::fs::canonicalize(path) {
+        return p;
+    }
+    let absolute_path = match absolute(path) {
+        Ok(p) => p,
+        Err(_) => return path.to_path_buf(),
+    };
+
+    let mut components = absolute_path.components();
+    let mut long_path = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                let mut s = OsString::from(r"\\?\");
+                s.push(prefix.as_os_str());
+                PathBuf::from(s)
+            }
+            Prefix::UNC(server, share) => {
+                let mut s = OsString::from(r"\\?\UNC\");
+                s.push(server);
+                s.push(r"\");
+                s.push(share);
+                PathBuf::from(s)
+            }
+            // Already verbatim or a device path.
+            _ => return absolute_path,
+        },
+        _ => return absolute_path,
+    };
+    long_path.push(r"\");
+    for component in components {
+        if let Component::Normal(c) = component {
+            long_path.push(c);
+        }
+    }
+    long_path
+}
+
+/// Convert a path to the Windows extended-length form (`\\?\C:\...` or `\\?\UNC\server\share\...`).
+///
+/// Paths in this form are not limited to 260 characters,
+/// and every path the compressor and archiver join onto it keeps the prefix.
+/// On other operating systems the path is returned unchanged.
+#[cfg(not(windows))]
+pub fn to_long_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().to_path_buf()
+}
+
+/// Make every name in the relative path valid on Windows, FAT and exFAT.
+///
+/// Names are normalized to Unicode NFC, illegal and control characters are replaced with `_`,
+/// trailing dots and spaces are removed, and reserved device names get a leading `_`.
+pub fn sanitize_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().components()
+        .map(|c| match c {
+            Component::Normal(name) => PathBuf::from(sanitize_name(&name.to_string_lossy())),
+            c => PathBuf::from(c.as_os_str()),
+        })
+        .collect()
+}
+
+fn sanitize_name(name: &str) -> String {
+    let name = name.nfc()
+        .map(|c| if c.is_control() || ILLEGAL_CHARS.contains(&c) { '_' } else { c })
+        .collect::<String>();
+    let name = name.trim_end_matches(['.', ' ']);
+    let device = name.split('.').next().unwrap_or_default().trim_end();
+    if name.is_empty() {
+        String::from("_")
+    } else if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(device)) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Open the file or folder with the default program of the operating system, without waiting for it.
+pub fn open_with_default_app<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let program = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(program).arg(path.as_ref()).spawn().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(windows)]
+    #[test]
+    fn long_path_disk_test(){
+        let path = to_long_path(r"C:\not_existing_dir\sub");
+        assert_eq!(path, PathBuf::from(r"\\?\C:\not_existing_dir\sub"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_path_unc_test(){
+        let path = to_long_path(r"\\server\share\not_existing_dir");
+        assert_eq!(path, PathBuf::from(r"\\?\UNC\server\share\not_existing_dir"));
+    }
+
+    #[test]
+    fn sanitize_path_test(){
+        assert_eq!(sanitize_path("dir/a:b?c*\u{7}.png"), PathBuf::from("dir/a_b_c__.png"));
+        assert_eq!(sanitize_path("album. /photo .png"), PathBuf::from("album/photo .png"));
+        assert_eq!(sanitize_path("con/Aux.tar.png"), PathBuf::from("_con/_Aux.tar.png"));
+        assert_eq!(sanitize_path(". ./cafe\u{301}.png"), PathBuf::from("_/caf\u{e9}.png"));
+        assert_eq!(sanitize_path("console/a.png"), PathBuf::from("console/a.png"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn long_path_unchanged_test(){
+        assert_eq!(to_long_path("origin/sub"), PathBuf::from("origin/sub"));
+    }
+}
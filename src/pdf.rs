@@ -0,0 +1,161 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use image_compressor::crawler::get_file_list;
+use printpdf::{ColorBits, ColorSpace, Image, ImageFilter, ImageTransform, ImageXObject, Mm, PdfDocument, PdfDocumentReference, Px};
+
+/// Resolution of the pages. Each image pixel takes 1/300 inch on its page.
+const DPI: f32 = 300.;
+
+/// Create a PDF that has one page for every JPEG image in the directory and its subdirectories.
+///
+/// The images are sorted by path and embedded as they are, without decoding and re-encoding them,
+/// and each page has the size of its image.
+/// Returns the path of the new PDF file.
+///
+/// # Error
+/// - When the PDF file already exists.
+/// - When there are no JPEG images in the directory.
+pub fn create_pdf<D: AsRef<Path>, P: AsRef<Path>>(dir: D, pdf_path: P) -> Result<PathBuf, Box<dyn Error>> {
+    let pdf_path = pdf_path.as_ref();
+    if pdf_path.exists() {
+        return Err(Box::new(io::Error::new(io::ErrorKind::AlreadyExists, format!("A file with the same name exists: {}", pdf_path.display()))));
+    }
+
+    let mut image_list = get_file_list(&dir)?
+        .into_iter()
+        .filter(|p| match p.extension().and_then(|e| e.to_str()) {
+            Some(e) => e.eq_ignore_ascii_case("jpg") || e.eq_ignore_ascii_case("jpeg"),
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    image_list.sort();
+
+    let title = match dir.as_ref().file_name() {
+        Some(s) => s.to_string_lossy().to_string(),
+        None => String::new(),
+    };
+    let mut doc: Option<PdfDocumentReference> = None;
+    for image_path in image_list {
+        let image_data = fs::read(&image_path)?;
+        let (width, height, components) = match read_jpeg_header(&image_data) {
+            Some(h) => h,
+            None => continue,
+        };
+        let page_width = Mm::from(Px(width).into_pt(DPI));
+        let page_height = Mm::from(Px(height).into_pt(DPI));
+
+        let (page, layer) = match &doc {
+            Some(d) => d.add_page(page_width, page_height, "Image"),
+            None => {
+                let (d, page, layer) = PdfDocument::new(&title, page_width, page_height, "Image");
+                doc = Some(d);
+                (page, layer)
+            }
+        };
+        let layer = doc.as_ref().unwrap().get_page(page).get_layer(layer);
+
+        let image = Image::from(ImageXObject {
+            width: Px(width),
+            height: Px(height),
+            color_space: match components {
+                1 => ColorSpace::Greyscale,
+                4 => ColorSpace::Cmyk,
+                _ => ColorSpace::Rgb,
+            },
+            bits_per_component: ColorBits::Bit8,
+            interpolate: true,
+            image_data,
+            image_filter: Some(ImageFilter::DCT),
+            smask: None,
+            clipping_bbox: None,
+        });
+        image.add_to_layer(layer, ImageTransform {
+            dpi: Some(DPI),
+            ..Default::default()
+        });
+    }
+
+    match doc {
+        Some(d) => {
+            d.save(&mut BufWriter::new(File::create(pdf_path)?))?;
+            Ok(pdf_path.to_path_buf())
+        }
+        None => Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("There are no JPEG images in the directory: {}", dir.as_ref().display())))),
+    }
+}
+
+/// Read the width, height and number of color components from the frame header of JPEG data.
+fn read_jpeg_header(data: &[u8]) -> Option<(usize, usize, u8)> {
+    if data.len() < 2 || data[0] != 0xFF || data[1] != 0xD8 {
+        return None;
+    }
+    let mut i = 2;
+    while i + 9 < data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        // Fill bytes before a marker.
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        // Start of frame markers, except DHT(C4), JPG(C8) and DAC(CC) which share the range.
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = u16::from_be_bytes([data[i + 5], data[i + 6]]) as usize;
+            let width = u16::from_be_bytes([data[i + 7], data[i + 8]]) as usize;
+            return Some((width, height, data[i + 9]));
+        }
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        i += 2 + length;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create the header of a baseline JPEG image. The image data itself is not needed to create a PDF.
+    fn jpeg_header(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        // APP0 segment
+        data.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00]);
+        // SOF0 segment
+        data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[0x03, 0x01, 0x22, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01]);
+        data.extend_from_slice(&[0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn read_jpeg_header_test(){
+        assert_eq!(read_jpeg_header(&jpeg_header(640, 480)), Some((640, 480, 3)));
+        assert_eq!(read_jpeg_header(b"not a jpeg image"), None);
+    }
+
+    #[test]
+    fn create_pdf_test(){
+        let test_dir = PathBuf::from("test_create_pdf");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let album = test_dir.join("album");
+        fs::create_dir_all(album.join("sub")).unwrap();
+        fs::write(album.join("1.jpg"), jpeg_header(640, 480)).unwrap();
+        fs::write(album.join("sub").join("2.jpg"), jpeg_header(480, 640)).unwrap();
+        fs::write(album.join("note.txt"), "not an image").unwrap();
+
+        let pdf_path = create_pdf(&album, test_dir.join("album.pdf")).unwrap();
+        assert!(fs::read(&pdf_path).unwrap().starts_with(b"%PDF"));
+        assert!(create_pdf(&album, &pdf_path).is_err());
+        assert!(create_pdf(album.join("sub").join("empty"), test_dir.join("empty.pdf")).is_err());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,2061 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::panic;
+use std::thread;
+use std::time::Instant;
+use chrono::Local;
+use image_compressor::FolderCompressor;
+use image_compressor::compressor::Factor;
+use image_compressor::crawler::get_file_list;
+use image_compressor::dir::delete_recursive;
+use log::warn;
+use zip_archive::{Archiver, get_dir_list_with_depth, Format};
+
+use crate::archive_progress::{ArchiveProgress, PROGRESS_INTERVAL};
+use crate::archive_filter::ArchiveFilter;
+use crate::archive_root::ArchiveRoot;
+use crate::archive_state::{get_fingerprint, ArchiveState};
+use crate::candidate::Candidates;
+use crate::content_class::classify_image;
+use crate::decode::{is_own_format, DecodeOptions};
+use crate::dest_lock::DestLock;
+use crate::download::download_image;
+use crate::encoder::{compress_to_jpg, is_mozjpeg_available, CompressOutput, EncoderBackend, StageTimes};
+use crate::event::{try_send_event, Event, EventSink, FileProgress, Forwarder, Severity, Stage, Verbosity};
+use crate::extension_rule::{ExtensionAction, ExtensionRules};
+use crate::image_step::{process_image, ImageStep, ProcessedImage, THUMBNAIL_DIR};
+use crate::in_place::{get_temp_dir, replace_originals};
+use crate::job_history::{append_job_record, JobRecord};
+use crate::journal::{get_journal_path, FileState, Journal};
+use crate::jpeg_quality::estimate_jpeg_quality;
+use crate::layout::{check_duplicate_pattern, get_bucket_paths, get_date_paths, get_flat_paths, get_mirrored_paths, number_duplicates, Layout, DEFAULT_DUPLICATE_PATTERN};
+use crate::path_util::{sanitize_path, to_long_path};
+use crate::output_spec::OutputSpec;
+use crate::pdf::create_pdf;
+use crate::file_select::is_excluded;
+use crate::preflight::{find_stem_collisions, format_size, get_free_space, get_total_size, is_same_dir, is_same_or_inside};
+use crate::quality_table::QualityTable;
+use crate::report::{add_quality_metrics, build_report, find_existing_outputs, get_file_sizes, save_report, CompressionSummary, FailureKind, WorkerTime};
+use crate::seven_zip::{archive_7z, SevenZipOptions};
+use crate::tar_writer::{archive_tar, archive_tar_xz, archive_tar_zstd, ZstdOptions};
+use crate::zip_writer::archive_zip;
+use crate::sidecar::{find_sidecars, get_sidecar_name};
+use crate::remote::{upload_with_retries, RemoteTarget};
+#[cfg(feature = "s3")]
+use crate::s3::S3Target;
+use crate::verify::{get_checksums_path, verify_archive, write_checksums};
+use crate::webdav::WebDavTarget;
+
+/// Output made from each compressed subdirectory.
+#[derive(PartialEq, Clone)]
+pub enum ArchiveOutput {
+    /// An archive file made by [`Archiver`].
+    Archive(Format),
+
+    /// An uncompressed tar file.
+    Tar,
+
+    /// A tar file compressed with zstd.
+    TarZstd,
+
+    /// A PDF file with one page per image.
+    Pdf,
+}
+
+impl ArchiveOutput {
+    /// Create an [`ArchiveOutput`] from the str. Unknown strings fall back to the default.
+    pub fn from(format_str: &str) -> Self {
+        match format_str {
+            "tar" => ArchiveOutput::Tar,
+            "zstd" => ArchiveOutput::TarZstd,
+            "pdf" => ArchiveOutput::Pdf,
+            "7z" | "xz" | "zip" => ArchiveOutput::Archive(Format::from(format_str)),
+            _ => ArchiveOutput::default(),
+        }
+    }
+
+    /// Get the extension of the output files, including the leading dot.
+    pub fn extension(&self) -> String {
+        match self {
+            ArchiveOutput::Archive(format) => format.extension(),
+            ArchiveOutput::Tar => String::from(".tar"),
+            ArchiveOutput::TarZstd => String::from(".tar.zst"),
+            ArchiveOutput::Pdf => String::from(".pdf"),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveOutput::Archive(format) => write!(f, "{}", format.to_string()),
+            ArchiveOutput::Tar => write!(f, "tar"),
+            ArchiveOutput::TarZstd => write!(f, "zstd"),
+            ArchiveOutput::Pdf => write!(f, "pdf"),
+        }
+    }
+}
+
+impl Default for ArchiveOutput {
+    fn default() -> Self {
+        ArchiveOutput::Archive(Format::Zip)
+    }
+}
+
+/// Pipeline that compresses a folder and then archives its subdirectories.
+///
+/// Before compressing, it checks that the destination is not inside the original folder,
+/// that no two files would be written to the same destination file,
+/// and that the destination volume has enough free space.
+/// The destination is locked while running, so that two jobs never write to it at once.
+/// If the destination is the original folder, the images are compressed in place.
+/// Otherwise each subdirectory is archived as soon as it is compressed, while the next one is compressed.
+///
+/// # Examples
+/// ```no_run
+/// use std::sync::mpsc;
+/// use ImageCompressor::{ArchiveOutput, Event, Pipeline};
+///
+/// let (tx, tr) = mpsc::channel::<Event>();
+///
+/// let mut pipeline = Pipeline::new("origin", "dest");
+/// pipeline.set_thread_count(4);
+/// pipeline.set_archive("archive", ArchiveOutput::default());
+/// pipeline.set_sender(tx);
+///
+/// match pipeline.run() {
+///     Ok(_) => {},
+///     Err(e) => println!("Cannot complete the job: {}", e),
+/// }
+/// ```
+pub struct Pipeline {
+    origin: PathBuf,
+    dest: PathBuf,
+    archive: Option<(PathBuf, ArchiveOutput)>,
+    group_depth: u32,
+    remove_intermediate: bool,
+    verify_archives: bool,
+    seven_zip_options: Option<SevenZipOptions>,
+    rearchive_changed: bool,
+    write_checksums: bool,
+    archive_filter: ArchiveFilter,
+    archive_root: ArchiveRoot,
+    archive_prefix: PathBuf,
+    zstd_options: ZstdOptions,
+    #[cfg(feature = "s3")]
+    s3_target: Option<S3Target>,
+    webdav_target: Option<WebDavTarget>,
+    remove_uploaded: bool,
+    excluded: Vec<PathBuf>,
+    url_list: Vec<String>,
+    factor: Factor,
+    quality_table: Option<QualityTable>,
+    max_dimension: Option<u32>,
+    decode_options: DecodeOptions,
+    layout: Layout,
+    bucket_size: usize,
+    duplicate_pattern: Option<String>,
+    candidates: Option<Candidates>,
+    extension_rules: Option<ExtensionRules>,
+    carry_sidecars: bool,
+    sanitize_names: bool,
+    cap_source_quality: bool,
+    copy_low_quality: bool,
+    content_aware: bool,
+    image_steps: Vec<ImageStep>,
+    thumbnail_size: Option<u32>,
+    output_specs: Vec<OutputSpec>,
+    sidecars: BTreeMap<PathBuf, Vec<PathBuf>>,
+    renamed: HashMap<PathBuf, OsString>,
+    to_resume: bool,
+    journal: Option<Journal>,
+    stop_flag: Option<Arc<AtomicBool>>,
+    thread_count: u32,
+    delete_source: bool,
+    keep_backup: bool,
+    report_dir: Option<PathBuf>,
+    history_path: Option<PathBuf>,
+    measure_quality: bool,
+    encoder_backend: EncoderBackend,
+    file_total: usize,
+    done_count: AtomicUsize,
+    failures: Mutex<HashMap<PathBuf, FailureKind>>,
+    outputs: Mutex<HashMap<PathBuf, CompressOutput>>,
+    worker_times: Mutex<Vec<WorkerTime>>,
+    sender: Option<Arc<dyn EventSink>>,
+    verbosity: Verbosity,
+}
+
+impl Pipeline {
+    /// Create a new `Pipeline` that compresses the original directory into the destination directory.
+    /// Only one thread is used by default, and nothing is archived unless [`set_archive`](Pipeline::set_archive) is called.
+    pub fn new<O: AsRef<Path>, D: AsRef<Path>>(origin: O, dest: D) -> Self {
+        Pipeline {
+            origin: to_long_path(origin),
+            dest: to_long_path(dest),
+            archive: None,
+            group_depth: 1,
+            remove_intermediate: false,
+            verify_archives: false,
+            seven_zip_options: None,
+            rearchive_changed: false,
+            write_checksums: false,
+            archive_filter: ArchiveFilter::default(),
+            archive_root: ArchiveRoot::default(),
+            archive_prefix: PathBuf::new(),
+            zstd_options: ZstdOptions::default(),
+            #[cfg(feature = "s3")]
+            s3_target: None,
+            webdav_target: None,
+            remove_uploaded: false,
+            excluded: Vec::new(),
+            url_list: Vec::new(),
+            factor: Factor::default(),
+            quality_table: None,
+            max_dimension: None,
+            decode_options: DecodeOptions::default(),
+            layout: Layout::default(),
+            bucket_size: 1000,
+            duplicate_pattern: None,
+            candidates: None,
+            extension_rules: None,
+            carry_sidecars: false,
+            sanitize_names: false,
+            cap_source_quality: false,
+            copy_low_quality: false,
+            content_aware: false,
+            image_steps: Vec::new(),
+            thumbnail_size: None,
+            output_specs: Vec::new(),
+            sidecars: BTreeMap::new(),
+            renamed: HashMap::new(),
+            to_resume: false,
+            journal: None,
+            stop_flag: None,
+            thread_count: 1,
+            delete_source: false,
+            keep_backup: false,
+            report_dir: None,
+            history_path: None,
+            measure_quality: false,
+            encoder_backend: EncoderBackend::default(),
+            file_total: 0,
+            done_count: AtomicUsize::new(0),
+            failures: Mutex::new(HashMap::new()),
+            outputs: Mutex::new(HashMap::new()),
+            worker_times: Mutex::new(Vec::new()),
+            sender: None,
+            verbosity: Verbosity::default(),
+        }
+    }
+
+    /// Archive each compressed subdirectory into the archive directory after compressing.
+    pub fn set_archive<A: AsRef<Path>>(&mut self, archive_dir: A, output: ArchiveOutput) {
+        self.archive = Some((to_long_path(archive_dir), output));
+    }
+
+    /// Set the depth of the subdirectories that are archived one by one. The default is 1.
+    ///
+    /// With depth 2, `2021/01` and `2021/02` are archived separately instead of `2021` as a whole,
+    /// and their archives are put into `2021` in the archive directory.
+    /// Depth 0 archives the whole destination directory.
+    pub fn set_group_depth(&mut self, group_depth: u32) {
+        self.group_depth = group_depth;
+    }
+
+    /// Set whether to remove each compressed directory after its archive is created.
+    ///
+    /// A directory is removed only when its archive did not exist before archiving and is not empty afterwards.
+    /// Nothing is removed when compressing in place, when the whole destination directory is archived,
+    /// or when files are left out of the archives.
+    pub fn set_remove_intermediate(&mut self, to_remove: bool) {
+        self.remove_intermediate = to_remove;
+    }
+
+    /// Set whether to extract each new archive and compare its files with the compressed directory.
+    ///
+    /// Archives that do not match are not recorded as completed, and their directories are not removed.
+    /// PDF files are not verified.
+    pub fn set_verify_archives(&mut self, to_verify: bool) {
+        self.verify_archives = to_verify;
+    }
+
+    /// Leave the files of the extensions out of the archives, while they are still compressed or copied to the destination,
+    /// and store symbolic links as links when the filter keeps them.
+    ///
+    /// Zip, tar and zstd archives are written without them, 7z is run with exclusions and `-snl`,
+    /// and xz archives are written by this crate instead of `zip_archive`. PDF files are not affected.
+    /// Compressed directories are not removed after archiving while files are left out, because the files are only in them.
+    pub fn set_archive_filter(&mut self, filter: ArchiveFilter) {
+        self.archive_filter = filter;
+    }
+
+    /// Set where the files of each directory are placed inside its archive. The prefix is used with [`ArchiveRoot::Prefix`].
+    ///
+    /// Zip, tar and zstd archives are written with the path, and xz archives are written by this crate instead of `zip_archive`.
+    /// 7z archives always have the directory at their root, so they fail with other roots.
+    pub fn set_archive_root<P: AsRef<Path>>(&mut self, root: ArchiveRoot, prefix: P) {
+        self.archive_root = root;
+        self.archive_prefix = prefix.as_ref().to_path_buf();
+    }
+
+    /// Set whether to write the hashes of the files of each new archive to a `SHA256SUMS` file beside it,
+    /// like `album.zip.SHA256SUMS`, so the archives can be audited without the compressed directories.
+    ///
+    /// Checksums are written only for archives that are completed, and verified when verifying. PDF files have none.
+    pub fn set_write_checksums(&mut self, to_write: bool) {
+        self.write_checksums = to_write;
+    }
+
+    /// Make `.tar.zst` archives with the level, long-range matching and threads of the options.
+    pub fn set_zstd_options(&mut self, options: ZstdOptions) {
+        self.zstd_options = options;
+    }
+
+    /// Upload the outputs to the S3 bucket after the run: the verified archives with their checksum files when archiving,
+    /// otherwise the compressed and copied files. They are uploaded under the prefix by their paths relative to
+    /// the archive directory or the destination, and every upload is tried again a few times when the connection fails.
+    ///
+    /// Archives completed in an earlier run are uploaded again, so that the uploads that failed are done by the next run.
+    #[cfg(feature = "s3")]
+    pub fn set_s3_target(&mut self, target: S3Target) {
+        self.s3_target = Some(target);
+    }
+
+    /// Upload the outputs to the folder on the WebDAV server after the run, the same way as
+    /// [`set_s3_target`](Pipeline::set_s3_target) does, creating the subfolders on the server.
+    /// The outputs are still written to the destination first, so the server is not a destination by itself.
+    pub fn set_webdav_target(&mut self, target: WebDavTarget) {
+        self.webdav_target = Some(target);
+    }
+
+    /// Remove the local outputs once they are uploaded to every remote target, so that the destination
+    /// and the archive directory are only staging folders. Files that fail to upload are kept.
+    /// Nothing is removed when compressing in place.
+    pub fn set_remove_uploaded(&mut self, to_remove: bool) {
+        self.remove_uploaded = to_remove;
+    }
+
+    /// Archive again the directories whose contents have changed since their archives were completed in an earlier run,
+    /// instead of skipping every completed archive.
+    ///
+    /// The contents of every directory are read to compare them, and the old archive is kept if the new one fails.
+    pub fn set_rearchive_changed(&mut self, to_rearchive: bool) {
+        self.rearchive_changed = to_rearchive;
+    }
+
+    /// Make 7z archives with the method, dictionary and solid block of the options,
+    /// instead of the fixed options of `zip_archive`.
+    pub fn set_seven_zip_options(&mut self, options: SevenZipOptions) {
+        self.seven_zip_options = Some(options);
+    }
+
+    /// Set the files and directories in the original directory that are not compressed.
+    /// The paths are relative to the original directory.
+    pub fn set_excluded(&mut self, excluded: Vec<PathBuf>) {
+        self.excluded = excluded;
+    }
+
+    /// Download the images at the URLs into the original directory before compressing,
+    /// so that remote images are compressed with the files already in it.
+    /// URLs that cannot be downloaded are reported and skipped.
+    pub fn set_url_list(&mut self, url_list: Vec<String>) {
+        self.url_list = url_list;
+    }
+
+    /// Set the quality and the resize ratio of the compressed images. The default is the `Factor` default.
+    pub fn set_factor(&mut self, factor: Factor) {
+        self.factor = factor;
+    }
+
+    /// Choose the factor of each image by the rules of the table.
+    /// Images that match no rule use the factor set by [`set_factor`](Pipeline::set_factor).
+    pub fn set_quality_table(&mut self, quality_table: QualityTable) {
+        self.quality_table = Some(quality_table);
+    }
+
+    /// Shrink each image further when its longer side would still be larger than the size in pixels after resizing.
+    /// Smaller images keep the resize ratio of their factor.
+    pub fn set_max_dimension(&mut self, max_dimension: u32) {
+        self.max_dimension = Some(max_dimension.max(1));
+    }
+
+    /// Set how the output files are placed in the destination. The default is [`Layout::Mirrored`].
+    ///
+    /// Unless mirrored, the output folders are archived one by one instead of the groups set by [`set_group_depth`](Pipeline::set_group_depth),
+    /// and the layout cannot be used when compressing in place.
+    /// [`Layout::Flat`] numbers duplicate names with [`DEFAULT_DUPLICATE_PATTERN`] unless [`set_duplicate_pattern`](Pipeline::set_duplicate_pattern) is called.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    /// Set the maximum number of files in each numbered folder of [`Layout::Buckets`]. The default is 1000.
+    pub fn set_bucket_size(&mut self, bucket_size: usize) {
+        self.bucket_size = bucket_size;
+    }
+
+    /// Give files that would be written to the same destination file new names instead of stopping the job.
+    ///
+    /// In the pattern, `{name}` is the file name without its extension, `{ext}` is the original extension
+    /// and `{n}` is the number, e.g. `{name} ({n})` or `{name}_{ext}`.
+    /// The first file of each group keeps its name, and every renamed file is reported before compressing.
+    /// Files are not renamed when compressing in place.
+    pub fn set_duplicate_pattern(&mut self, pattern: &str) {
+        self.duplicate_pattern = Some(pattern.to_string());
+    }
+
+    /// Compress each image with every candidate quality and keep the best candidate, instead of the quality of the factor.
+    ///
+    /// The resize ratio of the factor or the quality table is used for every candidate.
+    pub fn set_candidates(&mut self, candidates: Candidates) {
+        self.candidates = Some(candidates);
+    }
+
+    /// Copy the files of some extensions, or compress them with their own quality,
+    /// instead of the factor, the quality table and the candidates.
+    pub fn set_extension_rules(&mut self, extension_rules: ExtensionRules) {
+        self.extension_rules = Some(extension_rules);
+    }
+
+    /// Copy the sidecar files of each image next to its output, named after the output.
+    /// They are moved when the source files are deleted, and left as they are when compressing in place.
+    ///
+    /// See [`find_sidecars`] for the files that are taken as sidecars.
+    pub fn set_carry_sidecars(&mut self, to_carry: bool) {
+        self.carry_sidecars = to_carry;
+    }
+
+    /// Make the output names valid on Windows, FAT and exFAT with [`sanitize_path`], and report every changed name.
+    /// Names are not changed when compressing in place.
+    pub fn set_sanitize_names(&mut self, to_sanitize: bool) {
+        self.sanitize_names = to_sanitize;
+    }
+
+    /// Set whether to record each compressed file in a journal beside the destination,
+    /// and skip the files completed by an earlier run that was interrupted.
+    /// The journal is removed when the run completes. It is ignored when compressing in place.
+    pub fn set_resume(&mut self, to_resume: bool) {
+        self.to_resume = to_resume;
+    }
+
+    /// Stop compressing when the flag is set, after the files in progress are done.
+    /// The run then returns an error without archiving, and leaves the journal to resume it if it keeps one.
+    pub fn set_stop_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.stop_flag = Some(flag);
+    }
+
+    /// Set whether to compress JPEG files with at most the quality they were encoded with, estimated from their quantization tables.
+    /// Encoding a file with a higher quality than its own makes it larger without making it better.
+    pub fn set_cap_source_quality(&mut self, to_cap: bool) {
+        self.cap_source_quality = to_cap;
+    }
+
+    /// Set whether to copy JPEG files that were encoded with at most the target quality as they are, instead of compressing them.
+    /// Files are still compressed when they are resized.
+    pub fn set_copy_low_quality(&mut self, to_copy: bool) {
+        self.copy_low_quality = to_copy;
+    }
+
+    /// Set whether to raise the quality of the images classified as graphics or text, which show artifacts more than photos.
+    /// It applies to the images that no quality rule or extension rule matches.
+    pub fn set_content_aware(&mut self, to_classify: bool) {
+        self.content_aware = to_classify;
+    }
+
+    /// Set how the EXR and HDR images are tone mapped and the resolution SVG images are rasterized at,
+    /// which are decoded by this program instead of the compressor.
+    pub fn set_decode_options(&mut self, decode_options: DecodeOptions) {
+        self.decode_options = decode_options;
+    }
+
+    /// Set the steps that change each image after it is resized and before it is encoded, applied in order.
+    pub fn set_image_steps(&mut self, image_steps: Vec<ImageStep>) {
+        self.image_steps = image_steps;
+    }
+
+    /// Set to also save a thumbnail of each image, whose longest side is at most the size in pixels,
+    /// as `thumbs/{stem}.jpg` in its output directory.
+    /// The thumbnail is made from the pixels decoded for the output, so the images are not decoded twice.
+    pub fn set_thumbnail_size(&mut self, size: u32) {
+        self.thumbnail_size = Some(size.max(1));
+    }
+
+    /// Set the extra outputs of each image at other widths, saved as `{stem}{suffix}.jpg` next to its output.
+    /// They are resized from the pixels decoded for the output and compressed with its quality and encoder.
+    pub fn set_output_specs(&mut self, output_specs: Vec<OutputSpec>) {
+        self.output_specs = output_specs;
+    }
+
+    /// Set the number of threads used to compress and archive.
+    pub fn set_thread_count(&mut self, thread_count: u32) {
+        self.thread_count = thread_count;
+    }
+
+    /// Get the number of threads that the pipeline compresses and archives with.
+    pub fn get_thread_count(&self) -> u32 {
+        self.thread_count
+    }
+
+    /// Set whether to delete source files.
+    /// It is ignored when compressing in place, since the originals are replaced anyway.
+    pub fn set_delete_source(&mut self, to_delete: bool) {
+        self.delete_source = to_delete;
+    }
+
+    /// Set whether to keep the original files as `.bak` files when compressing in place.
+    pub fn set_keep_backup(&mut self, keep_backup: bool) {
+        self.keep_backup = keep_backup;
+    }
+
+    /// Save a JSON and CSV report of the compressed files to the directory.
+    pub fn set_report_dir<R: AsRef<Path>>(&mut self, report_dir: R) {
+        self.report_dir = Some(report_dir.as_ref().to_path_buf());
+    }
+
+    /// Add the totals of the job to the job history file of JSON lines, for the savings dashboard.
+    pub fn set_history_path<H: AsRef<Path>>(&mut self, history_path: H) {
+        self.history_path = Some(history_path.as_ref().to_path_buf());
+    }
+
+    /// Set whether to measure the PSNR and SSIM of each compressed image against its source for the report.
+    /// Images whose source is deleted after compressing are not measured.
+    pub fn set_measure_quality(&mut self, to_measure: bool) {
+        self.measure_quality = to_measure;
+    }
+
+    /// Set the encoder of the compressed images. The default is mozjpeg.
+    /// If mozjpeg cannot run on the system, image-rs is used instead when the pipeline runs.
+    pub fn set_encoder_backend(&mut self, backend: EncoderBackend) {
+        self.encoder_backend = backend;
+    }
+
+    /// Set Sender for message passing, or any other [`EventSink`] like a closure.
+    /// Every stage sends its messages to it as [`Event`]s, including the messages from the libraries.
+    pub fn set_sender<S: EventSink>(&mut self, sender: S) {
+        self.sender = Some(Arc::new(sender));
+    }
+
+    /// Set which events are sent by their [`Severity`]. The default is [`Verbosity::Normal`], which leaves out the details.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.verbosity = verbosity;
+    }
+
+    /// Set Sender of the messages as text, like `[compress] Compress complete! File: a.jpg`,
+    /// for the users of the string messages. [`set_sender`](Pipeline::set_sender) gives the typed events instead.
+    pub fn set_string_sender(&mut self, sender: Sender<String>) {
+        self.set_sender(sender);
+    }
+
+    /// Run the pipeline and wait until everything is done.
+    ///
+    /// The run plans the files and their outputs, compresses them, reports the results,
+    /// replaces the originals when compressing in place, archives the compressed folders and uploads the outputs.
+    ///
+    /// Since this function consume its `self`, the `Pipeline` instance is no longer available after calling this function.
+    pub fn run(mut self) -> Result<(), Box<dyn Error>> {
+        let in_place = is_same_dir(&self.dest, &self.origin);
+        if !in_place && is_same_or_inside(&self.dest, &self.origin) {
+            return Err(job_error("The destination folder must not be inside the original folder."));
+        }
+        // Held until the run returns, so that another job does not write to the destination meanwhile.
+        let _lock = DestLock::acquire(&self.dest)?;
+
+        if !self.url_list.is_empty() {
+            self.download(&self.url_list);
+        }
+
+        if self.encoder_backend == EncoderBackend::MozJpeg && !is_mozjpeg_available() {
+            self.send_with(Severity::Warning, Stage::Preflight, String::from("mozjpeg cannot run on this system, so the images are encoded by image-rs."));
+            self.encoder_backend = EncoderBackend::ImageRs;
+        }
+
+        let plan = self.plan(in_place)?;
+        let mut archived_list = self.compress_all(&plan)?;
+        if self.is_stopped() {
+            if in_place {
+                // The originals are untouched until every image is compressed.
+                if let Err(e) = fs::remove_dir_all(&plan.compress_dest) {
+                    warn!("Cannot remove the temporary folder: {}", e);
+                }
+            }
+            return Err(job_error(match self.journal {
+                Some(_) => "The job was stopped. Run it again with resuming to compress the remaining files.",
+                None => "The job was stopped.",
+            }));
+        }
+        self.report_results(&plan);
+
+        if in_place {
+            let replaced_count = replace_originals(&self.origin, &plan.compress_dest, &plan.file_list, self.keep_backup, &self.sender, self.verbosity);
+            if let Err(e) = fs::remove_dir_all(&plan.compress_dest) {
+                warn!("Cannot remove the temporary folder: {}", e);
+            }
+            self.send(Stage::Replace, format!("Replaced {} original files with compressed images.", replaced_count));
+        }
+
+        if !plan.overlap {
+            archived_list.append(&mut self.archive_groups(&plan)?);
+        }
+        self.upload_outputs(&plan, &archived_list);
+
+        if self.remove_intermediate && !in_place && self.archive_filter.is_empty() {
+            self.remove_archived(&archived_list);
+        }
+        if self.journal.take().is_some() {
+            if let Err(e) = fs::remove_file(get_journal_path(&self.dest)) {
+                warn!("Cannot remove the journal: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the files to compress and their output paths, and check that they can be written to the destination.
+    /// The journal is opened here when resuming, and the files completed by the earlier run are left out.
+    fn plan(&mut self, in_place: bool) -> Result<RunPlan, Box<dyn Error>> {
+        let mut file_list = get_file_list(&self.origin)?;
+        let excluded = self.excluded.iter().map(|p| self.origin.join(p)).collect::<Vec<_>>();
+        file_list.retain(|f| !is_excluded(f, &excluded));
+        if self.carry_sidecars && !in_place {
+            self.sidecars = find_sidecars(&file_list);
+            let sidecar_list = self.sidecars.values().flatten().cloned().collect::<HashSet<_>>();
+            file_list.retain(|f| !sidecar_list.contains(f));
+        }
+        let mut output_paths = match self.layout {
+            Layout::Mirrored => get_mirrored_paths(&self.origin, &file_list),
+            _ if in_place => return Err(job_error("Files can only be kept in their original folders when compressing in place.")),
+            Layout::Buckets => get_bucket_paths(&file_list, self.bucket_size),
+            Layout::ByDate => get_date_paths(&file_list),
+            Layout::Flat => get_flat_paths(&file_list),
+        };
+        let to_sanitize = self.sanitize_names && !in_place;
+        let mut is_rearranged = self.layout != Layout::Mirrored;
+        if to_sanitize {
+            for (source, output) in output_paths.iter_mut() {
+                let sanitized = sanitize_path(&output);
+                if sanitized == *output {
+                    continue;
+                }
+                self.send_with(Severity::Warning, Stage::Preflight, format!("Invalid name: {} is written as {}", source.display(), sanitized.display()));
+                if sanitized.file_stem() != output.file_stem() {
+                    self.renamed.insert(source.to_path_buf(), sanitized.file_stem().unwrap_or_default().to_os_string());
+                }
+                is_rearranged |= sanitized.parent() != output.parent();
+                *output = sanitized;
+            }
+        }
+        let collisions = find_stem_collisions(&output_paths);
+        let duplicate_pattern = match (&self.duplicate_pattern, self.layout) {
+            (None, Layout::Flat) => Some(DEFAULT_DUPLICATE_PATTERN.to_string()),
+            (p, _) => p.clone(),
+        };
+        if let (false, Some(pattern), false) = (collisions.is_empty(), &duplicate_pattern, in_place) {
+            check_duplicate_pattern(pattern).map_err(|e| job_error(&e))?;
+            for (source, output) in number_duplicates(&mut output_paths, &collisions, pattern).map_err(|e| job_error(&e))? {
+                self.send_with(Severity::Warning, Stage::Preflight, format!("Duplicate name: {} is written as {}", source.display(), output.display()));
+                self.renamed.insert(source, output.file_stem().unwrap_or_default().to_os_string());
+            }
+        } else if !collisions.is_empty() {
+            for files in &collisions {
+                let names = files.iter()
+                    .map(|f| f.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                self.send_with(Severity::Error, Stage::Preflight, format!("Name collision: {}", names));
+            }
+            return Err(job_error(&format!("{} groups of files would be written to the same file. Rename them and try again.", collisions.len())));
+        }
+
+        // When compressing in place, write to a temporary folder first
+        // and replace the originals after every image is compressed.
+        let compress_dest = match in_place {
+            true => {
+                let temp_dir = get_temp_dir(&self.origin)?;
+                if temp_dir.exists() {
+                    return Err(job_error(&format!("Remove the leftover temporary folder first: {}", temp_dir.display())));
+                }
+                temp_dir
+            }
+            false => self.dest.to_path_buf(),
+        };
+
+        // The compressed images are at most as large as the originals in practice,
+        // so the total size of the original files is a safe estimate of the required space.
+        let required_space = get_total_size(&file_list);
+        match get_free_space(&compress_dest) {
+            Ok(free_space) if free_space < required_space => {
+                return Err(job_error(&format!("Not enough free space in the destination: {} required, {} available.", format_size(required_space), format_size(free_space))));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Cannot check the free space of the destination: {}", e),
+        }
+
+        // The original subdirectories may be deleted while compressing.
+        let group_list = match (&self.archive, self.layout) {
+            (Some(_), Layout::Mirrored) => get_group_list(&self.origin, self.group_depth)?
+                .into_iter()
+                .map(|g| if to_sanitize { sanitize_path(g) } else { g })
+                .collect(),
+            (Some(_), _) => output_paths.values().filter_map(|p| p.parent()).map(Path::to_path_buf).collect::<BTreeSet<_>>().into_iter().collect(),
+            (None, _) => Vec::new(),
+        };
+        if self.to_resume && !in_place {
+            let journal = Journal::open(get_journal_path(&self.dest), &self.origin)?;
+            // The outputs of the files that were being compressed when the run stopped may be broken.
+            for file in journal.interrupted() {
+                if let Some(output) = output_paths.get(file) {
+                    remove_partial_output(&compress_dest.join(output));
+                }
+            }
+            if journal.done_count() > 0 {
+                file_list.retain(|f| !journal.is_done(f));
+                output_paths.retain(|f, _| !journal.is_done(f));
+                self.send(Stage::Preflight, format!("Resuming the interrupted job: {} files are already done, {} files are left.", journal.done_count(), file_list.len()));
+            }
+            self.journal = Some(journal);
+        }
+        self.file_total = file_list.len();
+        let file_sizes = get_file_sizes(&file_list);
+        let existing_outputs = find_existing_outputs(&output_paths, &compress_dest);
+
+        // Archive each group while the next one is compressed.
+        // In place, the originals are replaced only after everything is compressed, so there is nothing to overlap.
+        // Other layouts fill the output folders across the original directories, so they are archived after compressing,
+        // and so are the folders whose names are sanitized.
+        let overlap = self.archive.is_some() && !in_place && self.group_depth > 0 && !is_rearranged;
+        Ok(RunPlan {
+            in_place,
+            file_list,
+            output_paths,
+            compress_dest,
+            group_list,
+            is_rearranged,
+            overlap,
+            file_sizes,
+            existing_outputs,
+        })
+    }
+
+    /// Compress the files of the plan. Returns the archived directories when each group is archived while compressing.
+    fn compress_all(&self, plan: &RunPlan) -> Result<Vec<ArchivedDir>, Box<dyn Error>> {
+        match &self.archive {
+            Some((archive_dir, output)) if plan.overlap => {
+                return self.compress_and_archive(&plan.group_list, &plan.file_list, archive_dir, output);
+            }
+            _ if plan.is_rearranged => {
+                let output_list = plan.output_paths.iter()
+                    .filter_map(|(f, p)| Some((f.as_path(), plan.compress_dest.join(p.parent()?))))
+                    .collect::<Vec<_>>();
+                self.compress_files(&self.origin, &output_list, self.delete_source);
+            }
+            _ => self.compress_dir(&self.origin, &plan.compress_dest, &plan.file_list, self.delete_source && !plan.in_place)?,
+        }
+        Ok(Vec::new())
+    }
+
+    /// Send the summary of the compressed files, and save the report and the history if they are set.
+    fn report_results(&self, plan: &RunPlan) {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let outputs = self.outputs.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut report = build_report(&plan.output_paths, &plan.compress_dest, &self.dest, &plan.file_sizes, &plan.existing_outputs, &failures, &outputs);
+        if self.measure_quality {
+            self.send(Stage::Report, String::from("Measuring the quality of the compressed images..."));
+            add_quality_metrics(&mut report, &plan.compress_dest, &self.dest, self.thread_count);
+        }
+        let mut summary = CompressionSummary::from_report(&report);
+        summary.worker_times = self.worker_times.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        for message in summary.to_messages() {
+            self.send(Stage::Report, message);
+        }
+        if let Some(history_path) = &self.history_path {
+            let record = JobRecord::from_report(&self.origin, &report, Local::now().timestamp());
+            if let Err(e) = append_job_record(history_path, &record) {
+                self.send_with(Severity::Warning, Stage::Report, format!("Cannot add the job to the history!: {}", e));
+            }
+        }
+        if let Some(report_dir) = &self.report_dir {
+            let report_path = report_dir.join(Local::now().format("report_%Y%m%d_%H%M%S.json").to_string());
+            match save_report(&report, report_path) {
+                Ok((json_path, csv_path)) => self.send(Stage::Report, format!("Report saved: {}, {}", json_path.display(), csv_path.display())),
+                Err(e) => self.send_with(Severity::Warning, Stage::Report, format!("Cannot save the report!: {}", e)),
+            }
+        }
+    }
+
+    /// Archive the compressed groups after compressing, when they are not archived while compressing.
+    /// Returns the archived directories.
+    fn archive_groups(&self, plan: &RunPlan) -> Result<Vec<ArchivedDir>, Box<dyn Error>> {
+        let (archive_dir, output) = match &self.archive {
+            Some(a) => a,
+            None => return Ok(Vec::new()),
+        };
+        // Archives of groups in the same parent directory go to the mirrored directory in the archive directory.
+        let mut archive_dir_map: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+        for group in &plan.group_list {
+            let dir = self.dest.join(group);
+            if dir.is_dir() {
+                let parent = group.parent().map(Path::to_path_buf).unwrap_or_default();
+                archive_dir_map.entry(archive_dir.join(parent)).or_default().push(dir);
+            }
+        }
+        if archive_dir_map.is_empty() {
+            self.send(Stage::Archive, String::from("There are no folders to archive."));
+        }
+        let mut state = ArchiveState::load(archive_dir);
+        let mut archived_list = Vec::new();
+        for (archive_dir, dir_list) in archive_dir_map {
+            archived_list.append(&mut self.archive_new(&dir_list, &archive_dir, output, &mut state)?);
+        }
+        Ok(archived_list)
+    }
+
+    /// Upload the verified archives, or the outputs when nothing is archived, to every remote target,
+    /// and remove the uploaded files if it is set.
+    fn upload_outputs(&self, plan: &RunPlan, archived_list: &[ArchivedDir]) {
+        let targets = self.get_remote_targets();
+        if targets.is_empty() {
+            return;
+        }
+        let upload_list: Vec<_> = match &self.archive {
+            Some((archive_dir, _)) => archived_list.iter()
+                .filter(|a| a.is_verified)
+                .flat_map(|a| [a.archive_path.to_path_buf(), get_checksums_path(&a.archive_path)])
+                .filter(|p| p.is_file())
+                .filter_map(|p| Some((p.strip_prefix(archive_dir).ok()?.to_path_buf(), p)))
+                .collect(),
+            None => plan.output_paths.iter()
+                .filter(|(f, _)| !plan.existing_outputs.contains(f))
+                .filter_map(|(_, p)| [p.with_extension("jpg"), p.to_path_buf()].into_iter().find(|p| self.dest.join(p).is_file()))
+                .map(|p| (p.to_path_buf(), self.dest.join(p)))
+                .collect(),
+        };
+        let mut is_uploaded = vec![true; upload_list.len()];
+        for target in targets {
+            for (uploaded, result) in is_uploaded.iter_mut().zip(self.upload(target, &upload_list)) {
+                *uploaded &= result;
+            }
+        }
+        if self.remove_uploaded && !plan.in_place {
+            let root = self.archive.as_ref().map(|(a, _)| a.as_path()).unwrap_or(&self.dest);
+            let uploaded_list = upload_list.iter()
+                .zip(is_uploaded)
+                .filter(|(_, uploaded)| *uploaded)
+                .map(|((_, file), _)| file.as_path())
+                .collect::<Vec<_>>();
+            self.remove_uploaded_files(&uploaded_list, root);
+        }
+    }
+
+    /// Compress the groups one by one, and archive each group in another thread as soon as it is compressed.
+    /// Files that are not in any group are compressed at the end.
+    ///
+    /// Returns the archived directories.
+    fn compress_and_archive(&self, group_list: &[PathBuf], file_list: &[PathBuf], archive_dir: &Path, output: &ArchiveOutput) -> Result<Vec<ArchivedDir>, Box<dyn Error>> {
+        let (group_tx, group_rx) = mpsc::channel::<PathBuf>();
+        let (compress_result, archive_result) = thread::scope(|scope| {
+            let archive_handle = scope.spawn(move || {
+                let mut state = ArchiveState::load(archive_dir);
+                let mut archived_list = Vec::new();
+                for group in group_rx {
+                    let dir = self.dest.join(&group);
+                    if !dir.is_dir() {
+                        continue;
+                    }
+                    let group_archive_dir = archive_dir.join(group.parent().unwrap_or_else(|| Path::new("")));
+                    let mut archived = self.archive_new(&[dir], &group_archive_dir, output, &mut state).map_err(|e| e.to_string())?;
+                    archived_list.append(&mut archived);
+                }
+                Ok::<_, String>(archived_list)
+            });
+
+            let compress_result = self.compress_groups(group_list, file_list, group_tx);
+            let archive_result = archive_handle.join().unwrap_or_else(|_| Err(String::from("The archiving thread panicked")));
+            (compress_result, archive_result)
+        });
+        compress_result?;
+        archive_result.map_err(|e| job_error(&e))
+    }
+
+    /// Compress every group and send it to the sender when it is done, then compress the files outside the groups.
+    fn compress_groups(&self, group_list: &[PathBuf], file_list: &[PathBuf], group_tx: Sender<PathBuf>) -> Result<(), Box<dyn Error>> {
+        for group in group_list {
+            if self.is_stopped() {
+                return Ok(());
+            }
+            let group_dir = self.origin.join(group);
+            let group_file_list = file_list.iter()
+                .filter(|f| f.starts_with(&group_dir))
+                .cloned()
+                .collect::<Vec<_>>();
+            self.compress_dir(&group_dir, &self.dest.join(group), &group_file_list, self.delete_source)?;
+            // The receiver is gone only when archiving failed, and the error is returned from the archiving thread.
+            if group_tx.send(group.to_path_buf()).is_err() {
+                warn!("The archiving stage has stopped, so the folder is not archived: {}", group.display());
+            }
+        }
+        drop(group_tx);
+
+        let group_dir_list = group_list.iter().map(|g| self.origin.join(g)).collect::<Vec<_>>();
+        for file in file_list {
+            if group_dir_list.iter().any(|d| file.starts_with(d)) {
+                continue;
+            }
+            if self.is_stopped() {
+                return Ok(());
+            }
+            if let Some(parent) = file.parent().and_then(|p| p.strip_prefix(&self.origin).ok()) {
+                self.compress_file(file, &self.dest.join(parent), self.delete_source);
+            }
+        }
+        if self.delete_source {
+            match delete_recursive(&self.origin) {
+                Ok(_) => self.send(Stage::Compress, String::from("Delete source directories complete!")),
+                Err(e) => self.send_with(Severity::Warning, Stage::Compress, format!("Cannot delete source directories: {}", e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Compress the files of the root directory into the destination directory.
+    ///
+    /// `FolderCompressor` compresses everything under its root with one factor and keeps the names,
+    /// so the files are compressed one by one with the thread count when some files are excluded,
+    /// a quality table, a maximum dimension, candidates or extension rules are set, some files are renamed or have sidecars,
+    /// each file is recorded in the journal, the pipeline can be stopped, the quality is decided for each image,
+    /// image steps, thumbnails or output specs are set, the progress of each file is sent, the encoder is not mozjpeg,
+    /// or some files are in a format that is decoded by this program.
+    fn compress_dir(&self, root: &Path, dest: &Path, file_list: &[PathBuf], delete_source: bool) -> Result<(), Box<dyn Error>> {
+        let to_check_quality = self.cap_source_quality || self.copy_low_quality || self.content_aware;
+        if self.sender.is_none() && self.encoder_backend == EncoderBackend::MozJpeg && self.journal.is_none() && self.stop_flag.is_none() && !to_check_quality && self.image_steps.is_empty() && self.thumbnail_size.is_none() && self.output_specs.is_empty() && self.excluded.is_empty() && self.quality_table.is_none() && self.max_dimension.is_none() && self.candidates.is_none() && self.extension_rules.is_none() && self.renamed.is_empty() && self.sidecars.is_empty() && !file_list.iter().any(is_own_format) {
+            let mut compressor = FolderCompressor::new(root, dest);
+            compressor.set_factor(self.factor);
+            compressor.set_thread_count(self.thread_count);
+            compressor.set_delete_source(delete_source);
+            return compressor.compress();
+        }
+
+        let output_list = file_list.iter()
+            .filter_map(|f| Some((f.as_path(), dest.join(f.parent()?.strip_prefix(root).ok()?))))
+            .collect::<Vec<_>>();
+        self.compress_files(root, &output_list, delete_source);
+        Ok(())
+    }
+
+    /// Compress each file into its output directory one by one with the thread count,
+    /// then delete the empty source directories of the root directory if the source files are deleted.
+    /// The time of each worker is added to the worker times of the summary.
+    fn compress_files(&self, root: &Path, output_list: &[(&Path, PathBuf)], delete_source: bool) {
+        self.send_with(Severity::Debug, Stage::Compress, format!("Total file count: {}", output_list.len()));
+        let next_index = AtomicUsize::new(0);
+        let worker_times = thread::scope(|scope| {
+            let workers = (0..self.thread_count.max(1))
+                .map(|_| scope.spawn(|| {
+                    let mut worker = WorkerTime::default();
+                    while let Some((file, parent)) = output_list.get(next_index.fetch_add(1, Ordering::Relaxed)) {
+                        if self.is_stopped() {
+                            break;
+                        }
+                        let start = Instant::now();
+                        worker.stages += self.compress_file(file, parent, delete_source);
+                        worker.busy += start.elapsed();
+                        worker.files += 1;
+                    }
+                    worker
+                }))
+                .collect::<Vec<_>>();
+            workers.into_iter().map(|w| w.join().unwrap_or_else(|e| panic::resume_unwind(e))).collect::<Vec<_>>()
+        });
+        // Groups compressed one after another are added up by the worker.
+        let mut total_times = self.worker_times.lock().unwrap_or_else(|e| e.into_inner());
+        if total_times.len() < worker_times.len() {
+            total_times.resize(worker_times.len(), WorkerTime::default());
+        }
+        for (total, worker) in total_times.iter_mut().zip(worker_times) {
+            total.files += worker.files;
+            total.busy += worker.busy;
+            total.stages += worker.stages;
+        }
+        drop(total_times);
+        if self.is_stopped() {
+            self.send(Stage::Compress, String::from("Stopped after the files in progress."));
+            return;
+        }
+        self.send(Stage::Compress, String::from("Compress complete!"));
+        if delete_source {
+            match delete_recursive(root) {
+                Ok(_) => self.send(Stage::Compress, String::from("Delete source directories complete!")),
+                Err(e) => self.send_with(Severity::Warning, Stage::Compress, format!("Cannot delete source directories: {}", e)),
+            }
+        }
+    }
+
+    /// Compress the file into the output directory.
+    /// Returns the time spent in the stages of the encoder, which is zero for copies and failures.
+    fn compress_file(&self, file: &Path, parent: &Path, delete_source: bool) -> StageTimes {
+        let start = Instant::now();
+        if let Err(e) = fs::create_dir_all(parent) {
+            self.add_failure(file, &e);
+            self.send_with(Severity::Error, Stage::Compress, format!("Cannot create the parent directory of file {}: {}", file.display(), e));
+            return StageTimes::default();
+        }
+        let table_factor = self.quality_table.as_ref().and_then(|t| t.get_factor(file));
+        let factor = table_factor.unwrap_or(self.factor);
+        let action = self.extension_rules.as_ref().and_then(|r| r.get(file));
+        let (mut factor, candidates) = match action {
+            Some(ExtensionAction::Quality(q)) => (Factor::new(q, factor.size_ratio()), None),
+            _ => (factor, self.candidates.as_ref()),
+        };
+        if let Some(max_dimension) = self.max_dimension {
+            factor = Factor::new(factor.quality(), limit_size_ratio(file, factor.size_ratio(), max_dimension));
+        }
+        if self.content_aware && table_factor.is_none() && action.is_none() {
+            // Files that are not images fail to encode anyway.
+            if let Ok(class) = classify_image(file) {
+                factor = Factor::new(class.adjust_quality(factor.quality()), factor.size_ratio());
+            }
+        }
+        let source_quality = match (self.cap_source_quality || self.copy_low_quality) && action != Some(ExtensionAction::Copy) {
+            true => estimate_jpeg_quality(file).ok().flatten().filter(|q| *q as f32 <= factor.quality()),
+            false => None,
+        };
+        let action = match source_quality {
+            Some(_) if self.copy_low_quality && factor.size_ratio() >= 1. => Some(ExtensionAction::Copy),
+            Some(q) if self.cap_source_quality => {
+                factor = Factor::new(q as f32, factor.size_ratio());
+                action
+            }
+            _ => action,
+        };
+        // A file whose output already exists is not overwritten, so the output must not be removed when resuming.
+        let stem = self.output_stem(file);
+        let has_output = parent.join(stem).with_extension("jpg").exists() || parent.join(get_output_name(file, stem)).exists();
+        if !has_output {
+            self.record(file, FileState::Started);
+        }
+        let mut times = StageTimes::default();
+        let result = if action == Some(ExtensionAction::Copy) {
+            self.copy_file(file, parent, delete_source)
+        } else {
+            let output = if !self.image_steps.is_empty() || self.thumbnail_size.is_some() || !self.output_specs.is_empty() || is_own_format(file) {
+                self.compress_processed(file, parent, factor, candidates, delete_source)
+            } else if candidates.is_none() && !self.renamed.contains_key(file) {
+                compress_to_jpg(file, parent, factor, delete_source, self.encoder_backend)
+            } else {
+                self.compress_candidates(file, file, parent, factor, candidates, delete_source)
+            };
+            output.map(|o| {
+                times = o.times;
+                self.add_output(file, o)
+            })
+        };
+        if let Ok(p) = &result {
+            self.copy_sidecars(file, p, delete_source);
+        }
+        self.record(file, if result.is_ok() { FileState::Done } else { FileState::Failed });
+        if let Err(e) = &result {
+            self.add_failure(file, e.as_ref());
+        }
+        let (severity, message) = match (result, action) {
+            (Ok(p), Some(ExtensionAction::Copy)) => (Severity::Info, format!("Copy complete! File: {}", p.file_name().unwrap_or_default().to_string_lossy())),
+            (Ok(p), _) => (Severity::Info, format!("Compress complete! File: {}", p.file_name().unwrap_or_default().to_string_lossy())),
+            (Err(e), _) => (Severity::Error, e.to_string()),
+        };
+        let progress = FileProgress {
+            done: self.done_count.fetch_add(1, Ordering::Relaxed) + 1,
+            total: self.file_total,
+            file: file.to_path_buf(),
+            elapsed: start.elapsed(),
+        };
+        try_send_event(&self.sender, self.verbosity, Event::with_progress(Stage::Compress, message, progress).with_severity(severity));
+        times
+    }
+
+    /// Compress the file with each candidate quality into a temporary directory of the output directory,
+    /// then move the kept candidate to the output directory under its output name.
+    ///
+    /// The compressor names the output after the source file, so renamed files are compressed this way
+    /// with the quality of the factor as the only candidate when no candidates are given.
+    /// `source` is the image to encode, which is the file itself unless it is processed first.
+    fn compress_candidates(&self, file: &Path, source: &Path, parent: &Path, factor: Factor, candidates: Option<&Candidates>, delete_source: bool) -> Result<CompressOutput, Box<dyn Error>> {
+        let stem = self.output_stem(file);
+        let qualities = match candidates {
+            Some(c) => c.qualities().to_vec(),
+            None => vec![factor.quality()],
+        };
+        let mut temp_name = OsString::from(".candidates_");
+        temp_name.push(stem);
+        let temp_dir = parent.join(temp_name);
+
+        let mut outputs = Vec::new();
+        let mut error = None;
+        for (i, quality) in qualities.iter().enumerate() {
+            let candidate_dir = temp_dir.join(i.to_string());
+            let candidate_factor = Factor::new(*quality, factor.size_ratio());
+            match fs::create_dir_all(&candidate_dir).map_err(|e| e.into()).and_then(|_| compress_to_jpg(source, &candidate_dir, candidate_factor, false, self.encoder_backend)) {
+                Ok(p) => outputs.push(p),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let result = match error {
+            None => {
+                let output_sizes = outputs.iter().map(|o| o.compressed_size).collect::<Vec<_>>();
+                let source_size = file.metadata().map(|m| m.len()).unwrap_or(0);
+                let kept = candidates.and_then(|c| c.choose(source_size, &output_sizes)).unwrap_or(0);
+                // Every candidate took its time, not only the kept one.
+                let mut times = StageTimes::default();
+                for o in &outputs {
+                    times += o.times;
+                }
+                let mut output = outputs.swap_remove(kept);
+                output.times = times;
+                move_output(&output.path, parent, stem).map(|p| {
+                    output.path = p;
+                    output
+                })
+            }
+            Some(e) => {
+                // The encoder copies the images it cannot decode before returning the error.
+                let copied = temp_dir.join("0").join(source.file_name().unwrap_or_default());
+                if copied.is_file() {
+                    move_output(&copied, parent, stem)?;
+                }
+                Err(e)
+            }
+        };
+        fs::remove_dir_all(&temp_dir)?;
+        if result.is_ok() && delete_source {
+            fs::remove_file(file)?;
+        }
+        result
+    }
+
+    /// Resize the image and apply the image steps into a temporary directory of the output directory,
+    /// then compress the result without resizing it again.
+    /// Files that cannot be opened as images are compressed as they are, so that they are copied like the others.
+    fn compress_processed(&self, file: &Path, parent: &Path, factor: Factor, candidates: Option<&Candidates>, delete_source: bool) -> Result<CompressOutput, Box<dyn Error>> {
+        let mut temp_name = OsString::from(".processed_");
+        temp_name.push(self.output_stem(file));
+        let temp_dir = parent.join(temp_name);
+        fs::create_dir_all(&temp_dir)?;
+        let result = match process_image(file, &temp_dir, factor.size_ratio(), &self.image_steps, self.thumbnail_size, &self.output_specs, &self.decode_options) {
+            // The processed image is encoded, but the output replaces the file itself.
+            Ok(ProcessedImage { path, thumbnail, variants, times }) => self.compress_candidates(file, &path, parent, Factor::new(factor.quality(), 1.), candidates, false)
+                .and_then(|mut o| {
+                    o.original_size = fs::metadata(file)?.len();
+                    o.times += times;
+                    if let Some(thumbnail) = thumbnail {
+                        self.move_thumbnail(file, &thumbnail, parent);
+                    }
+                    o.times += self.compress_variants(file, &variants, &temp_dir, parent, factor.quality());
+                    Ok(o)
+                }),
+            Err(_) => self.compress_candidates(file, file, parent, factor, candidates, false),
+        };
+        fs::remove_dir_all(&temp_dir)?;
+        if result.is_ok() && delete_source {
+            fs::remove_file(file)?;
+        }
+        result
+    }
+
+    /// Move the thumbnail of the file into the `thumbs` directory of the output directory, named after the output.
+    /// The output is kept without its thumbnail if the thumbnail cannot be moved.
+    fn move_thumbnail(&self, file: &Path, thumbnail: &Path, parent: &Path) {
+        let thumbs_dir = parent.join(THUMBNAIL_DIR);
+        if let Err(e) = fs::create_dir_all(&thumbs_dir).map_err(|e| e.into()).and_then(|_| move_output(thumbnail, &thumbs_dir, self.output_stem(file))) {
+            self.send_with(Severity::Warning, Stage::Compress, format!("Cannot save the thumbnail of file {}: {}", file.display(), e));
+        }
+    }
+
+    /// Compress the resized images of the file for the output specs into the output directory,
+    /// named with the output stem and the suffix of each spec. Returns the time spent in the encoder.
+    /// The output is kept without a size that cannot be compressed.
+    fn compress_variants(&self, file: &Path, variants: &[PathBuf], temp_dir: &Path, parent: &Path, quality: f32) -> StageTimes {
+        let mut times = StageTimes::default();
+        for (variant, spec) in variants.iter().zip(&self.output_specs) {
+            let mut stem = self.output_stem(file).to_os_string();
+            stem.push(&spec.suffix);
+            let result = compress_to_jpg(variant, temp_dir, Factor::new(quality, 1.), false, self.encoder_backend).and_then(|o| {
+                times += o.times;
+                move_output(&o.path, parent, &stem)
+            });
+            if let Err(e) = result {
+                self.send_with(Severity::Warning, Stage::Compress, format!("Cannot save the {} px wide output of file {}: {}", spec.width, file.display(), e));
+            }
+        }
+        times
+    }
+
+    /// Copy the file into the output directory as it is, under its output name.
+    fn copy_file(&self, file: &Path, parent: &Path, delete_source: bool) -> Result<PathBuf, Box<dyn Error>> {
+        let target = parent.join(get_output_name(file, self.output_stem(file)));
+        if target.exists() {
+            return Err(already_exists(&target));
+        }
+        fs::copy(file, &target)?;
+        if delete_source {
+            fs::remove_file(file)?;
+        }
+        Ok(target)
+    }
+
+    /// Copy the sidecar files of the image next to its output, or move them if the source is deleted.
+    fn copy_sidecars(&self, image: &Path, output: &Path, delete_source: bool) {
+        let (sidecars, parent) = match (self.sidecars.get(image), output.parent()) {
+            (Some(s), Some(p)) => (s, p),
+            _ => return,
+        };
+        for sidecar in sidecars {
+            let target = parent.join(get_sidecar_name(sidecar, image, output));
+            let result = match target.exists() {
+                true => Err(already_exists(&target)),
+                false => fs::copy(sidecar, &target)
+                    .and_then(|_| if delete_source { fs::remove_file(sidecar) } else { Ok(()) })
+                    .map_err(|e| e.into()),
+            };
+            if let Err(e) = result {
+                self.send_with(Severity::Warning, Stage::Compress, format!("Cannot copy the sidecar file {}: {}", sidecar.display(), e));
+            }
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop_flag.as_ref().map(|f| f.load(Ordering::SeqCst)).unwrap_or(false)
+    }
+
+    /// Record the state of the file in the journal, if the pipeline keeps one.
+    fn record(&self, file: &Path, state: FileState) {
+        if let Some(Err(e)) = self.journal.as_ref().map(|j| j.record(file, state)) {
+            warn!("Cannot record {} in the journal: {}", file.display(), e);
+        }
+    }
+
+    /// Keep the compressed image of the file for the report, and return its path.
+    fn add_output(&self, file: &Path, output: CompressOutput) -> PathBuf {
+        let path = output.path.to_path_buf();
+        self.outputs.lock().unwrap_or_else(|e| e.into_inner()).insert(file.to_path_buf(), output);
+        path
+    }
+
+    /// Keep the category of the error of the file for the report.
+    fn add_failure(&self, file: &Path, error: &(dyn Error + 'static)) {
+        self.failures.lock().unwrap_or_else(|e| e.into_inner()).insert(file.to_path_buf(), FailureKind::from_error(error));
+    }
+
+    /// Get the output name of the file without its extension, which is changed when the file is renamed.
+    fn output_stem<'a>(&'a self, file: &'a Path) -> &'a OsStr {
+        self.renamed.get(file).map(|s| s.as_os_str()).or_else(|| file.file_stem()).unwrap_or_default()
+    }
+
+    /// Archive the directories, and return them with their archive files.
+    ///
+    /// Directories whose archives are completed in an earlier run are skipped, unless their contents have changed
+    /// when [`set_rearchive_changed`](Pipeline::set_rearchive_changed) is set,
+    /// and the newly completed archives are recorded in the state.
+    fn archive_new(&self, dir_list: &[PathBuf], archive_dir: &Path, output: &ArchiveOutput, state: &mut ArchiveState) -> Result<Vec<ArchivedDir>, Box<dyn Error>> {
+        let mut archived_list = Vec::new();
+        let mut to_archive = Vec::new();
+        for dir in dir_list {
+            let archive_path = get_archive_path(dir, archive_dir, output);
+            let fingerprint = match self.rearchive_changed {
+                true => get_fingerprint(dir).ok(),
+                false => None,
+            };
+            if !state.is_archived(&archive_path) {
+                let is_new = !archive_path.exists();
+                to_archive.push(PendingArchive { dir: dir.to_path_buf(), archive_path, is_new, fingerprint, backup: None });
+                continue;
+            }
+            match (state.get_fingerprint(&archive_path), &fingerprint) {
+                (Some(old), Some(new)) if old != new => {
+                    // Keep the old archive until the new one is completed.
+                    let mut backup = archive_path.as_os_str().to_os_string();
+                    backup.push(".old");
+                    let backup = PathBuf::from(backup);
+                    match fs::rename(&archive_path, &backup) {
+                        Ok(_) => {
+                            self.send(Stage::Archive, format!("Archiving again the folder changed since an earlier run: {}", dir.display()));
+                            to_archive.push(PendingArchive { dir: dir.to_path_buf(), archive_path, is_new: true, fingerprint, backup: Some(backup) });
+                        }
+                        Err(e) => {
+                            self.send_with(Severity::Error, Stage::Archive, format!("Cannot archive again the changed folder {}: {}", dir.display(), e));
+                            archived_list.push(ArchivedDir { dir: dir.to_path_buf(), archive_path, is_verified: false });
+                        }
+                    }
+                    continue;
+                }
+                // Archives recorded without a fingerprint are taken as unchanged, and compared from the next run.
+                (None, Some(_)) => {
+                    if let Err(e) = state.insert(&archive_path, fingerprint) {
+                        warn!("Cannot save the archive state: {}", e);
+                    }
+                }
+                _ => {}
+            }
+            self.send_with(Severity::Debug, Stage::Archive, format!("Skipped the folder archived in an earlier run: {}", dir.display()));
+            archived_list.push(ArchivedDir { dir: dir.to_path_buf(), archive_path, is_verified: true });
+        }
+        if to_archive.is_empty() {
+            return Ok(archived_list);
+        }
+
+        let to_archive_dir_list = to_archive.iter().map(|p| p.dir.to_path_buf()).collect::<Vec<_>>();
+        self.archive(&to_archive_dir_list, archive_dir, output)?;
+        for PendingArchive { dir, archive_path, is_new, fingerprint, backup } in to_archive {
+            let mut is_verified = is_new && fs::metadata(&archive_path).map(|m| m.len() > 0).unwrap_or(false);
+            if is_verified && self.verify_archives && *output != ArchiveOutput::Pdf {
+                is_verified = self.verify(&archive_path, &dir, output);
+            }
+            if is_verified && self.write_checksums && *output != ArchiveOutput::Pdf {
+                let (severity, message) = match write_checksums(&dir, &archive_path, &self.get_archive_root(&dir), &self.archive_filter) {
+                    Ok(p) => (Severity::Info, format!("Checksums written: {}", p.display())),
+                    Err(e) => (Severity::Error, format!("Cannot write the checksums of {}: {}", archive_path.display(), e)),
+                };
+                self.send_with(severity, Stage::Archive, message);
+            }
+            if is_verified {
+                if let Err(e) = state.insert(&archive_path, fingerprint) {
+                    warn!("Cannot save the archive state: {}", e);
+                }
+            }
+            if let Some(backup) = backup {
+                let result = match is_verified {
+                    true => fs::remove_file(&backup),
+                    false => fs::remove_file(&archive_path).or(Ok(())).and_then(|_| fs::rename(&backup, &archive_path)),
+                };
+                if let Err(e) = result {
+                    warn!("Cannot clean up the old archive {}: {}", backup.display(), e);
+                }
+            }
+            archived_list.push(ArchivedDir { dir, archive_path, is_verified });
+        }
+        Ok(archived_list)
+    }
+
+    /// Extract the archive and compare it with the directory. Returns whether they match.
+    fn verify(&self, archive_path: &Path, dir: &Path, output: &ArchiveOutput) -> bool {
+        match verify_archive(archive_path, dir, output, &self.get_archive_root(dir), &self.archive_filter) {
+            Ok(mismatches) if mismatches.is_empty() => {
+                self.send(Stage::Archive, format!("Verified the archive: {}", archive_path.display()));
+                true
+            }
+            Ok(mismatches) => {
+                for mismatch in mismatches {
+                    self.send_with(Severity::Error, Stage::Archive, format!("Verification failed for {}: {}", archive_path.display(), mismatch));
+                }
+                false
+            }
+            Err(e) => {
+                self.send_with(Severity::Error, Stage::Archive, format!("Cannot verify the archive {}: {}", archive_path.display(), e));
+                false
+            }
+        }
+    }
+
+    /// Download the images at the URLs into the original directory one by one.
+    fn download(&self, url_list: &[String]) {
+        let mut downloaded_count = 0;
+        for (i, url) in url_list.iter().enumerate() {
+            self.send(Stage::Download, format!("Downloading {}/{}: {}", i + 1, url_list.len(), url));
+            match download_image(url, &self.origin) {
+                Ok(path) => {
+                    downloaded_count += 1;
+                    self.send(Stage::Download, format!("Downloaded {} to {}", url, path.display()));
+                }
+                Err(e) => self.send_with(Severity::Error, Stage::Download, format!("Cannot download {}: {}", url, e)),
+            }
+        }
+        self.send(Stage::Download, format!("Downloaded {} of {} images", downloaded_count, url_list.len()));
+    }
+
+    /// Get the remote targets that the outputs are uploaded to.
+    fn get_remote_targets(&self) -> Vec<&dyn RemoteTarget> {
+        let mut targets: Vec<&dyn RemoteTarget> = Vec::new();
+        #[cfg(feature = "s3")]
+        if let Some(target) = &self.s3_target {
+            targets.push(target);
+        }
+        if let Some(target) = &self.webdav_target {
+            targets.push(target);
+        }
+        targets
+    }
+
+    /// Upload the files to the target by their relative paths, and send the progress and the failures of each upload.
+    /// Returns whether each file is uploaded.
+    fn upload(&self, target: &dyn RemoteTarget, upload_list: &[(PathBuf, PathBuf)]) -> Vec<bool> {
+        let total_count = upload_list.len();
+        let mut results = Vec::new();
+        for (i, (relative_path, file)) in upload_list.iter().enumerate() {
+            let url = target.get_url(relative_path);
+            self.send(Stage::Upload, format!("Uploading {}/{}: {}", i + 1, total_count, file.display()));
+            let result = upload_with_retries(target, file, relative_path, |attempt, e| {
+                self.send_with(Severity::Warning, Stage::Upload, format!("Upload of {} failed on attempt {}, trying again: {}", file.display(), attempt, e));
+            });
+            match &result {
+                Ok(_) => self.send(Stage::Upload, format!("Uploaded {} to {}", file.display(), url)),
+                Err(e) => self.send_with(Severity::Error, Stage::Upload, format!("Cannot upload {} to {}: {}", file.display(), url, e)),
+            }
+            results.push(result.is_ok());
+        }
+        let uploaded_count = results.iter().filter(|r| **r).count();
+        self.send(Stage::Upload, format!("Uploaded {} of {} files to {}", uploaded_count, total_count, target.get_url(Path::new(""))));
+        results
+    }
+
+    /// Remove the uploaded files, and then their folders under the root that are left empty.
+    fn remove_uploaded_files(&self, file_list: &[&Path], root: &Path) {
+        for file in file_list {
+            if let Err(e) = fs::remove_file(file) {
+                self.send_with(Severity::Warning, Stage::Upload, format!("Cannot remove the uploaded file {}: {}", file.display(), e));
+                continue;
+            }
+            for dir in file.ancestors().skip(1).take_while(|d| d.starts_with(root) && !is_same_dir(d, root)) {
+                if fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+        self.send(Stage::Upload, format!("Removed {} uploaded files from {}", file_list.len(), root.display()));
+    }
+
+    /// Remove the archived directories whose archives are verified.
+    fn remove_archived(&self, archived_list: &[ArchivedDir]) {
+        for ArchivedDir { dir, archive_path, is_verified } in archived_list {
+            if is_same_dir(dir, &self.dest) {
+                continue;
+            }
+            let (severity, message) = match is_verified {
+                true => match fs::remove_dir_all(dir) {
+                    Ok(_) => (Severity::Info, format!("Removed the compressed folder {}, archived to {}", dir.display(), archive_path.display())),
+                    Err(e) => (Severity::Warning, format!("Cannot remove the compressed folder {}: {}", dir.display(), e)),
+                },
+                false => (Severity::Warning, format!("Kept the compressed folder because its archive was not created: {}", dir.display())),
+            };
+            self.send_with(severity, Stage::Archive, message);
+        }
+    }
+
+    fn send(&self, stage: Stage, message: String) {
+        self.send_with(Severity::Info, stage, message);
+    }
+
+    fn send_with(&self, severity: Severity, stage: Stage, message: String) {
+        try_send_event(&self.sender, self.verbosity, Event::new(stage, message).with_severity(severity));
+    }
+
+    /// Start forwarding the messages of a library if the sender is set.
+    fn forwarder(&self, stage: Stage) -> Option<Forwarder> {
+        self.sender.as_ref().map(|s| Forwarder::new(s, stage, self.verbosity))
+    }
+
+    /// Get the path in the archive that the files of the directory are placed under.
+    fn get_archive_root(&self, dir: &Path) -> PathBuf {
+        self.archive_root.get_path(dir, &self.dest, &self.archive_prefix)
+    }
+
+    /// Archive every directory in the list with the function, instead of `Archiver`.
+    /// The messages are the same as `Archiver`, and a directory that fails does not stop the others.
+    fn archive_each<F>(&self, dir_list: &[PathBuf], archive_dir: &Path, output: &ArchiveOutput, archive_fn: F) -> Result<(), Box<dyn Error>>
+        where F: Fn(&Path, &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(archive_dir)?;
+        self.send_with(Severity::Debug, Stage::Archive, format!("Total archive directory count: {}", dir_list.len()));
+        for dir in dir_list {
+            let archive_path = get_archive_path(dir, archive_dir, output);
+            let (severity, message) = match archive_fn(dir, &archive_path) {
+                Ok(_) => (Severity::Info, format!("{} archiving complete: {}", output, archive_path.display())),
+                Err(e) => (Severity::Error, format!("{} archiving error occured!: {}", output, e)),
+            };
+            self.send_with(severity, Stage::Archive, message);
+        }
+        self.send(Stage::Archive, String::from("Archiving Complete!"));
+        Ok(())
+    }
+
+    /// Run the archive function while reporting the progress of the archives of the directories.
+    fn with_archive_progress<F>(&self, dir_list: &[PathBuf], archive_dir: &Path, output: &ArchiveOutput, archive_fn: F) -> Result<(), Box<dyn Error>>
+        where F: FnOnce() -> Result<(), Box<dyn Error>> {
+        let mut progress_list = dir_list.iter()
+            .map(|d| ArchiveProgress::new(d, get_archive_path(d, archive_dir, output), output))
+            .collect::<Vec<_>>();
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            scope.spawn(move || self.report_archive_progress(&mut progress_list, stop_rx));
+            let result = archive_fn();
+            drop(stop_tx);
+            result
+        })
+    }
+
+    /// Report the progress of the archives every [`PROGRESS_INTERVAL`] until the stop sender is dropped.
+    fn report_archive_progress(&self, progress_list: &mut [ArchiveProgress], stop_rx: Receiver<()>) {
+        while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(PROGRESS_INTERVAL) {
+            for progress in progress_list.iter_mut() {
+                if let Some(percent) = progress.poll() {
+                    self.send_with(Severity::Debug, Stage::Archive, format!("Archiving {}: about {}%", progress.name(), percent));
+                }
+            }
+        }
+    }
+
+    /// Make the archive output of every directory in the list.
+    fn archive(&self, dir_list: &[PathBuf], archive_dir: &Path, output: &ArchiveOutput) -> Result<(), Box<dyn Error>> {
+        let filter = &self.archive_filter;
+        match output {
+            ArchiveOutput::Archive(format) => {
+                let mut archiver = Archiver::new();
+                archiver.set_destination(archive_dir);
+                archiver.set_thread_count(self.thread_count);
+                archiver.push_from_iter(dir_list.iter());
+                let forwarder = self.forwarder(Stage::Archive);
+                if let Some(f) = &forwarder {
+                    archiver.set_sender(f.sender());
+                }
+                archiver.set_format(format.clone());
+                let is_default_root = self.archive_root == ArchiveRoot::Dir;
+                let result = self.with_archive_progress(dir_list, archive_dir, output, || match (format, &self.seven_zip_options) {
+                    (Format::_7z, _) if !is_default_root => self.archive_each(dir_list, archive_dir, output, |_, _| {
+                        Err(job_error("7z archives always have the folder at their root!"))
+                    }),
+                    (Format::_7z, options) => {
+                        let options = options.unwrap_or_default();
+                        self.archive_each(dir_list, archive_dir, output, |d, a| archive_7z(d, a, &options, filter))
+                    }
+                    (Format::Zip, _) => self.archive_each(dir_list, archive_dir, output, |d, a| archive_zip(d, a, &self.get_archive_root(d), filter)),
+                    (Format::Xz, _) if !filter.is_default() || !is_default_root => {
+                        self.archive_each(dir_list, archive_dir, output, |d, a| archive_tar_xz(d, a, &self.get_archive_root(d), filter))
+                    }
+                    _ => archiver.archive(),
+                });
+                drop(archiver);
+                if let Some(f) = forwarder {
+                    f.join();
+                }
+                result
+            }
+            ArchiveOutput::Tar => self.with_archive_progress(dir_list, archive_dir, output, || {
+                self.archive_each(dir_list, archive_dir, output, |d, a| archive_tar(d, a, &self.get_archive_root(d), filter))
+            }),
+            ArchiveOutput::TarZstd => self.with_archive_progress(dir_list, archive_dir, output, || {
+                self.archive_each(dir_list, archive_dir, output, |d, a| archive_tar_zstd(d, a, &self.zstd_options, &self.get_archive_root(d), filter))
+            }),
+            ArchiveOutput::Pdf => {
+                fs::create_dir_all(archive_dir)?;
+                for dir in dir_list {
+                    let (severity, message) = match create_pdf(dir, get_archive_path(dir, archive_dir, output)) {
+                        Ok(p) => (Severity::Info, format!("pdf archiving complete: {}", p.display())),
+                        Err(e) => (Severity::Error, format!("pdf archiving error occured!: {}", e)),
+                    };
+                    self.send_with(severity, Stage::Archive, message);
+                }
+                self.send(Stage::Archive, String::from("Archiving Complete!"));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Files of a run and where their outputs go, decided before compressing.
+struct RunPlan {
+    in_place: bool,
+    file_list: Vec<PathBuf>,
+    /// Output path of every file, relative to `compress_dest`.
+    output_paths: BTreeMap<PathBuf, PathBuf>,
+    /// The destination, or the temporary folder when compressing in place.
+    compress_dest: PathBuf,
+    group_list: Vec<PathBuf>,
+    /// Whether the outputs are not placed as the originals, by the layout or the sanitized folder names.
+    is_rearranged: bool,
+    /// Whether each group is archived while the next one is compressed.
+    overlap: bool,
+    file_sizes: Vec<(PathBuf, u64)>,
+    existing_outputs: Vec<PathBuf>,
+}
+
+/// Directory to be archived.
+struct PendingArchive {
+    dir: PathBuf,
+    archive_path: PathBuf,
+    /// Whether the archive does not exist before archiving, so a completed archive is made by this run.
+    is_new: bool,
+    fingerprint: Option<String>,
+    /// The archive of an earlier run, moved aside until the new archive is completed.
+    backup: Option<PathBuf>,
+}
+
+/// Directory that has been archived.
+struct ArchivedDir {
+    dir: PathBuf,
+    archive_path: PathBuf,
+
+    /// Whether the archive is newly created and not empty, or completed in an earlier run.
+    is_verified: bool,
+}
+
+/// Get the path of the file that is made from the directory in the archive directory.
+///
+/// `zip_archive` replaces the extension of the directory name, so `album.v2` becomes `album.zip`.
+fn get_archive_path(dir: &Path, archive_dir: &Path, output: &ArchiveOutput) -> PathBuf {
+    let file_name = PathBuf::from(dir.file_name().unwrap_or_default());
+    match output {
+        ArchiveOutput::Archive(_) | ArchiveOutput::Tar | ArchiveOutput::TarZstd => archive_dir.join(file_name.with_extension(&output.extension()[1..])),
+        ArchiveOutput::Pdf => {
+            let mut file_name = file_name.into_os_string();
+            file_name.push(output.extension());
+            archive_dir.join(file_name)
+        }
+    }
+}
+
+/// Get the size ratio that keeps the longer side of the image at most `max_dimension` pixels,
+/// or `size_ratio` when the image is already small enough or cannot be read.
+fn limit_size_ratio(file: &Path, size_ratio: f32, max_dimension: u32) -> f32 {
+    match image::image_dimensions(file) {
+        Ok((width, height)) if width.max(height) as f32 * size_ratio > max_dimension as f32 => max_dimension as f32 / width.max(height) as f32,
+        _ => size_ratio,
+    }
+}
+
+/// Move the output file into the directory, named with the stem and its own extension.
+///
+/// An existing file is not overwritten, like the compressor does.
+fn move_output(output: &Path, dir: &Path, stem: &OsStr) -> Result<PathBuf, Box<dyn Error>> {
+    let target = dir.join(get_output_name(output, stem));
+    if target.exists() {
+        return Err(already_exists(&target));
+    }
+    fs::rename(output, &target)?;
+    Ok(target)
+}
+
+/// Get the file name made of the stem and the extension of the file.
+fn get_output_name(file: &Path, stem: &OsStr) -> OsString {
+    let mut file_name = stem.to_os_string();
+    if let Some(e) = file.extension() {
+        file_name.push(".");
+        file_name.push(e);
+    }
+    file_name
+}
+
+/// Remove the output of a file that was being compressed when the run stopped,
+/// which is `{stem}.jpg` when compressed or the original file name when copied, and its temporary folders.
+fn remove_partial_output(output: &Path) {
+    for path in [output.with_extension("jpg"), output.to_path_buf()] {
+        if path.is_file() {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Cannot remove the partial output {}: {}", path.display(), e);
+            }
+        }
+    }
+    for prefix in [".candidates_", ".processed_"] {
+        let mut temp_name = OsString::from(prefix);
+        temp_name.push(output.file_stem().unwrap_or_default());
+        let temp_dir = output.with_file_name(temp_name);
+        if temp_dir.is_dir() {
+            if let Err(e) = fs::remove_dir_all(&temp_dir) {
+                warn!("Cannot remove the temporary folder {}: {}", temp_dir.display(), e);
+            }
+        }
+    }
+}
+
+fn already_exists(target: &Path) -> Box<dyn Error> {
+    let message = format!("A file with the same name exists: {}", target.file_name().unwrap_or_default().to_string_lossy());
+    Box::new(io::Error::new(io::ErrorKind::AlreadyExists, message))
+}
+
+/// Get the directories at the depth, relative to the root directory.
+fn get_group_list<R: AsRef<Path>>(root: R, depth: u32) -> io::Result<Vec<PathBuf>> {
+    Ok(get_dir_list_with_depth(&root, depth)?
+        .into_iter()
+        .filter_map(|d| d.strip_prefix(&root).ok().map(Path::to_path_buf))
+        .collect())
+}
+
+fn job_error(message: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::ToneMap;
+    use super::*;
+
+    #[test]
+    fn archive_path_test(){
+        let dir = Path::new("dest/2021/01");
+        let archive_dir = Path::new("archive/2021");
+        assert_eq!(get_archive_path(dir, archive_dir, &ArchiveOutput::Archive(Format::Zip)), PathBuf::from("archive/2021/01.zip"));
+        assert_eq!(get_archive_path(dir, archive_dir, &ArchiveOutput::Archive(Format::Xz)), PathBuf::from("archive/2021/01.tar.xz"));
+        assert_eq!(get_archive_path(dir, archive_dir, &ArchiveOutput::Tar), PathBuf::from("archive/2021/01.tar"));
+        assert_eq!(get_archive_path(dir, archive_dir, &ArchiveOutput::TarZstd), PathBuf::from("archive/2021/01.tar.zst"));
+        assert_eq!(get_archive_path(dir, archive_dir, &ArchiveOutput::Pdf), PathBuf::from("archive/2021/01.pdf"));
+        let dir = Path::new("dest/album.v2");
+        assert_eq!(get_archive_path(dir, archive_dir, &ArchiveOutput::Archive(Format::_7z)), PathBuf::from("archive/2021/album.7z"));
+        assert_eq!(get_archive_path(dir, archive_dir, &ArchiveOutput::Pdf), PathBuf::from("archive/2021/album.v2.pdf"));
+    }
+
+    #[test]
+    fn report_failure_test(){
+        let test_dir = PathBuf::from("test_report_failure");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let origin = test_dir.join("origin");
+        let dest = test_dir.join("dest");
+        let report_dir = test_dir.join("report");
+        fs::create_dir_all(&origin).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        image::RgbImage::from_pixel(8, 8, image::Rgb([10, 20, 30])).save(origin.join("a.png")).unwrap();
+        fs::copy(origin.join("a.png"), origin.join("d.png")).unwrap();
+        fs::write(dest.join("d.jpg"), "existing").unwrap();
+        fs::write(origin.join("b.txt"), "not an image").unwrap();
+        let mut broken = fs::read(origin.join("a.png")).unwrap();
+        broken.truncate(20);
+        fs::write(origin.join("c.png"), broken).unwrap();
+
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_encoder_backend(EncoderBackend::ImageRs);
+        pipeline.set_report_dir(&report_dir);
+        pipeline.run().unwrap();
+
+        let report = fs::read_to_string(crate::report::find_latest_report(&report_dir).unwrap()).unwrap();
+        let field_of = |name: &str, index: usize| report.lines()
+            .find(|l| l.starts_with(&origin.join(name).display().to_string()))
+            .and_then(|l| l.split(',').nth(index))
+            .unwrap()
+            .to_string();
+        let status_of = |name: &str| field_of(name, 5);
+        assert_eq!(status_of("a.png"), "Compressed");
+        assert_eq!((field_of("a.png", 8), field_of("a.png", 9)), ("6".to_string(), "6".to_string()));
+        assert_eq!(status_of("b.txt"), "Failed(UnsupportedFormat)");
+        assert_eq!(status_of("c.png"), "Copied");
+        assert_eq!(status_of("d.png"), "Failed(DestinationExists)");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    /// Create a clean test folder with an image at each path relative to its `origin` folder,
+    /// and return the test folder with its `origin`.
+    fn create_test_tree(name: &str, image_list: &[&str]) -> (PathBuf, PathBuf) {
+        let test_dir = PathBuf::from(name);
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let origin = test_dir.join("origin");
+        for (i, path) in image_list.iter().enumerate() {
+            let file = origin.join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            image::RgbImage::from_fn(16, 12, |x, y| image::Rgb([x as u8 * 16, y as u8 * 20, i as u8 * 40])).save(file).unwrap();
+        }
+        (test_dir, origin)
+    }
+
+    /// Get the paths of every file in the folder relative to it, sorted.
+    fn get_relative_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = get_file_list(dir).unwrap()
+            .into_iter()
+            .map(|f| f.strip_prefix(dir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    fn is_jpeg(file: &Path) -> bool {
+        image::ImageReader::open(file).unwrap().with_guessed_format().unwrap().format() == Some(image::ImageFormat::Jpeg)
+    }
+
+    #[test]
+    fn run_default_test(){
+        let (test_dir, origin) = create_test_tree("test_run_default", &["a.png", "sub/b.png", "sub/deep/c.jpg"]);
+        let dest = test_dir.join("dest");
+        Pipeline::new(&origin, &dest).run().unwrap();
+
+        let expected = [PathBuf::from("a.jpg"), PathBuf::from("sub/b.jpg"), PathBuf::from("sub/deep/c.jpg")];
+        assert_eq!(get_relative_files(&dest), expected);
+        assert!(expected.iter().all(|f| is_jpeg(&dest.join(f))));
+        assert_eq!(get_relative_files(&origin).len(), 3);
+        // The same destination can be used again, as its lock is released.
+        assert!(DestLock::acquire(&dest).is_ok());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_thumbnail_test(){
+        let (test_dir, origin) = create_test_tree("test_run_thumbnail", &["a.png", "sub/b.png"]);
+        let dest = test_dir.join("dest");
+        fs::create_dir_all(dest.join("sub").join("thumbs")).unwrap();
+        fs::write(dest.join("sub").join("thumbs").join("b.jpg"), "existing").unwrap();
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_thumbnail_size(8);
+        pipeline.run().unwrap();
+
+        // An existing thumbnail is kept, while the image is still compressed.
+        assert_eq!(get_relative_files(&dest), [PathBuf::from("a.jpg"), PathBuf::from("sub/b.jpg"), PathBuf::from("sub/thumbs/b.jpg"), PathBuf::from("thumbs/a.jpg")]);
+        assert_eq!(image::image_dimensions(dest.join("a.jpg")).unwrap(), (13, 10));
+        assert_eq!(image::image_dimensions(dest.join("thumbs").join("a.jpg")).unwrap(), (8, 6));
+        assert_eq!(fs::read_to_string(dest.join("sub").join("thumbs").join("b.jpg")).unwrap(), "existing");
+        assert!(is_jpeg(&dest.join("sub").join("b.jpg")));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_max_dimension_test(){
+        let (test_dir, origin) = create_test_tree("test_run_max_dimension", &["a.png"]);
+        let dest = test_dir.join("dest");
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_factor(Factor::new(80., 1.));
+        pipeline.set_max_dimension(8);
+        pipeline.run().unwrap();
+        assert_eq!(image::image_dimensions(dest.join("a.jpg")).unwrap(), (8, 6));
+
+        // An image already smaller than the limit after resizing keeps its factor.
+        fs::remove_dir_all(&dest).unwrap();
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_factor(Factor::new(80., 0.25));
+        pipeline.set_max_dimension(8);
+        pipeline.run().unwrap();
+        assert_eq!(image::image_dimensions(dest.join("a.jpg")).unwrap(), (4, 3));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_hdr_test(){
+        let (test_dir, origin) = create_test_tree("test_run_hdr", &["a.png"]);
+        let light = image::Rgb32FImage::from_fn(16, 12, |x, _| image::Rgb([x as f32 / 4.; 3]));
+        image::DynamicImage::ImageRgb32F(light.clone()).save(origin.join("b.exr")).unwrap();
+        image::DynamicImage::ImageRgb32F(light).save(origin.join("c.hdr")).unwrap();
+        let dest = test_dir.join("dest");
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_factor(Factor::new(90., 1.));
+        pipeline.set_decode_options(DecodeOptions { tone_map: ToneMap::Clamp, ..DecodeOptions::default() });
+        pipeline.run().unwrap();
+
+        // The images are tone mapped and compressed instead of being copied.
+        assert_eq!(get_relative_files(&dest), ["a.jpg", "b.jpg", "c.jpg"].map(PathBuf::from));
+        for file in ["b.jpg", "c.jpg"] {
+            assert!(is_jpeg(&dest.join(file)));
+            let image = image::open(dest.join(file)).unwrap().to_luma8();
+            assert!(image.get_pixel(0, 6)[0] < 10 && image.get_pixel(15, 6)[0] > 245, "{}", file);
+        }
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_output_specs_test(){
+        let (test_dir, origin) = create_test_tree("test_run_output_specs", &["a.png", "b.png"]);
+        let dest = test_dir.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("b-8w.jpg"), "existing").unwrap();
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_factor(Factor::new(80., 1.));
+        pipeline.set_output_specs(OutputSpec::parse_list("8, 100", "-{w}w").unwrap());
+        pipeline.run().unwrap();
+
+        // Every width is saved next to the output, except the one that already exists.
+        assert_eq!(get_relative_files(&dest), ["a-100w.jpg", "a-8w.jpg", "a.jpg", "b-100w.jpg", "b-8w.jpg", "b.jpg"].map(PathBuf::from));
+        assert_eq!(image::image_dimensions(dest.join("a-8w.jpg")).unwrap(), (8, 6));
+        assert_eq!(image::image_dimensions(dest.join("a-100w.jpg")).unwrap(), (16, 12));
+        assert_eq!(image::image_dimensions(dest.join("a.jpg")).unwrap(), (16, 12));
+        assert_eq!(fs::read_to_string(dest.join("b-8w.jpg")).unwrap(), "existing");
+        assert!(is_jpeg(&dest.join("b.jpg")) && is_jpeg(&dest.join("b-100w.jpg")));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_archive_test(){
+        let (test_dir, origin) = create_test_tree("test_run_archive", &["x/a.png", "x/b.png", "y/c.png"]);
+        let dest = test_dir.join("dest");
+        let archive_dir = test_dir.join("archive");
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_archive(&archive_dir, ArchiveOutput::Archive(Format::Zip));
+        pipeline.set_remove_intermediate(true);
+        pipeline.run().unwrap();
+
+        let mut archive_files = fs::read_dir(&archive_dir).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|n| n.ends_with(".zip"))
+            .collect::<Vec<_>>();
+        archive_files.sort();
+        assert_eq!(archive_files, ["x.zip", "y.zip"]);
+        let archive = zip::ZipArchive::new(fs::File::open(archive_dir.join("x.zip")).unwrap()).unwrap();
+        let mut names = archive.file_names().map(str::to_string).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["x/a.jpg", "x/b.jpg"]);
+        // The compressed folders are removed once they are archived.
+        assert!(!dest.join("x").exists() && !dest.join("y").exists());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_duplicate_pattern_test(){
+        let (test_dir, origin) = create_test_tree("test_run_duplicate_pattern", &["x/a.png", "y/a.png"]);
+        let dest = test_dir.join("dest");
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_layout(Layout::Flat);
+        pipeline.set_duplicate_pattern("../{name}_{n}");
+        assert_eq!(pipeline.run().unwrap_err().to_string(), "The pattern of duplicate names must not contain a path separator or \"..\".");
+        assert!(!test_dir.join("a_1.jpg").exists() && !dest.join("a.jpg").exists());
+
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_layout(Layout::Flat);
+        pipeline.set_duplicate_pattern("{name}_{n}");
+        pipeline.run().unwrap();
+        assert_eq!(get_relative_files(&dest), [PathBuf::from("a.jpg"), PathBuf::from("a_1.jpg")]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_overlap_test(){
+        let (test_dir, origin) = create_test_tree("test_run_overlap", &["2021/01/a.png", "2021/01/b.png", "2021/02/c.png", "2021/cover.png", "top.png"]);
+        let dest = test_dir.join("dest");
+        let archive_dir = test_dir.join("archive");
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_archive(&archive_dir, ArchiveOutput::Archive(Format::Zip));
+        pipeline.set_group_depth(2);
+        pipeline.set_thread_count(2);
+        pipeline.run().unwrap();
+
+        // One archive for each group, with only the files of the group.
+        let archive_files = get_relative_files(&archive_dir).into_iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "zip"))
+            .collect::<Vec<_>>();
+        assert_eq!(archive_files, [PathBuf::from("2021/01.zip"), PathBuf::from("2021/02.zip")]);
+        let get_names = |archive: &str| {
+            let archive = zip::ZipArchive::new(fs::File::open(archive_dir.join(archive)).unwrap()).unwrap();
+            let mut names = archive.file_names().map(str::to_string).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(get_names("2021/01.zip"), ["01/a.jpg", "01/b.jpg"]);
+        assert_eq!(get_names("2021/02.zip"), ["02/c.jpg"]);
+        // The files outside the groups are compressed after the groups.
+        assert_eq!(get_relative_files(&dest), [
+            PathBuf::from("2021/01/a.jpg"), PathBuf::from("2021/01/b.jpg"), PathBuf::from("2021/02/c.jpg"),
+            PathBuf::from("2021/cover.jpg"), PathBuf::from("top.jpg"),
+        ]);
+        assert!(is_jpeg(&dest.join("2021").join("cover.jpg")) && is_jpeg(&dest.join("top.jpg")));
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_resume_test(){
+        let (test_dir, origin) = create_test_tree("test_run_resume", &["a.png", "b.png", "c.png", "sub/d.png"]);
+        let dest = test_dir.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        // An earlier run completed a.png, was killed while writing b.png, failed c.png and never reached d.png.
+        fs::write(dest.join("a.jpg"), "done by the earlier run").unwrap();
+        fs::write(dest.join("b.jpg"), "partial").unwrap();
+        fs::create_dir_all(dest.join(".candidates_b").join("0")).unwrap();
+        let journal_path = get_journal_path(&dest);
+        let journal = Journal::open(&journal_path, &origin).unwrap();
+        for (file, state) in [("a.png", FileState::Started), ("a.png", FileState::Done), ("b.png", FileState::Started), ("c.png", FileState::Started), ("c.png", FileState::Failed)] {
+            journal.record(origin.join(file), state).unwrap();
+        }
+        drop(journal);
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let sink = messages.clone();
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_resume(true);
+        pipeline.set_sender(move |event: Event| sink.lock().unwrap().push(event));
+        pipeline.run().unwrap();
+
+        // The completed file is skipped, and the others are compressed again.
+        assert_eq!(fs::read_to_string(dest.join("a.jpg")).unwrap(), "done by the earlier run");
+        assert!(is_jpeg(&dest.join("b.jpg")) && is_jpeg(&dest.join("c.jpg")) && is_jpeg(&dest.join("sub").join("d.jpg")));
+        assert!(!dest.join(".candidates_b").exists());
+        let events = messages.lock().unwrap();
+        assert!(events.iter().any(|e| e.message == "Resuming the interrupted job: 1 files are already done, 3 files are left."));
+        let progress_list = events.iter().filter_map(|e| e.progress.as_ref()).collect::<Vec<_>>();
+        assert_eq!(progress_list.iter().map(|p| p.done).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(progress_list.iter().all(|p| p.total == 3 && !p.elapsed.is_zero()));
+        assert!(events.windows(2).all(|w| w[0].time <= w[1].time));
+        assert!(events.iter().any(|e| e.message.starts_with("Time in stages: decode ")));
+        assert!(events.iter().any(|e| e.message.starts_with("Workers: #1 ")));
+        // The journal is removed when the run completes.
+        assert!(!journal_path.exists());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_stopped_test(){
+        let (test_dir, origin) = create_test_tree("test_run_stopped", &["a.png", "sub/b.png"]);
+        let dest = test_dir.join("dest");
+        let flag = Arc::new(AtomicBool::new(true));
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_resume(true);
+        pipeline.set_stop_flag(flag.clone());
+        let error = pipeline.run().unwrap_err();
+        assert_eq!(error.to_string(), "The job was stopped. Run it again with resuming to compress the remaining files.");
+        assert!(!dest.join("a.jpg").exists() && !dest.join("sub").join("b.jpg").exists());
+        // The journal is kept, and the next run completes the job.
+        let journal_path = get_journal_path(&dest);
+        assert!(journal_path.is_file());
+        flag.store(false, Ordering::SeqCst);
+        let mut pipeline = Pipeline::new(&origin, &dest);
+        pipeline.set_resume(true);
+        pipeline.set_stop_flag(flag.clone());
+        pipeline.run().unwrap();
+        assert_eq!(get_relative_files(&dest), [PathBuf::from("a.jpg"), PathBuf::from("sub/b.jpg")]);
+        assert!(!journal_path.exists());
+
+        // In place, the originals are untouched and the temporary folder is removed.
+        flag.store(true, Ordering::SeqCst);
+        let mut pipeline = Pipeline::new(&origin, &origin);
+        pipeline.set_stop_flag(flag);
+        assert_eq!(pipeline.run().unwrap_err().to_string(), "The job was stopped.");
+        assert_eq!(get_relative_files(&origin), [PathBuf::from("a.png"), PathBuf::from("sub/b.png")]);
+        assert!(!get_temp_dir(&origin).unwrap().exists());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn run_in_place_test(){
+        let (test_dir, origin) = create_test_tree("test_run_in_place", &["a.png", "sub/b.jpg"]);
+        fs::write(origin.join("sub").join("notes.txt"), "not an image").unwrap();
+        let mut pipeline = Pipeline::new(&origin, &origin);
+        pipeline.set_keep_backup(true);
+        pipeline.run().unwrap();
+
+        assert_eq!(get_relative_files(&origin), [
+            PathBuf::from("a.jpg"), PathBuf::from("a.png.bak"),
+            PathBuf::from("sub/b.jpg"), PathBuf::from("sub/b.jpg.bak"), PathBuf::from("sub/notes.txt"),
+        ]);
+        assert!(is_jpeg(&origin.join("a.jpg")));
+        assert_eq!(fs::read_to_string(origin.join("sub").join("notes.txt")).unwrap(), "not an image");
+        // The temporary folder is removed after the originals are replaced.
+        assert!(!get_temp_dir(&origin).unwrap().exists());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn group_list_test(){
+        let test_dir = PathBuf::from("test_group_list");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        fs::create_dir_all(test_dir.join("2021").join("01")).unwrap();
+        fs::create_dir_all(test_dir.join("2021").join("02")).unwrap();
+        fs::write(test_dir.join("2021").join("cover.jpg"), "image").unwrap();
+
+        assert_eq!(get_group_list(&test_dir, 0).unwrap(), vec![PathBuf::new()]);
+        assert_eq!(get_group_list(&test_dir, 1).unwrap(), vec![PathBuf::from("2021")]);
+        let mut group_list = get_group_list(&test_dir, 2).unwrap();
+        group_list.sort();
+        assert_eq!(group_list, vec![PathBuf::from("2021/01"), PathBuf::from("2021/02")]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// File next to the executable that turns on the portable mode.
+pub const PORTABLE_FLAG_FILE: &str = "portable.flag";
+
+/// Folder of the executable, where the data is kept in the portable mode.
+static PORTABLE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Get the folder of the executable.
+fn get_exe_dir() -> io::Result<PathBuf> {
+    env::current_exe()?
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "The executable has no folder"))
+}
+
+/// Whether [`PORTABLE_FLAG_FILE`] is next to the executable.
+pub fn has_portable_flag() -> bool {
+    get_exe_dir().map(|d| d.join(PORTABLE_FLAG_FILE).is_file()).unwrap_or(false)
+}
+
+/// Keep the settings, the history and the reports next to the executable instead of the working directory,
+/// for running the program from a USB stick on several computers. Returns the folder of the executable.
+///
+/// # Error
+/// - When the folder of the executable cannot be found.
+pub fn enable_portable_mode() -> io::Result<PathBuf> {
+    let exe_dir = get_exe_dir()?;
+    Ok(PORTABLE_DIR.get_or_init(|| exe_dir).to_path_buf())
+}
+
+pub fn is_portable() -> bool {
+    PORTABLE_DIR.get().is_some()
+}
+
+/// Get the path of the data file or folder, like [`DEFAULT_SAVE_FILE_PATH`](crate::DEFAULT_SAVE_FILE_PATH),
+/// which is next to the executable in the portable mode and in the working directory otherwise.
+pub fn get_data_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    match PORTABLE_DIR.get() {
+        Some(dir) => dir.join(path),
+        None => path.as_ref().to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portable_test(){
+        assert!(!is_portable());
+        assert_eq!(get_data_path("data/history.json"), PathBuf::from("data/history.json"));
+        assert!(!has_portable_flag());
+
+        let exe_dir = enable_portable_mode().unwrap();
+        assert!(is_portable());
+        assert_eq!(get_data_path("data/history.json"), exe_dir.join("data").join("history.json"));
+    }
+}
@@ -0,0 +1,89 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::process::Command;
+use std::time::Duration;
+
+/// Time to cancel the power action after the jobs are done.
+pub const POWER_COUNTDOWN: Duration = Duration::from_secs(60);
+
+/// Action on the computer after the jobs complete, for jobs left running overnight.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PowerAction {
+    #[default]
+    Nothing,
+    Sleep,
+    Hibernate,
+    ShutDown,
+}
+
+impl PowerAction {
+    pub const ALL: [PowerAction; 4] = [PowerAction::Nothing, PowerAction::Sleep, PowerAction::Hibernate, PowerAction::ShutDown];
+
+    /// Whether the action can be run on the platform.
+    pub fn is_supported(&self) -> bool {
+        *self == PowerAction::Nothing || get_power_command(*self, env::consts::OS).is_some()
+    }
+}
+
+impl fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerAction::Nothing => write!(f, "Do nothing"),
+            PowerAction::Sleep => write!(f, "Sleep"),
+            PowerAction::Hibernate => write!(f, "Hibernate"),
+            PowerAction::ShutDown => write!(f, "Shut down"),
+        }
+    }
+}
+
+/// Get the program and its arguments that run the action on the OS, or `None` if the OS has no such action.
+fn get_power_command(action: PowerAction, os: &str) -> Option<(&'static str, Vec<&'static str>)> {
+    match (os, action) {
+        (_, PowerAction::Nothing) => None,
+        ("windows", PowerAction::Sleep) => Some(("rundll32.exe", vec!["powrprof.dll,SetSuspendState", "0,1,0"])),
+        ("windows", PowerAction::Hibernate) => Some(("shutdown", vec!["/h"])),
+        ("windows", PowerAction::ShutDown) => Some(("shutdown", vec!["/s", "/t", "0"])),
+        ("macos", PowerAction::Sleep) => Some(("pmset", vec!["sleepnow"])),
+        ("macos", PowerAction::ShutDown) => Some(("osascript", vec!["-e", "tell application \"System Events\" to shut down"])),
+        ("linux", PowerAction::Sleep) => Some(("systemctl", vec!["suspend"])),
+        ("linux", PowerAction::Hibernate) => Some(("systemctl", vec!["hibernate"])),
+        ("linux", PowerAction::ShutDown) => Some(("systemctl", vec!["poweroff"])),
+        _ => None,
+    }
+}
+
+/// Put the computer to sleep, hibernate it or shut it down.
+///
+/// # Error
+/// - When the action is not supported on the OS.
+/// - When the command of the OS cannot be run or fails, for example without the permission.
+pub fn run_power_action(action: PowerAction) -> io::Result<()> {
+    if action == PowerAction::Nothing {
+        return Ok(());
+    }
+    let (program, args) = get_power_command(action, env::consts::OS)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, format!("{} is not supported on this OS!", action)))?;
+    let status = Command::new(program).args(args).status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{} failed with {}", program, status)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_command_test(){
+        assert_eq!(get_power_command(PowerAction::ShutDown, "linux"), Some(("systemctl", vec!["poweroff"])));
+        assert_eq!(get_power_command(PowerAction::Hibernate, "windows"), Some(("shutdown", vec!["/h"])));
+        assert_eq!(get_power_command(PowerAction::Hibernate, "macos"), None);
+        assert_eq!(get_power_command(PowerAction::Nothing, "linux"), None);
+        assert_eq!(get_power_command(PowerAction::Sleep, "freebsd"), None);
+        assert!(PowerAction::Nothing.is_supported());
+        assert!(run_power_action(PowerAction::Nothing).is_ok());
+        assert_eq!(PowerAction::ShutDown.to_string(), "Shut down");
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{absolute, Path, PathBuf};
+use fs2::available_space;
+
+/// Find source files that would be written to the same destination file.
+///
+/// `output_paths` maps every source file to its output path.
+/// The compressor names every output by replacing the extension of the stem with `jpg`,
+/// so files that share an output directory and that name collide,
+/// e.g. `photo.png` and `photo.jpg`, or `trip.2021.png` and `trip.2022.png` which both become `trip.jpg`.
+/// Each returned group contains two or more colliding source files, sorted by path.
+pub fn find_stem_collisions(output_paths: &BTreeMap<PathBuf, PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut stem_map: HashMap<(PathBuf, PathBuf), Vec<PathBuf>> = HashMap::new();
+    for (source, output) in output_paths {
+        let (parent, name) = match (output.parent(), output.file_stem()) {
+            (Some(p), Some(s)) => (p.to_path_buf(), Path::new(s).with_extension("jpg")),
+            _ => continue,
+        };
+        stem_map.entry((parent, name)).or_default().push(source.to_path_buf());
+    }
+
+    let mut collisions = stem_map.into_values()
+        .filter(|files| files.len() > 1)
+        .map(|mut files| {
+            files.sort();
+            files
+        })
+        .collect::<Vec<_>>();
+    collisions.sort();
+    collisions
+}
+
+/// Check whether `dir` is the same directory as `root` or is located somewhere below it.
+///
+/// Both paths are resolved through symbolic links as far as they exist,
+/// so a destination folder that will only be created by the job is also detected.
+pub fn is_same_or_inside<P: AsRef<Path>, R: AsRef<Path>>(dir: P, root: R) -> bool {
+    resolve(dir.as_ref()).starts_with(resolve(root.as_ref()))
+}
+
+/// Check whether both paths point to the same directory.
+pub fn is_same_dir<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, other: Q) -> bool {
+    resolve(dir.as_ref()) == resolve(other.as_ref())
+}
+
+/// Check whether the images would be compressed in place, as the original and destination folders are the same folder.
+/// A folder that is not chosen yet, i.e. an empty path, is never in place.
+pub fn is_in_place<P: AsRef<Path>, Q: AsRef<Path>>(origin: P, dest: Q) -> bool {
+    let (origin, dest) = (origin.as_ref(), dest.as_ref());
+    !origin.as_os_str().is_empty() && !dest.as_os_str().is_empty() && is_same_dir(origin, dest)
+}
+
+/// Get the total size of the files in bytes. Files that cannot be read are counted as empty.
+pub fn get_total_size<P: AsRef<Path>>(file_list: &[P]) -> u64 {
+    file_list.iter()
+        .filter_map(|f| fs::metadata(f).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Get the free space in bytes of the volume the directory is, or will be, created on.
+pub fn get_free_space<P: AsRef<Path>>(dir: P) -> io::Result<u64> {
+    let dir = absolute(dir.as_ref())?;
+    match dir.ancestors().find(|p| p.is_dir()) {
+        Some(p) => available_space(p),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "Cannot find the volume of the directory")),
+    }
+}
+
+/// Format a byte count for messages, e.g. `1.5 GB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+/// Canonicalize the longest existing ancestor of the path and append the rest of it.
+fn resolve(path: &Path) -> PathBuf {
+    let path = absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    for ancestor in path.ancestors() {
+        if let Ok(p) = fs::canonicalize(ancestor) {
+            return match path.strip_prefix(ancestor) {
+                Ok(rest) => p.join(rest),
+                Err(_) => p,
+            };
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_output_paths(file_list: &[PathBuf]) -> BTreeMap<PathBuf, PathBuf> {
+        file_list.iter().map(|f| (f.to_path_buf(), f.to_path_buf())).collect()
+    }
+
+    #[test]
+    fn stem_collision_test(){
+        let file_list = vec![
+            PathBuf::from("origin/photo.png"),
+            PathBuf::from("origin/photo.jpg"),
+            PathBuf::from("origin/other.png"),
+            PathBuf::from("origin/sub/photo.gif"),
+        ];
+        let collisions = find_stem_collisions(&to_output_paths(&file_list));
+        assert_eq!(collisions, vec![vec![PathBuf::from("origin/photo.jpg"), PathBuf::from("origin/photo.png")]]);
+
+        let output_paths = BTreeMap::from([
+            (PathBuf::from("origin/a.png"), PathBuf::from("0001/a.png")),
+            (PathBuf::from("origin/sub/a.png"), PathBuf::from("0001/a.png")),
+        ]);
+        assert_eq!(find_stem_collisions(&output_paths), vec![vec![PathBuf::from("origin/a.png"), PathBuf::from("origin/sub/a.png")]]);
+    }
+
+    #[test]
+    fn dotted_stem_collision_test(){
+        let file_list = vec![
+            PathBuf::from("origin/trip.2021.png"),
+            PathBuf::from("origin/trip.2022.png"),
+            PathBuf::from("origin/trip.jpg"),
+            PathBuf::from("origin/trip.2021.backup.png"),
+        ];
+        let collisions = find_stem_collisions(&to_output_paths(&file_list));
+        assert_eq!(collisions, vec![vec![
+            PathBuf::from("origin/trip.2021.png"),
+            PathBuf::from("origin/trip.2022.png"),
+            PathBuf::from("origin/trip.jpg"),
+        ]]);
+    }
+
+    #[test]
+    fn no_stem_collision_test(){
+        let file_list = vec![
+            PathBuf::from("origin/a.png"),
+            PathBuf::from("origin/b.png"),
+            PathBuf::from("origin/sub/a.png"),
+        ];
+        assert!(find_stem_collisions(&to_output_paths(&file_list)).is_empty());
+    }
+
+    #[test]
+    fn dest_inside_origin_test(){
+        let origin = PathBuf::from("test_preflight_origin");
+        fs::create_dir_all(&origin).unwrap();
+        assert!(is_same_or_inside(&origin, &origin));
+        assert!(is_same_or_inside(origin.join("dest"), &origin));
+        assert!(is_same_or_inside(origin.join("dest").join("sub"), &origin));
+        assert!(!is_same_or_inside("test_preflight_dest", &origin));
+        assert!(!is_same_or_inside(&origin, origin.join("dest")));
+        fs::create_dir_all(origin.join("sub")).unwrap();
+        assert!(is_same_dir(&origin, origin.join("sub").join("..")));
+        assert!(!is_same_dir(origin.join("dest"), &origin));
+        assert!(is_in_place(&origin, origin.join("sub").join("..")));
+        assert!(!is_in_place(&origin, origin.join("dest")));
+        fs::remove_dir_all(&origin).unwrap();
+    }
+
+    #[test]
+    fn unset_dir_in_place_test(){
+        assert!(!is_in_place("", ""));
+        assert!(!is_in_place("", "test_preflight_unset"));
+        assert!(!is_in_place("test_preflight_unset", ""));
+    }
+
+    #[test]
+    fn total_size_test(){
+        let test_dir = PathBuf::from("test_preflight_total_size");
+        fs::create_dir_all(&test_dir).unwrap();
+        let file_list = vec![test_dir.join("a.txt"), test_dir.join("b.txt"), test_dir.join("not_existing.txt")];
+        fs::write(&file_list[0], [0u8; 100]).unwrap();
+        fs::write(&file_list[1], [0u8; 50]).unwrap();
+        assert_eq!(get_total_size(&file_list), 150);
+        assert!(get_free_space(test_dir.join("not_existing_dir")).is_ok());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn format_size_test(){
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+}
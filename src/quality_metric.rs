@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::path::Path;
+use image::imageops::FilterType;
+use image::{GrayImage, RgbImage};
+
+/// PSNR reported for identical images, whose PSNR is infinite.
+pub const MAX_PSNR: f64 = 100.;
+
+/// Size of the square windows that SSIM is computed in, and the step between them.
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STEP: u32 = 4;
+
+/// Measure the PSNR in dB and the SSIM of the compressed image against its source.
+///
+/// The source is resized to the size of the compressed image first, the same way the compressor resizes it,
+/// so only the loss of the encoding is measured.
+/// PSNR is of the RGB channels, and SSIM is the mean of 8x8 windows of the luma.
+///
+/// # Error
+/// - When either image cannot be opened.
+pub fn measure_quality<S: AsRef<Path>, C: AsRef<Path>>(source: S, compressed: C) -> Result<(f64, f64), Box<dyn Error>> {
+    let compressed = image::open(compressed)?;
+    let source = image::open(source)?;
+    let source = match source.width() == compressed.width() && source.height() == compressed.height() {
+        true => source,
+        false => source.resize_exact(compressed.width(), compressed.height(), FilterType::Triangle),
+    };
+    Ok((get_psnr(&source.to_rgb8(), &compressed.to_rgb8()), get_ssim(&source.to_luma8(), &compressed.to_luma8())))
+}
+
+/// PSNR of two images of the same size, at most [`MAX_PSNR`].
+fn get_psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+    let squared_error = a.as_raw().iter()
+        .zip(b.as_raw())
+        .map(|(x, y)| (*x as f64 - *y as f64).powi(2))
+        .sum::<f64>();
+    let mse = squared_error / a.as_raw().len().max(1) as f64;
+    match mse {
+        0. => MAX_PSNR,
+        m => (10. * (255f64.powi(2) / m).log10()).min(MAX_PSNR),
+    }
+}
+
+/// Mean SSIM of the windows of two images of the same size. An image smaller than a window is one window.
+fn get_ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    let (width, height) = a.dimensions();
+    let window_width = SSIM_WINDOW.min(width);
+    let window_height = SSIM_WINDOW.min(height);
+    let mut total = 0.;
+    let mut count = 0;
+    for y in (0..=height.saturating_sub(window_height)).step_by(SSIM_STEP as usize) {
+        for x in (0..=width.saturating_sub(window_width)).step_by(SSIM_STEP as usize) {
+            let pixels = (y..y + window_height)
+                .flat_map(|y| (x..x + window_width).map(move |x| (x, y)))
+                .map(|(x, y)| (a.get_pixel(x, y)[0] as f64, b.get_pixel(x, y)[0] as f64))
+                .collect::<Vec<_>>();
+            total += get_window_ssim(&pixels);
+            count += 1;
+        }
+    }
+    match count {
+        0 => 1.,
+        c => total / c as f64,
+    }
+}
+
+/// SSIM of a window of pixel pairs.
+fn get_window_ssim(pixels: &[(f64, f64)]) -> f64 {
+    const C1: f64 = (0.01 * 255.) * (0.01 * 255.);
+    const C2: f64 = (0.03 * 255.) * (0.03 * 255.);
+    let n = pixels.len() as f64;
+    let mean_a = pixels.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_b = pixels.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut var_a, mut var_b, mut covariance) = (0., 0., 0.);
+    for (a, b) in pixels {
+        var_a += (a - mean_a).powi(2);
+        var_b += (b - mean_b).powi(2);
+        covariance += (a - mean_a) * (b - mean_b);
+    }
+    let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+    ((2. * mean_a * mean_b + C1) * (2. * covariance + C2))
+        / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (var_a + var_b + C2))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use image::{Luma, Rgb};
+    use super::*;
+
+    #[test]
+    fn measure_quality_test(){
+        let gradient = RgbImage::from_fn(32, 32, |x, y| Rgb([(x * 8) as u8, (y * 8) as u8, 128]));
+        assert_eq!(get_psnr(&gradient, &gradient), MAX_PSNR);
+        let noisy = RgbImage::from_fn(32, 32, |x, y| Rgb([(x * 8) as u8 ^ ((x + y) % 2 * 16) as u8, (y * 8) as u8, 128]));
+        let psnr = get_psnr(&gradient, &noisy);
+        assert!(psnr > 20. && psnr < 40.);
+
+        let luma = GrayImage::from_fn(32, 32, |x, y| Luma([((x + y) * 4) as u8]));
+        assert!((get_ssim(&luma, &luma) - 1.).abs() < 1e-9);
+        let inverted = GrayImage::from_fn(32, 32, |x, y| Luma([255 - ((x + y) * 4) as u8]));
+        assert!(get_ssim(&luma, &inverted) < 0.);
+        assert!((get_ssim(&GrayImage::new(4, 4), &GrayImage::new(4, 4)) - 1.).abs() < 1e-9);
+
+        let test_dir = PathBuf::from("test_measure_quality");
+        fs::create_dir_all(&test_dir).unwrap();
+        gradient.save(test_dir.join("source.png")).unwrap();
+        image::imageops::resize(&gradient, 16, 16, FilterType::Triangle).save(test_dir.join("output.png")).unwrap();
+        let (psnr, ssim) = measure_quality(test_dir.join("source.png"), test_dir.join("output.png")).unwrap();
+        assert_eq!(psnr, MAX_PSNR);
+        assert!((ssim - 1.).abs() < 1e-9);
+        assert!(measure_quality(test_dir.join("missing.png"), test_dir.join("output.png")).is_err());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
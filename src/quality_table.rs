@@ -0,0 +1,132 @@
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::Path;
+use image_compressor::compressor::Factor;
+use serde::{Deserialize, Serialize};
+use serde_json::from_reader;
+
+/// Factor used for images at least as large as the thresholds.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct QualityRule {
+    /// Minimum file size in bytes.
+    #[serde(default)]
+    pub min_file_size: u64,
+    /// Minimum length of the longer side in pixels.
+    #[serde(default)]
+    pub min_dimension: u32,
+    /// Quality from 1 to 100.
+    pub quality: f32,
+    /// Resize ratio from 0 to 1.
+    pub size_ratio: f32,
+}
+
+/// Rules that choose the factor of each image by its file size and dimensions.
+///
+/// The first matching rule is used, so larger thresholds should come first.
+/// Images that match no rule use the default factor of the job.
+///
+/// A table is written in JSON:
+/// ```json
+/// {
+///     "rules": [
+///         { "min_file_size": 10000000, "quality": 70, "size_ratio": 0.5 },
+///         { "min_dimension": 3000, "quality": 75, "size_ratio": 0.7 }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct QualityTable {
+    pub rules: Vec<QualityRule>,
+}
+
+impl QualityTable {
+    /// Get the built-in table that compresses larger files harder, for choosing the factor automatically by file size.
+    /// Files smaller than 1 MB match no rule.
+    pub fn by_file_size() -> Self {
+        const MB: u64 = 1024 * 1024;
+        let rule = |min_file_size, quality, size_ratio| QualityRule { min_file_size, min_dimension: 0, quality, size_ratio };
+        QualityTable {
+            rules: vec![rule(10 * MB, 70., 0.5), rule(3 * MB, 75., 0.7), rule(MB, 80., 0.9)],
+        }
+    }
+
+    /// Load the table from a JSON file.
+    ///
+    /// # Error
+    /// - When the file cannot be read or is not a table.
+    /// - When a rule has a quality or size ratio that `Factor` does not accept.
+    pub fn load<P: AsRef<Path>>(file_path: P) -> Result<Self, Box<dyn Error>> {
+        let table: QualityTable = from_reader(BufReader::new(File::open(file_path)?))?;
+        for (i, rule) in table.rules.iter().enumerate() {
+            let is_valid = rule.quality > 0. && rule.quality <= 100. && rule.size_ratio > 0. && rule.size_ratio <= 1.;
+            if !is_valid {
+                let message = format!("Rule {} needs a quality from 1 to 100 and a size ratio from 0 to 1", i + 1);
+                return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, message)));
+            }
+        }
+        Ok(table)
+    }
+
+    /// Get the factor of the first rule that the image matches.
+    /// The dimensions are read only when a rule needs them.
+    pub fn get_factor<P: AsRef<Path>>(&self, image_path: P) -> Option<Factor> {
+        let file_size = image_path.as_ref().metadata().map(|m| m.len()).unwrap_or(0);
+        let mut dimension = None;
+        self.rules.iter()
+            .find(|rule| {
+                if file_size < rule.min_file_size {
+                    return false;
+                }
+                if rule.min_dimension == 0 {
+                    return true;
+                }
+                let d = *dimension.get_or_insert_with(|| image::image_dimensions(image_path.as_ref())
+                    .map(|(w, h)| w.max(h))
+                    .unwrap_or(0));
+                d >= rule.min_dimension
+            })
+            .map(|rule| Factor::new(rule.quality, rule.size_ratio))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use image::RgbImage;
+    use super::*;
+
+    #[test]
+    fn quality_table_test(){
+        let test_dir = PathBuf::from("test_quality_table");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        fs::create_dir_all(&test_dir).unwrap();
+        let table_path = test_dir.join("table.json");
+        fs::write(&table_path, r#"{"rules": [
+            {"min_file_size": 1000000000, "quality": 50, "size_ratio": 0.5},
+            {"min_dimension": 100, "quality": 70, "size_ratio": 0.6},
+            {"quality": 90, "size_ratio": 1}
+        ]}"#).unwrap();
+        let small = test_dir.join("small.png");
+        let large = test_dir.join("large.png");
+        RgbImage::new(10, 20).save(&small).unwrap();
+        RgbImage::new(50, 120).save(&large).unwrap();
+
+        let table = QualityTable::load(&table_path).unwrap();
+        assert_eq!(table.get_factor(&large), Some(Factor::new(70., 0.6)));
+        assert_eq!(table.get_factor(&small), Some(Factor::new(90., 1.)));
+        assert_eq!(QualityTable::default().get_factor(&small), None);
+
+        assert_eq!(QualityTable::by_file_size().get_factor(&small), None);
+        fs::write(test_dir.join("large.bin"), vec![0u8; 4 * 1024 * 1024]).unwrap();
+        assert_eq!(QualityTable::by_file_size().get_factor(test_dir.join("large.bin")), Some(Factor::new(75., 0.7)));
+
+        fs::write(&table_path, r#"{"rules": [{"quality": 0, "size_ratio": 3}]}"#).unwrap();
+        assert!(QualityTable::load(&table_path).is_err());
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
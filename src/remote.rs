@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Number of times an upload is tried before giving up.
+pub const UPLOAD_ATTEMPT_COUNT: u32 = 4;
+
+/// Server that the outputs of a [`Pipeline`](crate::Pipeline) are uploaded to.
+pub trait RemoteTarget {
+    /// Upload the file to the path relative to the root of the target.
+    fn upload(&self, file: &Path, relative_path: &Path) -> Result<(), Box<dyn Error>>;
+
+    /// Get the URL of the path relative to the root of the target, for the messages.
+    fn get_url(&self, relative_path: &Path) -> String;
+}
+
+/// Error response of a server, with the message from its body.
+#[derive(Debug)]
+pub struct RemoteError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The server responded with status {}: {}", self.status, self.message)
+    }
+}
+
+impl Error for RemoteError {}
+
+/// Upload the file, trying again after a while when the connection fails or the server is busy.
+/// `on_retry` is called with the number of the failed attempt and its error before each retry.
+///
+/// # Error
+/// - When the file cannot be read.
+/// - When the server refuses the upload, or every attempt fails.
+pub fn upload_with_retries<F: Fn(u32, &dyn Error)>(target: &dyn RemoteTarget, file: &Path, relative_path: &Path, on_retry: F) -> Result<(), Box<dyn Error>> {
+    let mut attempt = 1;
+    loop {
+        match target.upload(file, relative_path) {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt < UPLOAD_ATTEMPT_COUNT && is_retryable(e.as_ref()) => {
+                on_retry(attempt, e.as_ref());
+                thread::sleep(Duration::from_secs(1 << (attempt - 1)));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Whether the upload may succeed when tried again: the connection failed, or the server is busy or broken.
+fn is_retryable(error: &(dyn Error + 'static)) -> bool {
+    if let Some(e) = error.downcast_ref::<RemoteError>() {
+        return e.status == 429 || e.status >= 500;
+    }
+    error.downcast_ref::<ureq::Error>().is_some()
+}
+
+/// Turn the error response of the request into a [`RemoteError`] with the message of the response, its short body or its status text.
+/// Other errors are kept.
+pub fn to_remote_error(error: ureq::Error) -> Box<dyn Error> {
+    match error {
+        ureq::Error::Status(status, response) => {
+            let status_text = response.status_text().to_string();
+            let body = response.into_string().unwrap_or_default();
+            // Keep a short plain text body, but not an HTML page.
+            let message = get_xml_value(&body, "Message")
+                .or_else(|| get_xml_value(&body, "Code"))
+                .or_else(|| Some(body.trim().to_string()).filter(|b| !b.is_empty() && !b.starts_with('<') && b.len() <= 200))
+                .unwrap_or(status_text);
+            Box::new(RemoteError { status, message })
+        }
+        e => Box::new(e),
+    }
+}
+
+/// Percent-encode the path for the URL, except the unreserved characters and `/`.
+pub fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Get the text of the first element of the name in the XML body.
+pub(crate) fn get_xml_value(body: &str, name: &str) -> Option<String> {
+    let start = body.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + body[start..].find(&format!("</{}>", name))?;
+    Some(body[start..end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_error_test(){
+        assert_eq!(encode_path("photos/a b+c.zip"), "photos/a%20b%2Bc.zip");
+        assert_eq!(get_xml_value("<Error><Code>AccessDenied</Code><Message>Access Denied</Message></Error>", "Message").unwrap(), "Access Denied");
+        assert!(is_retryable(&RemoteError { status: 503, message: String::new() }));
+        assert!(!is_retryable(&RemoteError { status: 403, message: String::new() }));
+        assert!(!is_retryable(&std::io::Error::new(std::io::ErrorKind::NotFound, "missing")));
+    }
+}
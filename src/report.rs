@@ -0,0 +1,601 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use image::ImageError;
+use serde::Serialize;
+use serde_json::to_writer_pretty;
+use crate::encoder::{CompressOutput, StageTimes};
+use crate::preflight::format_size;
+use crate::quality_metric::measure_quality;
+
+/// Result of a single source file.
+#[derive(Debug, PartialEq, Serialize)]
+pub enum FileStatus {
+    /// The file was compressed to a jpg image.
+    Compressed,
+
+    /// The file could not be opened as an image and was copied as it is.
+    Copied,
+
+    /// There is no output for the file.
+    Failed(FailureKind),
+}
+
+/// Category of the reason a source file has no output.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum FailureKind {
+    /// A file with the same name was already in the destination before compressing.
+    DestinationExists,
+
+    /// The source file or the output cannot be read or written, e.g. because of permissions.
+    Unreadable,
+
+    /// The file looks like an image of a known format, but it is broken and cannot be decoded.
+    DecodeError,
+
+    /// The file is not recognized as an image, or the image cannot be encoded.
+    UnsupportedFormat,
+}
+
+impl FailureKind {
+    /// Get the category of the error of a file that has no output.
+    ///
+    /// The encoders return `io::ErrorKind::InvalidInput` for the files of unknown formats and
+    /// `io::ErrorKind::InvalidData` for the images they cannot decode, and image steps return the `ImageError` as it is.
+    /// A file that ends too early is taken as a broken image.
+    pub fn from_error(error: &(dyn Error + 'static)) -> Self {
+        if let Some(e) = error.downcast_ref::<io::Error>() {
+            return FailureKind::from_io_error(e);
+        }
+        match error.downcast_ref::<ImageError>() {
+            Some(ImageError::IoError(e)) => FailureKind::from_io_error(e),
+            Some(ImageError::Decoding(_)) | Some(ImageError::Limits(_)) => FailureKind::DecodeError,
+            _ => FailureKind::UnsupportedFormat,
+        }
+    }
+
+    fn from_io_error(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::AlreadyExists => FailureKind::DestinationExists,
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => FailureKind::DecodeError,
+            io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported => FailureKind::UnsupportedFormat,
+            _ => FailureKind::Unreadable,
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::DestinationExists => write!(f, "destination exists"),
+            FailureKind::Unreadable => write!(f, "IO/permission error"),
+            FailureKind::DecodeError => write!(f, "decode error"),
+            FailureKind::UnsupportedFormat => write!(f, "unsupported format"),
+        }
+    }
+}
+
+/// A row of the report for a single source file.
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub(crate) source: PathBuf,
+    pub(crate) destination: Option<PathBuf>,
+    pub(crate) original_size: u64,
+    pub(crate) compressed_size: Option<u64>,
+    pub(crate) ratio: Option<f64>,
+    pub(crate) status: FileStatus,
+    /// PSNR in dB of the compressed image against its source, if measured.
+    pub(crate) psnr: Option<f64>,
+    /// SSIM of the compressed image against its source, if measured.
+    pub(crate) ssim: Option<f64>,
+    /// Width of the compressed image, if known from the encoder.
+    pub(crate) width: Option<u32>,
+    /// Height of the compressed image, if known from the encoder.
+    pub(crate) height: Option<u32>,
+}
+
+/// Time spent by a worker thread compressing files one by one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WorkerTime {
+    pub files: usize,
+    /// Time from the start to the end of each file, including the copies and the checks.
+    pub busy: Duration,
+    /// Time spent in the stages of the encoder, which is part of the busy time.
+    pub stages: StageTimes,
+}
+
+/// Summary of a finished compression with the distribution of the compression ratios.
+#[derive(Debug, PartialEq)]
+pub struct CompressionSummary {
+    pub compressed_count: usize,
+    pub copied_count: usize,
+    pub failed_count: usize,
+
+    /// Failed files grouped by the category of the failure, in the order of [`FailureKind`].
+    pub failures: Vec<(FailureKind, Vec<PathBuf>)>,
+
+    pub original_size: u64,
+    pub compressed_size: u64,
+
+    /// 10th, 50th and 90th percentile of the ratios of the compressed images.
+    /// `None` if no image was compressed.
+    pub ratio_percentiles: Option<[f64; 3]>,
+
+    /// Number of compressed images in each 10% ratio bucket (0-10%, ..., 90-100%).
+    /// The last bucket counts the images that became larger than the original.
+    pub ratio_histogram: [usize; 11],
+
+    /// Mean PSNR and SSIM of the measured images. `None` if no image was measured.
+    pub mean_quality: Option<[f64; 2]>,
+
+    /// The measured image of the lowest SSIM, with its SSIM.
+    pub lowest_ssim: Option<(PathBuf, f64)>,
+
+    /// Time of each worker thread. Empty if the files were not compressed one by one.
+    pub worker_times: Vec<WorkerTime>,
+}
+
+impl CompressionSummary {
+    /// Summarize the report of a finished compression.
+    pub fn from_report(report: &[FileReport]) -> Self {
+        let mut summary = CompressionSummary {
+            compressed_count: 0,
+            copied_count: 0,
+            failed_count: 0,
+            failures: Vec::new(),
+            original_size: 0,
+            compressed_size: 0,
+            ratio_percentiles: None,
+            ratio_histogram: [0; 11],
+            mean_quality: None,
+            lowest_ssim: None,
+            worker_times: Vec::new(),
+        };
+        let mut ratios = Vec::new();
+        let mut qualities = Vec::new();
+        for row in report {
+            match row.status {
+                FileStatus::Compressed => summary.compressed_count += 1,
+                FileStatus::Copied => summary.copied_count += 1,
+                FileStatus::Failed(kind) => {
+                    summary.failed_count += 1;
+                    match summary.failures.iter_mut().find(|(k, _)| *k == kind) {
+                        Some((_, files)) => files.push(row.source.to_path_buf()),
+                        None => summary.failures.push((kind, vec![row.source.to_path_buf()])),
+                    }
+                }
+            }
+            summary.original_size += row.original_size;
+            summary.compressed_size += row.compressed_size.unwrap_or_default();
+            if let (FileStatus::Compressed, Some(ratio)) = (&row.status, row.ratio) {
+                ratios.push(ratio);
+                let bucket = ((ratio * 10.) as usize).min(10);
+                summary.ratio_histogram[bucket] += 1;
+            }
+            if let (Some(psnr), Some(ssim)) = (row.psnr, row.ssim) {
+                qualities.push([psnr, ssim]);
+                if summary.lowest_ssim.as_ref().map(|(_, s)| ssim < *s).unwrap_or(true) {
+                    summary.lowest_ssim = Some((row.source.to_path_buf(), ssim));
+                }
+            }
+        }
+        summary.failures.sort_by_key(|(kind, _)| *kind as u8);
+        if !ratios.is_empty() {
+            ratios.sort_by(|a, b| a.total_cmp(b));
+            let percentile = |p: f64| ratios[((ratios.len() - 1) as f64 * p).round() as usize];
+            summary.ratio_percentiles = Some([percentile(0.1), percentile(0.5), percentile(0.9)]);
+        }
+        if !qualities.is_empty() {
+            let mean = |i: usize| qualities.iter().map(|q| q[i]).sum::<f64>() / qualities.len() as f64;
+            summary.mean_quality = Some([mean(0), mean(1)]);
+        }
+        summary
+    }
+
+    /// Lines of the summary to show in the status dialog.
+    pub fn to_messages(&self) -> Vec<String> {
+        let mut messages = vec![format!("Summary: {} compressed, {} copied, {} failed. {} -> {}",
+                                        self.compressed_count, self.copied_count, self.failed_count,
+                                        format_size(self.original_size), format_size(self.compressed_size))];
+        for (kind, files) in &self.failures {
+            let examples = files.iter()
+                .take(3)
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>();
+            messages.push(format!("Failed ({}): {} files, e.g. {}", kind, files.len(), examples.join(", ")));
+        }
+        if let Some([p10, p50, p90]) = self.ratio_percentiles {
+            messages.push(format!("Compression ratio: 10% of images below {:.1}%, median {:.1}%, 90% of images below {:.1}%",
+                                  p10 * 100., p50 * 100., p90 * 100.));
+            let buckets = self.ratio_histogram.iter()
+                .enumerate()
+                .map(|(i, count)| match i {
+                    10 => format!(">100%: {}", count),
+                    _ => format!("{}-{}%: {}", i * 10, (i + 1) * 10, count),
+                })
+                .collect::<Vec<_>>();
+            messages.push(format!("Compression ratio histogram: {}", buckets.join(", ")));
+        }
+        if let (Some([psnr, ssim]), Some((file, lowest))) = (self.mean_quality, &self.lowest_ssim) {
+            messages.push(format!("Quality: mean PSNR {:.2} dB, mean SSIM {:.4}, lowest SSIM {:.4} ({})", psnr, ssim, lowest, file.display()));
+        }
+        if !self.worker_times.is_empty() {
+            let mut stages = StageTimes::default();
+            let mut busy = Duration::ZERO;
+            for worker in &self.worker_times {
+                stages += worker.stages;
+                busy += worker.busy;
+            }
+            // The rest of the busy time goes to reading and writing files outside the encoder, like copies and checks.
+            messages.push(format!("Time in stages: decode {:.2}s, resize and steps {:.2}s, encode {:.2}s, other {:.2}s",
+                                  stages.decode.as_secs_f64(), stages.steps.as_secs_f64(), stages.encode.as_secs_f64(),
+                                  busy.saturating_sub(stages.total()).as_secs_f64()));
+            let workers = self.worker_times.iter()
+                .enumerate()
+                .map(|(i, w)| format!("#{} {} files in {:.2}s", i + 1, w.files, w.busy.as_secs_f64()))
+                .collect::<Vec<_>>();
+            messages.push(format!("Workers: {}", workers.join(", ")));
+        }
+        messages
+    }
+}
+
+/// Get the size of each file before it is compressed, since the source may be deleted or replaced.
+/// Files that cannot be read are left out.
+pub fn get_file_sizes<P: AsRef<Path>>(file_list: &[P]) -> Vec<(PathBuf, u64)> {
+    file_list.iter()
+        .filter_map(|f| fs::metadata(f).ok().map(|m| (f.as_ref().to_path_buf(), m.len())))
+        .collect()
+}
+
+/// Find the source files whose output is already in `output_dir` before compressing.
+/// `output_paths` maps every source file to its output path relative to `output_dir`.
+/// The compressor refuses to overwrite them, so they will fail.
+pub fn find_existing_outputs<T: AsRef<Path>>(output_paths: &BTreeMap<PathBuf, PathBuf>, output_dir: T) -> Vec<PathBuf> {
+    output_paths.iter()
+        .filter(|(_, p)| output_dir.as_ref().join(p).with_extension("jpg").is_file())
+        .map(|(f, _)| f.to_path_buf())
+        .collect()
+}
+
+/// Build the report of a finished compression.
+///
+/// The output of every source file is looked up at its path in `output_paths`, relative to `output_dir`,
+/// the same way the compressor names it: `{stem}.jpg` when compressed, or the original file name when copied.
+/// The destination is reported relative to `dest`, which differs from `output_dir` when compressing in place.
+/// `existing_outputs` are the source files found by [`find_existing_outputs`] before compressing.
+///
+/// A file with an image in `outputs`, returned by the encoder while compressing, is reported from it.
+/// A file without output is reported with the category of its error in `failures`, recorded while compressing.
+/// Files compressed by `FolderCompressor` have nothing recorded, so they are looked up in `output_dir`
+/// and the category of their errors is guessed from the source file.
+pub fn build_report<T: AsRef<Path>, D: AsRef<Path>>(output_paths: &BTreeMap<PathBuf, PathBuf>, output_dir: T, dest: D, file_sizes: &[(PathBuf, u64)], existing_outputs: &[PathBuf], failures: &HashMap<PathBuf, FailureKind>, outputs: &HashMap<PathBuf, CompressOutput>) -> Vec<FileReport> {
+    let mut report = Vec::new();
+    for (source, original_size) in file_sizes {
+        let relative_path = match output_paths.get(source) {
+            Some(p) => p,
+            None => continue,
+        };
+        let compressed = relative_path.with_extension("jpg");
+        let output = outputs.get(source);
+        let (status, relative_output) = if existing_outputs.contains(source) {
+            (FileStatus::Failed(FailureKind::DestinationExists), None)
+        } else if let Some(o) = output {
+            let relative_output = o.path.strip_prefix(output_dir.as_ref()).map(|p| p.to_path_buf()).unwrap_or(compressed);
+            (FileStatus::Compressed, Some(relative_output))
+        } else if output_dir.as_ref().join(&compressed).is_file() {
+            (FileStatus::Compressed, Some(compressed))
+        } else if output_dir.as_ref().join(relative_path).is_file() {
+            (FileStatus::Copied, Some(relative_path.to_path_buf()))
+        } else if let Some(kind) = failures.get(source) {
+            (FileStatus::Failed(*kind), None)
+        } else if File::open(source).is_err() {
+            (FileStatus::Failed(FailureKind::Unreadable), None)
+        } else {
+            (FileStatus::Failed(FailureKind::UnsupportedFormat), None)
+        };
+        let output = output.filter(|_| status == FileStatus::Compressed);
+        let compressed_size = match output {
+            Some(o) => Some(o.compressed_size),
+            None => relative_output.as_ref()
+                .and_then(|p| fs::metadata(output_dir.as_ref().join(p)).ok())
+                .map(|m| m.len()),
+        };
+        report.push(FileReport {
+            source: source.to_path_buf(),
+            destination: relative_output.map(|p| dest.as_ref().join(p)),
+            original_size: *original_size,
+            compressed_size,
+            ratio: match compressed_size {
+                Some(s) if *original_size > 0 => Some(s as f64 / *original_size as f64),
+                _ => None,
+            },
+            status,
+            psnr: None,
+            ssim: None,
+            width: output.map(|o| o.width),
+            height: output.map(|o| o.height),
+        });
+    }
+    report
+}
+
+/// Measure the PSNR and SSIM of every compressed image of the report against its source with the thread count.
+///
+/// The compressed images are looked up in `output_dir` by their destinations relative to `dest`, as in [`build_report`].
+/// Images whose source is already deleted or replaced cannot be measured.
+pub fn add_quality_metrics<T: AsRef<Path>, D: AsRef<Path>>(report: &mut [FileReport], output_dir: T, dest: D, thread_count: u32) {
+    let rows = report.iter_mut()
+        .filter(|r| r.status == FileStatus::Compressed)
+        .map(Mutex::new)
+        .collect::<Vec<_>>();
+    let (output_dir, dest) = (output_dir.as_ref(), dest.as_ref());
+    let next_index = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..thread_count.max(1) {
+            scope.spawn(|| {
+                while let Some(row) = rows.get(next_index.fetch_add(1, Ordering::Relaxed)) {
+                    let mut row = row.lock().unwrap_or_else(|e| e.into_inner());
+                    let compressed = match row.destination.as_ref().and_then(|d| d.strip_prefix(dest).ok()) {
+                        Some(p) => output_dir.join(p),
+                        None => continue,
+                    };
+                    if let Ok((psnr, ssim)) = measure_quality(&row.source, compressed) {
+                        row.psnr = Some(psnr);
+                        row.ssim = Some(ssim);
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Save the report as a JSON file and a CSV file with the same name next to it.
+/// Returns the paths of the JSON file and the CSV file.
+pub fn save_report<P: AsRef<Path>>(report: &[FileReport], json_path: P) -> Result<(PathBuf, PathBuf), Box<dyn Error>> {
+    let json_path = json_path.as_ref();
+    if let Some(p) = json_path.parent() {
+        fs::create_dir_all(p)?;
+    }
+    to_writer_pretty(BufWriter::new(File::create(json_path)?), report)?;
+
+    let csv_path = json_path.with_extension("csv");
+    let mut csv_file = BufWriter::new(File::create(&csv_path)?);
+    writeln!(csv_file, "source,destination,original_size,compressed_size,ratio,status,psnr,ssim,width,height")?;
+    for row in report {
+        writeln!(csv_file, "{},{},{},{},{},{:?},{},{},{},{}",
+                 csv_field(&row.source.display().to_string()),
+                 csv_field(&row.destination.as_ref().map(|p| p.display().to_string()).unwrap_or_default()),
+                 row.original_size,
+                 row.compressed_size.map(|s| s.to_string()).unwrap_or_default(),
+                 row.ratio.map(|r| format!("{:.4}", r)).unwrap_or_default(),
+                 row.status,
+                 row.psnr.map(|p| format!("{:.2}", p)).unwrap_or_default(),
+                 row.ssim.map(|s| format!("{:.4}", s)).unwrap_or_default(),
+                 row.width.map(|w| w.to_string()).unwrap_or_default(),
+                 row.height.map(|h| h.to_string()).unwrap_or_default())?;
+    }
+    csv_file.flush()?;
+    Ok((json_path.to_path_buf(), csv_path))
+}
+
+/// Find the CSV file of the latest report saved in the directory by [`save_report`] with a timestamped name.
+pub fn find_latest_report<P: AsRef<Path>>(report_dir: P) -> Option<PathBuf> {
+    fs::read_dir(report_dir).ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "csv"))
+        .max_by(|a, b| a.file_name().cmp(&b.file_name()))
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image_compressor::compressor::Factor;
+    use crate::encoder::{compress_to_jpg, EncoderBackend};
+    use crate::layout::get_mirrored_paths;
+    use super::*;
+
+    #[test]
+    fn build_report_test(){
+        let test_dir = PathBuf::from("test_build_report");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let origin = test_dir.join("origin");
+        let dest = test_dir.join("dest");
+        fs::create_dir_all(origin.join("sub")).unwrap();
+        fs::create_dir_all(dest.join("sub")).unwrap();
+        let file_list = vec![origin.join("a.png"), origin.join("sub").join("b.txt"), origin.join("c.gif"), origin.join("d.png")];
+        for file in &file_list {
+            fs::write(file, [0u8; 100]).unwrap();
+        }
+        fs::write(dest.join("d.jpg"), [0u8; 10]).unwrap();
+        let output_paths = get_mirrored_paths(&origin, &file_list);
+        let existing_outputs = find_existing_outputs(&output_paths, &dest);
+        assert_eq!(existing_outputs, vec![origin.join("d.png")]);
+        fs::write(dest.join("a.jpg"), [0u8; 25]).unwrap();
+        fs::write(dest.join("sub").join("b.txt"), [0u8; 100]).unwrap();
+
+        let failures = HashMap::from([(origin.join("c.gif"), FailureKind::DecodeError)]);
+        let report = build_report(&output_paths, &dest, &dest, &get_file_sizes(&file_list), &existing_outputs, &HashMap::new(), &HashMap::new());
+        assert_eq!(report.len(), 4);
+        assert_eq!(report[0].status, FileStatus::Compressed);
+        assert_eq!(report[0].destination, Some(dest.join("a.jpg")));
+        assert_eq!(report[0].ratio, Some(0.25));
+        assert_eq!(report[1].status, FileStatus::Copied);
+        assert_eq!(report[2].status, FileStatus::Failed(FailureKind::UnsupportedFormat));
+        assert_eq!(report[2].compressed_size, None);
+        assert_eq!(report[3].status, FileStatus::Failed(FailureKind::DestinationExists));
+
+        assert_eq!(report[0].width, None);
+
+        let output = CompressOutput {
+            path: dest.join("a.jpg"),
+            original_size: 100,
+            compressed_size: 20,
+            width: 16,
+            height: 12,
+            times: StageTimes::default(),
+        };
+        let outputs = HashMap::from([(origin.join("a.png"), output)]);
+        let report = build_report(&output_paths, &dest, &dest, &get_file_sizes(&file_list), &existing_outputs, &failures, &outputs);
+        assert_eq!(report[0].status, FileStatus::Compressed);
+        assert_eq!(report[0].destination, Some(dest.join("a.jpg")));
+        assert_eq!(report[0].compressed_size, Some(20));
+        assert_eq!((report[0].width, report[0].height), (Some(16), Some(12)));
+        assert_eq!(report[1].width, None);
+        assert_eq!(report[2].status, FileStatus::Failed(FailureKind::DecodeError));
+
+        let (json_path, csv_path) = save_report(&report, test_dir.join("report.json")).unwrap();
+        assert!(json_path.is_file());
+        assert_eq!(fs::read_to_string(csv_path).unwrap().lines().count(), 5);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn failure_kind_test(){
+        let test_dir = PathBuf::from("test_failure_kind");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let dest = test_dir.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        image::RgbImage::from_pixel(8, 8, image::Rgb([10, 20, 30])).save(test_dir.join("a.png")).unwrap();
+        fs::write(dest.join("a.jpg"), "existing").unwrap();
+        fs::write(test_dir.join("b.txt"), "not an image").unwrap();
+        let mut broken = fs::read(test_dir.join("a.png")).unwrap();
+        broken.truncate(20);
+        fs::write(test_dir.join("c.png"), broken).unwrap();
+
+        for backend in [EncoderBackend::MozJpeg, EncoderBackend::ImageRs] {
+            let get_kind = |name: &str| FailureKind::from_error(compress_to_jpg(test_dir.join(name), &dest, Factor::default(), false, backend).unwrap_err().as_ref());
+            assert_eq!(get_kind("a.png"), FailureKind::DestinationExists, "{}", backend);
+            assert_eq!(get_kind("b.txt"), FailureKind::UnsupportedFormat, "{}", backend);
+            fs::remove_file(dest.join("c.png")).ok();
+            assert_eq!(get_kind("c.png"), FailureKind::DecodeError, "{}", backend);
+            assert_eq!(get_kind("missing.png"), FailureKind::Unreadable, "{}", backend);
+        }
+        assert_eq!(FailureKind::from_error(&image::open(test_dir.join("c.png")).unwrap_err()), FailureKind::DecodeError);
+        assert_eq!(FailureKind::from_error(&image::open(test_dir.join("b.txt")).unwrap_err()), FailureKind::UnsupportedFormat);
+        assert_eq!(FailureKind::from_error(&io::Error::from(io::ErrorKind::PermissionDenied)), FailureKind::Unreadable);
+        assert_eq!(FailureKind::DecodeError.to_string(), "decode error");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn find_latest_report_test(){
+        let test_dir = PathBuf::from("test_find_latest_report");
+        fs::create_dir_all(&test_dir).unwrap();
+        assert_eq!(find_latest_report(&test_dir), None);
+        for name in ["report_20220101_120000.csv", "report_20220301_080000.csv", "report_20220301_080000.json", "report_20220201_000000.csv"] {
+            File::create(test_dir.join(name)).unwrap();
+        }
+        assert_eq!(find_latest_report(&test_dir), Some(test_dir.join("report_20220301_080000.csv")));
+        assert_eq!(find_latest_report(test_dir.join("missing")), None);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn csv_field_test(){
+        assert_eq!(csv_field("a/b.jpg"), "a/b.jpg");
+        assert_eq!(csv_field("a,b.jpg"), "\"a,b.jpg\"");
+        assert_eq!(csv_field("a\"b\".jpg"), "\"a\"\"b\"\".jpg\"");
+    }
+
+    fn make_row(status: FileStatus, original_size: u64, compressed_size: Option<u64>) -> FileReport {
+        FileReport {
+            source: PathBuf::from("origin/file"),
+            destination: None,
+            original_size,
+            compressed_size,
+            ratio: compressed_size.map(|s| s as f64 / original_size as f64),
+            status,
+            psnr: None,
+            ssim: None,
+            width: None,
+            height: None,
+        }
+    }
+
+    #[test]
+    fn summary_test(){
+        let mut report = (1..=10)
+            .map(|i| make_row(FileStatus::Compressed, 100, Some(i * 10 - 5)))
+            .collect::<Vec<_>>();
+        report.push(make_row(FileStatus::Compressed, 100, Some(150)));
+        report.push(make_row(FileStatus::Copied, 100, Some(100)));
+        report.push(make_row(FileStatus::Failed(FailureKind::UnsupportedFormat), 100, None));
+        report.push(make_row(FileStatus::Failed(FailureKind::DestinationExists), 100, None));
+        report.push(make_row(FileStatus::Failed(FailureKind::UnsupportedFormat), 100, None));
+
+        let summary = CompressionSummary::from_report(&report);
+        assert_eq!(summary.compressed_count, 11);
+        assert_eq!(summary.copied_count, 1);
+        assert_eq!(summary.failed_count, 3);
+        assert_eq!(summary.failures, vec![
+            (FailureKind::DestinationExists, vec![PathBuf::from("origin/file")]),
+            (FailureKind::UnsupportedFormat, vec![PathBuf::from("origin/file"), PathBuf::from("origin/file")]),
+        ]);
+        assert_eq!(summary.original_size, 1500);
+        assert_eq!(summary.compressed_size, 750);
+        assert_eq!(summary.ratio_histogram, [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(summary.ratio_percentiles, Some([0.15, 0.55, 0.95]));
+        assert_eq!(summary.to_messages().len(), 5);
+    }
+
+    #[test]
+    fn quality_summary_test(){
+        let mut report = Vec::new();
+        for (psnr, ssim) in [(40., 0.98), (30., 0.9)] {
+            let mut row = make_row(FileStatus::Compressed, 100, Some(50));
+            row.psnr = Some(psnr);
+            row.ssim = Some(ssim);
+            report.push(row);
+        }
+        report.push(make_row(FileStatus::Compressed, 100, Some(50)));
+
+        let summary = CompressionSummary::from_report(&report);
+        assert_eq!(summary.mean_quality, Some([35., 0.94]));
+        assert_eq!(summary.lowest_ssim, Some((PathBuf::from("origin/file"), 0.9)));
+        assert_eq!(summary.to_messages().len(), 4);
+    }
+
+    #[test]
+    fn empty_summary_test(){
+        let summary = CompressionSummary::from_report(&[make_row(FileStatus::Failed(FailureKind::Unreadable), 100, None)]);
+        assert_eq!(summary.ratio_percentiles, None);
+        assert_eq!(summary.to_messages().len(), 2);
+    }
+
+    #[test]
+    fn worker_summary_test(){
+        let mut summary = CompressionSummary::from_report(&[make_row(FileStatus::Compressed, 100, Some(50))]);
+        assert!(summary.worker_times.is_empty());
+        let stages = StageTimes { decode: Duration::from_millis(500), steps: Duration::from_millis(250), encode: Duration::from_secs(1) };
+        summary.worker_times = vec![
+            WorkerTime { files: 3, busy: Duration::from_secs(2), stages },
+            WorkerTime { files: 2, busy: Duration::from_millis(1500), stages },
+        ];
+        let messages = summary.to_messages();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[3], "Time in stages: decode 1.00s, resize and steps 0.50s, encode 2.00s, other 0.00s");
+        assert_eq!(messages[4], "Workers: #1 3 files in 2.00s, #2 2 files in 1.50s");
+    }
+}
@@ -0,0 +1,92 @@
+use image::imageops::FilterType;
+use image::{imageops, GrayImage, ImageBuffer, Luma, RgbImage};
+
+/// Longest side of the image that the saliency is computed on, since it changes slowly across the image.
+const SALIENCY_SIZE: u32 = 128;
+
+type FloatImage = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// Get the saliency map of the image, the same size as the image, from 0 for the background to 255 for the subjects.
+///
+/// Salient areas stand out from their surroundings in brightness, or have the tones of skin, for the faces.
+/// Areas near the center are weighted up, as subjects are usually there.
+pub fn get_saliency_map(image: &RgbImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let scale = (SALIENCY_SIZE as f32 / width.max(height) as f32).min(1.);
+    let small_width = ((width as f32 * scale).round() as u32).max(1);
+    let small_height = ((height as f32 * scale).round() as u32).max(1);
+    let small = imageops::resize(image, small_width, small_height, FilterType::Triangle);
+
+    // Contrast between each pixel and its surroundings.
+    let luma = FloatImage::from_fn(small_width, small_height, |x, y| {
+        let [r, g, b] = small.get_pixel(x, y).0;
+        Luma([0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32])
+    });
+    let fine = imageops::blur(&luma, 1.);
+    let coarse = imageops::blur(&luma, small_width.max(small_height) as f32 / 16.);
+    let contrast = FloatImage::from_fn(small_width, small_height, |x, y| {
+        Luma([(fine.get_pixel(x, y)[0] - coarse.get_pixel(x, y)[0]).abs()])
+    });
+    let contrast = imageops::blur(&contrast, small_width.max(small_height) as f32 / 32.);
+    let max_contrast = contrast.pixels().map(|p| p[0]).fold(0., f32::max).max(1.);
+
+    let saliency = GrayImage::from_fn(small_width, small_height, |x, y| {
+        let skin = if is_skin(small.get_pixel(x, y).0) { 0.5 } else { 0. };
+        let dx = (x as f32 + 0.5) / small_width as f32 - 0.5;
+        let dy = (y as f32 + 0.5) / small_height as f32 - 0.5;
+        let center = 1. - (dx * dx + dy * dy) * 2.;
+        let value = (contrast.get_pixel(x, y)[0] / max_contrast + skin).min(1.) * (0.5 + 0.5 * center);
+        Luma([(value * 255.).round() as u8])
+    });
+    imageops::resize(&saliency, width, height, FilterType::Triangle)
+}
+
+/// Whether the color is in the usual range of skin tones in YCbCr.
+fn is_skin([r, g, b]: [u8; 3]) -> bool {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let cb = 128. - 0.168736 * r - 0.331264 * g + 0.5 * b;
+    let cr = 128. + 0.5 * r - 0.418688 * g - 0.081312 * b;
+    (77. ..=127.).contains(&cb) && (133. ..=173.).contains(&cr)
+}
+
+/// Blur the areas of low saliency, so that the encoder spends the bits on the subjects.
+/// `strength` from 0 to 1 is how much the background is blurred.
+pub fn blur_background(image: &RgbImage, strength: f32) -> RgbImage {
+    let strength = strength.clamp(0., 1.);
+    if strength == 0. {
+        return image.clone();
+    }
+    let saliency = get_saliency_map(image);
+    let blurred = imageops::blur(image, 0.5 + 2.5 * strength);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        // The subjects are kept sharp, and the background fades into the blurred image.
+        let weight = (saliency.get_pixel(x, y)[0] as f32 / 255. * 2.).min(1.);
+        let original = image.get_pixel(x, y).0;
+        let blurred = blurred.get_pixel(x, y).0;
+        image::Rgb([0, 1, 2].map(|i| (original[i] as f32 * weight + blurred[i] as f32 * (1. - weight)).round() as u8))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+    use super::*;
+
+    #[test]
+    fn saliency_test(){
+        // A detailed subject in the middle of a smooth background.
+        let image = RgbImage::from_fn(200, 200, |x, y| match (70..130).contains(&x) && (70..130).contains(&y) {
+            true => if (x / 4 + y / 4) % 2 == 0 { Rgb([250, 250, 250]) } else { Rgb([10, 10, 10]) },
+            false => Rgb([100, (x / 4) as u8 + 100, 120]),
+        });
+        let saliency = get_saliency_map(&image);
+        assert_eq!(saliency.dimensions(), (200, 200));
+        assert!(saliency.get_pixel(100, 100)[0] > saliency.get_pixel(10, 10)[0] * 2);
+
+        let blurred = blur_background(&image, 1.);
+        assert_eq!(blurred.get_pixel(100, 100), image.get_pixel(100, 100));
+        assert_eq!(blur_background(&image, 0.), image);
+        assert!(is_skin([224, 172, 140]));
+        assert!(!is_skin([40, 90, 200]));
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+
+/// When a scheduled job starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    /// At the next time of day.
+    At(NaiveTime),
+    /// After the number of minutes.
+    After(u32),
+}
+
+impl Schedule {
+    /// Get the time when the job starts if it is scheduled at `now`.
+    ///
+    /// A time of day that has already passed today is scheduled for tomorrow.
+    /// A time of day skipped by a daylight saving change starts an hour later.
+    pub fn start_time(&self, now: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            Schedule::At(time) => {
+                let mut date = now.naive_local().date();
+                if *time <= now.naive_local().time() {
+                    date = date.succ_opt().unwrap_or(date);
+                }
+                let start = date.and_time(*time);
+                Local.from_local_datetime(&start).earliest()
+                    .or_else(|| Local.from_local_datetime(&(start + Duration::hours(1))).earliest())
+                    .unwrap_or(now)
+            }
+            Schedule::After(minutes) => now + Duration::minutes(*minutes as i64),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::At(time) => write!(f, "at {}", time.format("%H:%M")),
+            Schedule::After(minutes) => write!(f, "after {} minutes", minutes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_time_test(){
+        let noon = Local::now().naive_local().date().and_hms_opt(12, 0, 0).unwrap();
+        let now = Local.from_local_datetime(&noon).earliest().unwrap();
+        assert_eq!(Schedule::After(90).start_time(now), now + Duration::minutes(90));
+
+        let later = Schedule::At(NaiveTime::from_hms_opt(14, 30, 0).unwrap()).start_time(now);
+        assert_eq!(later.naive_local(), now.naive_local() + Duration::minutes(150));
+        let tomorrow = Schedule::At(NaiveTime::from_hms_opt(2, 0, 0).unwrap()).start_time(now);
+        assert_eq!(tomorrow.naive_local(), now.naive_local() + Duration::hours(14));
+    }
+}
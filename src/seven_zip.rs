@@ -0,0 +1,140 @@
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::archive_filter::ArchiveFilter;
+use crate::seven_zip_download::get_managed_7z_path;
+
+/// Compression method of 7z archives.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SevenZipMethod {
+    /// The default method of 7z.
+    #[default]
+    Lzma2,
+    /// Better for text and documents.
+    Ppmd,
+    /// Store the files as they are, which is as small and much faster for compressed images.
+    Copy,
+}
+
+impl SevenZipMethod {
+    /// Create a [`SevenZipMethod`] from the str. Unknown strings fall back to the default.
+    pub fn from(method_str: &str) -> Self {
+        match method_str {
+            "PPMd" => SevenZipMethod::Ppmd,
+            "Copy" => SevenZipMethod::Copy,
+            _ => SevenZipMethod::default(),
+        }
+    }
+}
+
+impl fmt::Display for SevenZipMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SevenZipMethod::Lzma2 => write!(f, "LZMA2"),
+            SevenZipMethod::Ppmd => write!(f, "PPMd"),
+            SevenZipMethod::Copy => write!(f, "Copy"),
+        }
+    }
+}
+
+/// Options of 7z archives. The default is the same as `zip_archive`, which runs 7z with `-mx=9 -t7z`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SevenZipOptions {
+    pub method: SevenZipMethod,
+    /// Dictionary size of LZMA2, or memory size of PPMd, in MB. 0 uses the default of 7z.
+    pub dictionary_mb: u32,
+    /// Compress the files together as one solid block.
+    pub solid: bool,
+}
+
+impl Default for SevenZipOptions {
+    fn default() -> Self {
+        SevenZipOptions {
+            method: SevenZipMethod::default(),
+            dictionary_mb: 0,
+            solid: true,
+        }
+    }
+}
+
+impl SevenZipOptions {
+    /// Get the arguments of 7z that add the directory to the archive with the options.
+    pub fn get_args<D: AsRef<Path>, A: AsRef<Path>>(&self, dir: D, archive_path: A) -> Vec<OsString> {
+        let mut method = format!("-m0={}", self.method);
+        match (self.method, self.dictionary_mb) {
+            (_, 0) | (SevenZipMethod::Copy, _) => {}
+            (SevenZipMethod::Lzma2, d) => method.push_str(&format!(":d={}m", d)),
+            (SevenZipMethod::Ppmd, d) => method.push_str(&format!(":mem={}m", d)),
+        }
+        let solid = if self.solid { "-ms=on" } else { "-ms=off" };
+        vec![
+            OsString::from("a"),
+            OsString::from("-mx=9"),
+            OsString::from("-t7z"),
+            OsString::from(method),
+            OsString::from(solid),
+            archive_path.as_ref().as_os_str().to_os_string(),
+            Path::new("./").join(dir).into_os_string(),
+        ]
+    }
+}
+
+/// Archive the files of the directory that the filter does not exclude into the 7z archive with the options.
+///
+/// 7z compares the excluded extensions with case only on case-sensitive file systems.
+///
+/// # Error
+/// - When the archive already exists, because 7z would add the files to it.
+/// - When 7z cannot be run or fails.
+pub fn archive_7z<D: AsRef<Path>, A: AsRef<Path>>(dir: D, archive_path: A, options: &SevenZipOptions, filter: &ArchiveFilter) -> Result<(), Box<dyn Error>> {
+    if archive_path.as_ref().exists() {
+        return Err(Box::new(io::Error::new(io::ErrorKind::AlreadyExists, "The 7z archive file already exists!")));
+    }
+    let status = Command::new(get_7z_executable_path()?)
+        .args(options.get_args(dir, archive_path))
+        .args(filter.get_7z_args())
+        .status()?;
+    if !status.success() {
+        return Err(Box::new(io::Error::other(format!("7z cannot make the archive: {}", status))));
+    }
+    Ok(())
+}
+
+/// Get the 7z executable downloaded into the data folder, or the same executable that `zip_archive` runs
+/// to make 7z archives if none was downloaded.
+pub fn get_7z_executable_path() -> io::Result<PathBuf> {
+    if let Some(p) = get_managed_7z_path().filter(|p| p.is_file()) {
+        return Ok(p);
+    }
+    match env::consts::OS {
+        "macos" => Ok(PathBuf::from("./7zz")),
+        "windows" => Ok(PathBuf::from("7z.exe")),
+        "linux" => Ok(PathBuf::from("./7zzs")),
+        _ => Err(io::Error::new(io::ErrorKind::NotFound, "Cannot find the 7z executable!")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seven_zip_args_test(){
+        let options = SevenZipOptions { method: SevenZipMethod::Lzma2, dictionary_mb: 16, solid: false };
+        let args = options.get_args("dest/album", "archive/album.7z");
+        assert_eq!(args, ["a", "-mx=9", "-t7z", "-m0=LZMA2:d=16m", "-ms=off", "archive/album.7z", "./dest/album"]);
+
+        let options = SevenZipOptions { method: SevenZipMethod::Copy, dictionary_mb: 16, ..SevenZipOptions::default() };
+        assert_eq!(options.get_args("a", "a.7z")[3], "-m0=Copy");
+        let options = SevenZipOptions { method: SevenZipMethod::Ppmd, dictionary_mb: 64, ..SevenZipOptions::default() };
+        assert_eq!(options.get_args("a", "a.7z")[3], "-m0=PPMd:mem=64m");
+
+        for method in [SevenZipMethod::Lzma2, SevenZipMethod::Ppmd, SevenZipMethod::Copy] {
+            assert_eq!(SevenZipMethod::from(&method.to_string()), method);
+        }
+    }
+}
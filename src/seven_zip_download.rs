@@ -0,0 +1,202 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tar::Archive;
+use xz2::read::XzDecoder;
+use crate::portable::get_data_path;
+
+/// GitHub API of the latest release of the official 7-Zip.
+pub const LATEST_7Z_RELEASE_URL: &str = "https://api.github.com/repos/ip7z/7zip/releases/latest";
+
+/// Folder in the data folder where the downloaded 7z executable is kept.
+pub const MANAGED_7Z_DIR: &str = "data/7z";
+
+#[derive(Debug, Deserialize)]
+struct SevenZipRelease {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+/// File of a release, with its SHA-256 hash like `sha256:<hex>` computed by GitHub.
+#[derive(Debug, Clone, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    digest: Option<String>,
+}
+
+/// Get the end of the name of the release file with the console 7-Zip for the platform.
+/// The file is a `tar.xz` archive, except the standalone `7zr.exe` on Windows.
+fn get_asset_suffix(os: &str, arch: &str) -> Option<&'static str> {
+    match (os, arch) {
+        ("windows", _) => Some("7zr.exe"),
+        ("macos", _) => Some("-mac.tar.xz"),
+        ("linux", "x86_64") => Some("-linux-x64.tar.xz"),
+        ("linux", "x86") => Some("-linux-x86.tar.xz"),
+        ("linux", "aarch64") => Some("-linux-arm64.tar.xz"),
+        ("linux", "arm") => Some("-linux-arm.tar.xz"),
+        _ => None,
+    }
+}
+
+/// Get the name of the 7z executable for the platform, the same as the one that `zip_archive` runs.
+fn get_executable_name(os: &str) -> Option<&'static str> {
+    match os {
+        "windows" => Some("7z.exe"),
+        "macos" => Some("7zz"),
+        "linux" => Some("7zzs"),
+        _ => None,
+    }
+}
+
+/// Get the path where the downloaded 7z executable is kept, or `None` on platforms without 7-Zip.
+pub fn get_managed_7z_path() -> Option<PathBuf> {
+    get_executable_name(env::consts::OS).map(|n| get_data_path(MANAGED_7Z_DIR).join(n))
+}
+
+/// Whether the 7z executable can be run. 7z without arguments only prints its usage.
+pub fn is_7z_available<P: AsRef<Path>>(executable: P) -> bool {
+    Command::new(executable.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// Check the SHA-256 hash of the data against the digest of the release file, like `sha256:<hex>`.
+///
+/// # Error
+/// - When the digest is not a SHA-256 hash.
+/// - When the hash of the data is different.
+fn verify_digest(data: &[u8], digest: &str) -> Result<(), String> {
+    let expected = digest.strip_prefix("sha256:")
+        .filter(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("\"{}\" is not a SHA-256 checksum", digest))?;
+    let actual = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(format!("The checksum of the download is {}, but {} was expected", actual, expected.to_lowercase()));
+    }
+    Ok(())
+}
+
+/// Get the executable named like the name out of the `tar.xz` archive.
+///
+/// # Error
+/// - When the archive cannot be read.
+/// - When the archive has no such file.
+fn extract_executable<R: Read>(archive: R, name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut archive = Archive::new(XzDecoder::new(archive));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name().is_some_and(|n| n == name) {
+            let mut executable = Vec::new();
+            entry.read_to_end(&mut executable)?;
+            return Ok(executable);
+        }
+    }
+    Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("The 7-Zip archive has no {}", name))))
+}
+
+/// Download the official console 7-Zip for the platform from its latest release on GitHub,
+/// check it against the checksum of the release, and keep it at [`get_managed_7z_path`].
+/// Returns the path of the executable and the version of the release.
+///
+/// # Error
+/// - When the platform has no 7-Zip, or the release has no file for it.
+/// - When the download fails or the release file has no checksum.
+/// - When the checksum of the download is different.
+/// - When the executable cannot be written.
+pub fn download_7z() -> Result<(PathBuf, String), Box<dyn Error>> {
+    let not_supported = || io::Error::new(io::ErrorKind::Unsupported, "7-Zip is not available for this platform!");
+    let suffix = get_asset_suffix(env::consts::OS, env::consts::ARCH).ok_or_else(not_supported)?;
+    let name = get_executable_name(env::consts::OS).ok_or_else(not_supported)?;
+    let executable_path = get_managed_7z_path().ok_or_else(not_supported)?;
+
+    let release: SevenZipRelease = ureq::get(LATEST_7Z_RELEASE_URL)
+        .set("Accept", "application/vnd.github.v3+json")
+        .set("User-Agent", concat!("ImageCompressor/", env!("CARGO_PKG_VERSION")))
+        .call()?
+        .into_json()?;
+    let asset = release.assets.iter()
+        .find(|a| a.name.ends_with(suffix))
+        .ok_or_else(|| format!("The 7-Zip release {} has no file for this platform", release.tag_name))?;
+    let digest = asset.digest.as_ref()
+        .ok_or_else(|| format!("{} has no checksum to check the download against", asset.name))?;
+
+    let mut data = Vec::new();
+    ureq::get(&asset.browser_download_url)
+        .set("User-Agent", concat!("ImageCompressor/", env!("CARGO_PKG_VERSION")))
+        .call()?
+        .into_reader()
+        .read_to_end(&mut data)?;
+    verify_digest(&data, digest)?;
+    let executable = match suffix.ends_with(".tar.xz") {
+        true => extract_executable(data.as_slice(), name)?,
+        false => data,
+    };
+
+    // The executable is written next to its place first, so that a broken download never replaces a working one.
+    if let Some(p) = executable_path.parent() {
+        fs::create_dir_all(p)?;
+    }
+    let temp_path = executable_path.with_extension("download");
+    fs::write(&temp_path, executable)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(&temp_path, &executable_path)?;
+    Ok((executable_path, release.tag_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use tar::{Builder, Header};
+    use xz2::write::XzEncoder;
+    use super::*;
+
+    #[test]
+    fn asset_suffix_test(){
+        assert_eq!(get_asset_suffix("linux", "x86_64"), Some("-linux-x64.tar.xz"));
+        assert_eq!(get_asset_suffix("linux", "aarch64"), Some("-linux-arm64.tar.xz"));
+        assert_eq!(get_asset_suffix("macos", "aarch64"), Some("-mac.tar.xz"));
+        assert_eq!(get_asset_suffix("windows", "x86_64"), Some("7zr.exe"));
+        assert_eq!(get_asset_suffix("linux", "riscv64"), None);
+        assert_eq!(get_asset_suffix("freebsd", "x86_64"), None);
+        assert_eq!(get_executable_name("linux"), Some("7zzs"));
+    }
+
+    #[test]
+    fn verify_digest_test(){
+        let digest = "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(verify_digest(b"hello", digest).is_ok());
+        assert!(verify_digest(b"hello", &digest.to_uppercase().replace("SHA256", "sha256")).is_ok());
+        assert!(verify_digest(b"hello!", digest).is_err());
+        assert!(verify_digest(b"hello", "md5:5d41402abc4b2a76b9719d911017c592").is_err());
+        assert!(verify_digest(b"hello", "sha256:2cf24d").is_err());
+    }
+
+    #[test]
+    fn extract_executable_test(){
+        let mut builder = Builder::new(XzEncoder::new(Vec::new(), 6));
+        for (path, content) in [("readme.txt", "readme"), ("bin/7zzs", "executable")] {
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+        }
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+        assert_eq!(extract_executable(archive.as_slice(), "7zzs").unwrap(), b"executable");
+        assert!(extract_executable(archive.as_slice(), "7zz").is_err());
+        assert!(extract_executable(&b"not an archive"[..], "7zzs").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt;
+use egui::{Context, Key, Modifiers};
+
+/// Action of the window that can be run by a shortcut or from the command palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    OpenPalette,
+    Start,
+    Cancel,
+    SelectOrigin,
+    SelectDest,
+    SelectArchive,
+    OpenReport,
+    NewTab,
+    CloseTab,
+    OpenDashboard,
+}
+
+impl Command {
+    pub const ALL: [Command; 10] = [
+        Command::OpenPalette, Command::Start, Command::Cancel,
+        Command::SelectOrigin, Command::SelectDest, Command::SelectArchive,
+        Command::OpenReport, Command::NewTab, Command::CloseTab, Command::OpenDashboard,
+    ];
+
+    /// Name of the command in the shortcut settings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::OpenPalette => "palette",
+            Command::Start => "start",
+            Command::Cancel => "cancel",
+            Command::SelectOrigin => "select_origin",
+            Command::SelectDest => "select_dest",
+            Command::SelectArchive => "select_archive",
+            Command::OpenReport => "open_report",
+            Command::NewTab => "new_tab",
+            Command::CloseTab => "close_tab",
+            Command::OpenDashboard => "dashboard",
+        }
+    }
+
+    /// Description of the command in the command palette.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Command::OpenPalette => "Open the command palette",
+            Command::Start => "Start or schedule the job",
+            Command::Cancel => "Cancel the scheduled job",
+            Command::SelectOrigin => "Select the original folder",
+            Command::SelectDest => "Select the destination folder",
+            Command::SelectArchive => "Select the archive folder",
+            Command::OpenReport => "Open the latest report",
+            Command::NewTab => "Open a new tab",
+            Command::CloseTab => "Close the tab",
+            Command::OpenDashboard => "Show the savings dashboard",
+        }
+    }
+
+    fn default_binding(&self) -> Option<KeyBinding> {
+        let command = |key| Some(KeyBinding { modifiers: Modifiers::COMMAND, key });
+        match self {
+            Command::OpenPalette => command(Key::P),
+            Command::Start => command(Key::Enter),
+            Command::Cancel => Some(KeyBinding { modifiers: Modifiers::NONE, key: Key::Escape }),
+            Command::SelectOrigin => command(Key::O),
+            Command::SelectDest => command(Key::D),
+            Command::SelectArchive => command(Key::R),
+            Command::OpenReport | Command::OpenDashboard => None,
+            Command::NewTab => command(Key::T),
+            // Ctrl+W deletes the previous word in the text fields.
+            Command::CloseTab => Some(KeyBinding { modifiers: Modifiers { shift: true, ..Modifiers::COMMAND }, key: Key::W }),
+        }
+    }
+}
+
+/// Keys that can be bound, with their names in the shortcut settings.
+const KEYS: [(&str, Key); 51] = [
+    ("Down", Key::ArrowDown), ("Left", Key::ArrowLeft), ("Right", Key::ArrowRight), ("Up", Key::ArrowUp),
+    ("Escape", Key::Escape), ("Tab", Key::Tab), ("Backspace", Key::Backspace), ("Enter", Key::Enter), ("Space", Key::Space),
+    ("Insert", Key::Insert), ("Delete", Key::Delete), ("Home", Key::Home), ("End", Key::End), ("PageUp", Key::PageUp), ("PageDown", Key::PageDown),
+    ("0", Key::Num0), ("1", Key::Num1), ("2", Key::Num2), ("3", Key::Num3), ("4", Key::Num4),
+    ("5", Key::Num5), ("6", Key::Num6), ("7", Key::Num7), ("8", Key::Num8), ("9", Key::Num9),
+    ("A", Key::A), ("B", Key::B), ("C", Key::C), ("D", Key::D), ("E", Key::E), ("F", Key::F), ("G", Key::G),
+    ("H", Key::H), ("I", Key::I), ("J", Key::J), ("K", Key::K), ("L", Key::L), ("M", Key::M), ("N", Key::N),
+    ("O", Key::O), ("P", Key::P), ("Q", Key::Q), ("R", Key::R), ("S", Key::S), ("T", Key::T), ("U", Key::U),
+    ("V", Key::V), ("W", Key::W), ("X", Key::X), ("Y", Key::Y), ("Z", Key::Z),
+];
+
+fn parse_key(name: &str) -> Option<Key> {
+    KEYS.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, k)| *k)
+}
+
+fn get_key_name(key: Key) -> &'static str {
+    KEYS.iter().find(|(_, k)| *k == key).map(|(n, _)| *n).unwrap_or_default()
+}
+
+/// Combination of modifiers and a key, like `Ctrl+Shift+P`.
+///
+/// Ctrl is Cmd on macOS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyBinding {
+    modifiers: Modifiers,
+    key: Key,
+}
+
+impl KeyBinding {
+    /// Parse a binding like `Ctrl+Shift+P`, or `None` if it is not valid.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = Modifiers::NONE;
+        let mut parts = text.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parse_key(parts.pop()?)?;
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "cmd" => modifiers.command = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => return None,
+            }
+        }
+        Some(KeyBinding { modifiers, key })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{}", get_key_name(self.key))
+    }
+}
+
+/// Shortcuts of the commands, the default ones changed by the settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(Command, KeyBinding)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: Command::ALL.iter().filter_map(|c| Some((*c, c.default_binding()?))).collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Parse the settings of the shortcuts, lines like `open_report = Ctrl+Shift+R`, on top of the default ones.
+    /// `none` removes the shortcut of a command.
+    ///
+    /// # Error
+    /// - When a line has an unknown command or an invalid shortcut.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keymap = Keymap::default();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (name, binding) = line.split_once('=').ok_or_else(|| format!("\"{}\" is not like command = Ctrl+K", line))?;
+            let command = *Command::ALL.iter().find(|c| c.name() == name.trim())
+                .ok_or_else(|| format!("Unknown command \"{}\"", name.trim()))?;
+            keymap.bindings.retain(|(c, _)| *c != command);
+            if !binding.trim().eq_ignore_ascii_case("none") {
+                let binding = KeyBinding::parse(binding).ok_or_else(|| format!("Invalid shortcut \"{}\"", binding.trim()))?;
+                keymap.bindings.push((command, binding));
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Get the shortcut of the command as text, or an empty text if it has none.
+    pub fn get_text(&self, command: Command) -> String {
+        self.bindings.iter().find(|(c, _)| *c == command).map(|(_, b)| b.to_string()).unwrap_or_default()
+    }
+
+    /// Take the shortcuts from the input, so that no widget handles the same keys.
+    pub fn consume(&self, ctx: &Context) -> Shortcuts {
+        let mut input = ctx.input_mut();
+        let mut shortcuts = Shortcuts::default();
+        for (command, binding) in &self.bindings {
+            if input.consume_key(binding.modifiers, binding.key) {
+                shortcuts.set(*command);
+            }
+        }
+        shortcuts
+    }
+}
+
+/// Commands to run in this frame, by the shortcuts pressed or from the command palette.
+#[derive(Debug, Default)]
+pub struct Shortcuts {
+    pub open_palette: bool,
+    pub start: bool,
+    pub cancel: bool,
+    pub select_origin: bool,
+    pub select_dest: bool,
+    pub select_archive: bool,
+    pub open_report: bool,
+    pub new_tab: bool,
+    pub close_tab: bool,
+    pub open_dashboard: bool,
+}
+
+impl Shortcuts {
+    pub fn set(&mut self, command: Command) {
+        let field = match command {
+            Command::OpenPalette => &mut self.open_palette,
+            Command::Start => &mut self.start,
+            Command::Cancel => &mut self.cancel,
+            Command::SelectOrigin => &mut self.select_origin,
+            Command::SelectDest => &mut self.select_dest,
+            Command::SelectArchive => &mut self.select_archive,
+            Command::OpenReport => &mut self.open_report,
+            Command::NewTab => &mut self.new_tab,
+            Command::CloseTab => &mut self.close_tab,
+            Command::OpenDashboard => &mut self.open_dashboard,
+        };
+        *field = true;
+    }
+}
+
+/// Get the commands whose description or name contains every word of the query, for the command palette.
+pub fn filter_commands(query: &str) -> Vec<Command> {
+    let query = query.to_lowercase();
+    Command::ALL.iter()
+        .filter(|c| *c != &Command::OpenPalette)
+        .filter(|c| {
+            let text = format!("{} {}", c.description().to_lowercase(), c.name());
+            query.split_whitespace().all(|w| text.contains(w))
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_binding_test(){
+        let binding = KeyBinding::parse("ctrl + shift + p").unwrap();
+        assert_eq!(binding, KeyBinding { modifiers: Modifiers { shift: true, command: true, ..Modifiers::NONE }, key: Key::P });
+        assert_eq!(binding.to_string(), "Ctrl+Shift+P");
+        assert_eq!(KeyBinding::parse("Z").unwrap().to_string(), "Z");
+        assert_eq!(KeyBinding::parse("Alt+Enter").unwrap().to_string(), "Alt+Enter");
+        assert_eq!(KeyBinding::parse("Hyper+P"), None);
+        assert_eq!(KeyBinding::parse("Ctrl+"), None);
+    }
+
+    #[test]
+    fn keymap_test(){
+        let keymap = Keymap::parse("open_report = Ctrl+Shift+R\nstart = none\n\n").unwrap();
+        assert_eq!(keymap.get_text(Command::OpenReport), "Ctrl+Shift+R");
+        assert_eq!(keymap.get_text(Command::Start), "");
+        assert_eq!(keymap.get_text(Command::OpenPalette), "Ctrl+P");
+        assert_eq!(keymap.get_text(Command::CloseTab), "Ctrl+Shift+W");
+        assert_eq!(Keymap::parse("").unwrap(), Keymap::default());
+        assert!(Keymap::parse("launch = Ctrl+L").is_err());
+        assert!(Keymap::parse("start = Ctrl+F13").is_err());
+        assert!(Keymap::parse("start").is_err());
+    }
+
+    #[test]
+    fn filter_commands_test(){
+        assert_eq!(filter_commands("folder dest"), vec![Command::SelectDest]);
+        assert_eq!(filter_commands("REPORT"), vec![Command::OpenReport]);
+        assert_eq!(filter_commands("").len(), Command::ALL.len() - 1);
+        assert!(filter_commands("nothing like this").is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files that keep the metadata or edits of an image:
+/// `.xmp` of photo editors, `.json` of Google Takeout and `.aae` of Apple Photos.
+pub const SIDECAR_EXTENSIONS: [&str; 3] = ["xmp", "json", "aae"];
+
+/// Find the sidecar files of the images in the list, and return them by their image.
+///
+/// A sidecar is in the same directory as its image and named either `{stem}.{extension}`
+/// or `{file name}.{extension}`, like `a.xmp` or `a.png.json` for `a.png`.
+/// Sidecar files without an image are not returned.
+pub fn find_sidecars(file_list: &[PathBuf]) -> BTreeMap<PathBuf, Vec<PathBuf>> {
+    let images = file_list.iter()
+        .filter(|f| !is_sidecar(f))
+        .collect::<HashSet<_>>();
+    let images_by_stem = images.iter()
+        .map(|i| ((i.parent(), i.file_stem()), *i))
+        .collect::<HashMap<_, _>>();
+    let mut sidecars: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for sidecar in file_list.iter().filter(|f| is_sidecar(f)) {
+        let base = match (sidecar.parent(), sidecar.file_stem()) {
+            (Some(p), Some(s)) => p.join(s),
+            _ => continue,
+        };
+        let image = match images.get(&base) {
+            Some(i) => *i,
+            None => match images_by_stem.get(&(sidecar.parent(), base.file_name())) {
+                Some(i) => *i,
+                None => continue,
+            },
+        };
+        sidecars.entry(image.to_path_buf()).or_default().push(sidecar.to_path_buf());
+    }
+    sidecars
+}
+
+/// Get the name of the sidecar next to the output of its image.
+///
+/// `a.png.json` becomes `{output file name}.json`, and `a.xmp` becomes `{output stem}.xmp`.
+pub fn get_sidecar_name<S: AsRef<Path>, I: AsRef<Path>, O: AsRef<Path>>(sidecar: S, image: I, output: O) -> OsString {
+    let (sidecar, output) = (sidecar.as_ref(), output.as_ref());
+    let mut name = match sidecar.file_stem() == image.as_ref().file_name() {
+        true => output.file_name(),
+        false => output.file_stem(),
+    }.unwrap_or_default().to_os_string();
+    if let Some(e) = sidecar.extension() {
+        name.push(".");
+        name.push(e);
+    }
+    name
+}
+
+fn is_sidecar(file_path: &Path) -> bool {
+    match file_path.extension() {
+        Some(e) => SIDECAR_EXTENSIONS.iter().any(|s| e.eq_ignore_ascii_case(s)),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_sidecars_test(){
+        let origin = PathBuf::from("origin");
+        let file_list = vec![
+            origin.join("a.png"), origin.join("a.xmp"), origin.join("a.png.json"),
+            origin.join("sub").join("b.heic"), origin.join("sub").join("b.AAE"),
+            origin.join("c.xmp"), origin.join("sub").join("a.xmp"),
+        ];
+        let sidecars = find_sidecars(&file_list);
+        assert_eq!(sidecars.len(), 2);
+        assert_eq!(sidecars[&origin.join("a.png")], vec![origin.join("a.xmp"), origin.join("a.png.json")]);
+        assert_eq!(sidecars[&origin.join("sub").join("b.heic")], vec![origin.join("sub").join("b.AAE")]);
+    }
+
+    #[test]
+    fn sidecar_name_test(){
+        assert_eq!(get_sidecar_name("o/a.png.json", "o/a.png", "d/a (1).jpg"), OsString::from("a (1).jpg.json"));
+        assert_eq!(get_sidecar_name("o/a.xmp", "o/a.png", "d/a (1).jpg"), OsString::from("a (1).xmp"));
+    }
+}
@@ -0,0 +1,194 @@
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::thread;
+use tar::Builder;
+use xz2::write::XzEncoder;
+use zstd::stream::write::Encoder;
+use crate::archive_filter::ArchiveFilter;
+
+/// Largest window of long-range matching that zstd accepts on this platform, like `zstd --long=31`.
+pub const MAX_WINDOW_LOG: u32 = if cfg!(target_pointer_width = "64") { 31 } else { 30 };
+
+/// Window of long-range matching that `zstd --long` uses without a value.
+pub const DEFAULT_WINDOW_LOG: u32 = 27;
+
+/// Options of `.tar.zst` archives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZstdOptions {
+    /// Compression level from 1 to 22.
+    pub level: i32,
+    /// Window of long-range matching as a power of two, from 10 to [`MAX_WINDOW_LOG`]. 0 turns it off.
+    ///
+    /// Windows larger than [`DEFAULT_WINDOW_LOG`] need `zstd -d --long=31` or a similar option to extract.
+    pub window_log: u32,
+    /// Number of threads compressing each archive. 0 uses every core, like `zstd -T0`.
+    pub worker_count: u32,
+}
+
+impl Default for ZstdOptions {
+    fn default() -> Self {
+        ZstdOptions {
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+            window_log: 0,
+            worker_count: 0,
+        }
+    }
+}
+
+impl ZstdOptions {
+    /// Make the zstd encoder of the writer with the options.
+    fn get_encoder<'a, W: Write>(&self, writer: W) -> io::Result<Encoder<'a, W>> {
+        let mut encoder = Encoder::new(writer, self.level.clamp(1, 22))?;
+        if self.window_log > 0 {
+            encoder.long_distance_matching(true)?;
+            encoder.window_log(self.window_log.clamp(10, MAX_WINDOW_LOG))?;
+        }
+        let worker_count = match self.worker_count {
+            0 => thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1),
+            n => n,
+        };
+        if worker_count > 1 {
+            encoder.multithread(worker_count)?;
+        }
+        Ok(encoder)
+    }
+}
+
+/// Archive the files of the directory that the filter does not exclude into an uncompressed tar archive,
+/// under the root path in the archive.
+///
+/// Compressed images barely shrink any further, so this is much faster than the compressed formats,
+/// for backups that deduplicate or compress the files by themselves.
+///
+/// # Error
+/// - When the archive already exists.
+/// - When the files cannot be read or the archive cannot be written.
+pub fn archive_tar<D: AsRef<Path>, A: AsRef<Path>>(dir: D, archive_path: A, root: &Path, filter: &ArchiveFilter) -> Result<(), Box<dyn Error>> {
+    let writer = BufWriter::new(create_archive_file(archive_path.as_ref())?);
+    write_tar(dir.as_ref(), writer, root, filter)?.flush()?;
+    Ok(())
+}
+
+/// Archive the files of the directory that the filter does not exclude into a tar archive compressed with zstd and the options,
+/// under the root path in the archive.
+///
+/// # Error
+/// - When the archive already exists.
+/// - When the files cannot be read or the archive cannot be written.
+pub fn archive_tar_zstd<D: AsRef<Path>, A: AsRef<Path>>(dir: D, archive_path: A, options: &ZstdOptions, root: &Path, filter: &ArchiveFilter) -> Result<(), Box<dyn Error>> {
+    let encoder = options.get_encoder(BufWriter::new(create_archive_file(archive_path.as_ref())?))?;
+    write_tar(dir.as_ref(), encoder, root, filter)?.finish()?.flush()?;
+    Ok(())
+}
+
+/// Archive the files of the directory that the filter does not exclude into a tar archive compressed with xz,
+/// under the root path in the archive, at the same level as `zip_archive`, which cannot do either.
+///
+/// # Error
+/// - When the archive already exists.
+/// - When the files cannot be read or the archive cannot be written.
+pub fn archive_tar_xz<D: AsRef<Path>, A: AsRef<Path>>(dir: D, archive_path: A, root: &Path, filter: &ArchiveFilter) -> Result<(), Box<dyn Error>> {
+    let encoder = XzEncoder::new(BufWriter::new(create_archive_file(archive_path.as_ref())?), 9);
+    write_tar(dir.as_ref(), encoder, root, filter)?.finish()?.flush()?;
+    Ok(())
+}
+
+fn create_archive_file(archive_path: &Path) -> io::Result<File> {
+    if archive_path.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "The tar archive file already exists!"));
+    }
+    File::create(archive_path)
+}
+
+/// Write the directory as a tar archive under the root path into the writer, and return the writer.
+fn write_tar<W: Write>(dir: &Path, writer: W, root: &Path, filter: &ArchiveFilter) -> io::Result<W> {
+    let mut builder = Builder::new(writer);
+    builder.follow_symlinks(!filter.is_symlink_kept());
+    if filter.is_empty() && !root.as_os_str().is_empty() {
+        builder.append_dir_all(root, dir)?;
+    } else {
+        for file in filter.get_file_list(dir)? {
+            if let Ok(p) = file.strip_prefix(dir) {
+                builder.append_path_with_name(&file, root.join(p))?;
+            }
+        }
+    }
+    builder.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use tar::Archive;
+    use xz2::read::XzDecoder;
+    use zstd::stream::read::Decoder;
+    use super::*;
+
+    fn make_album(test_dir: &Path) -> PathBuf {
+        if test_dir.is_dir() {
+            fs::remove_dir_all(test_dir).unwrap();
+        }
+        let dir = test_dir.join("album");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("a.jpg"), "image").unwrap();
+        dir
+    }
+
+    fn read_member<R: Read>(reader: R, member: &str) -> String {
+        let mut archive = Archive::new(reader);
+        let mut entry = archive.entries().unwrap()
+            .map(|e| e.unwrap())
+            .find(|e| e.path().unwrap() == Path::new(member))
+            .unwrap();
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn archive_tar_test(){
+        let test_dir = PathBuf::from("test_archive_tar");
+        let dir = make_album(&test_dir);
+        let archive_path = test_dir.join("album.tar");
+        archive_tar(&dir, &archive_path, Path::new("album"), &ArchiveFilter::default()).unwrap();
+        assert!(archive_tar(&dir, &archive_path, Path::new("album"), &ArchiveFilter::default()).is_err());
+        assert_eq!(read_member(File::open(&archive_path).unwrap(), "album/sub/a.jpg"), "image");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn archive_tar_zstd_test(){
+        let test_dir = PathBuf::from("test_archive_tar_zstd");
+        let dir = make_album(&test_dir);
+        let options = ZstdOptions { level: 19, window_log: MAX_WINDOW_LOG, worker_count: 2 };
+        let archive_path = test_dir.join("album.tar.zst");
+        archive_tar_zstd(&dir, &archive_path, &options, Path::new("album"), &ArchiveFilter::default()).unwrap();
+        assert!(archive_tar_zstd(&dir, &archive_path, &options, Path::new("album"), &ArchiveFilter::default()).is_err());
+
+        let mut decoder = Decoder::new(File::open(&archive_path).unwrap()).unwrap();
+        decoder.window_log_max(MAX_WINDOW_LOG).unwrap();
+        assert_eq!(read_member(decoder, "album/sub/a.jpg"), "image");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn archive_tar_xz_test(){
+        let test_dir = PathBuf::from("test_archive_tar_xz");
+        let dir = make_album(&test_dir);
+        fs::write(dir.join("sub").join("a.raw"), "master").unwrap();
+        let archive_path = test_dir.join("album.tar.xz");
+        archive_tar_xz(&dir, &archive_path, Path::new(""), &ArchiveFilter::parse("raw")).unwrap();
+
+        let mut archive = Archive::new(XzDecoder::new(File::open(&archive_path).unwrap()));
+        let members = archive.entries().unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(members, vec![PathBuf::from("sub/a.jpg")]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use egui::{ColorImage, Context, TextureHandle};
+use image::ImageFormat;
+use image_compressor::crawler::get_file_list;
+use log::warn;
+
+/// Size of the longer side of the thumbnails in pixels.
+pub const THUMBNAIL_SIZE: u32 = 64;
+
+/// Maximum number of thumbnails loaded from a folder.
+pub const MAX_THUMBNAIL_COUNT: usize = 300;
+
+enum ThumbnailMessage {
+    ImageCount(usize),
+    Thumbnail(PathBuf, ColorImage),
+}
+
+/// Thumbnails of the images in a folder, loaded on a background thread.
+///
+/// The loading thread stops when the loader is dropped.
+pub struct ThumbnailLoader {
+    dir: PathBuf,
+    receiver: Receiver<ThumbnailMessage>,
+    to_stop: Arc<AtomicBool>,
+    image_count: Option<usize>,
+    is_loading: bool,
+    thumbnails: Vec<(PathBuf, TextureHandle)>,
+}
+
+impl ThumbnailLoader {
+    /// Start loading the thumbnails of the first [`MAX_THUMBNAIL_COUNT`] images in the directory, sorted by path.
+    pub fn new<D: AsRef<Path>>(dir: D) -> Self {
+        let (tx, receiver) = mpsc::channel();
+        let to_stop = Arc::new(AtomicBool::new(false));
+        let thread_dir = dir.as_ref().to_path_buf();
+        let thread_to_stop = Arc::clone(&to_stop);
+        thread::spawn(move || {
+            let image_list = match get_image_list(&thread_dir) {
+                Ok(l) => l,
+                Err(e) => {
+                    warn!("Cannot read the folder to preview: {}", e);
+                    Vec::new()
+                }
+            };
+            if tx.send(ThumbnailMessage::ImageCount(image_list.len())).is_err() {
+                return;
+            }
+            for path in image_list.into_iter().take(MAX_THUMBNAIL_COUNT) {
+                if thread_to_stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let thumbnail = match image::open(&path) {
+                    Ok(i) => i.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgba8(),
+                    Err(_) => continue,
+                };
+                let size = [thumbnail.width() as usize, thumbnail.height() as usize];
+                let image = ColorImage::from_rgba_unmultiplied(size, thumbnail.as_raw());
+                if tx.send(ThumbnailMessage::Thumbnail(path, image)).is_err() {
+                    return;
+                }
+            }
+        });
+        ThumbnailLoader {
+            dir: dir.as_ref().to_path_buf(),
+            receiver,
+            to_stop,
+            image_count: None,
+            is_loading: true,
+            thumbnails: Vec::new(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Number of images in the directory, once the directory is read.
+    pub fn image_count(&self) -> Option<usize> {
+        self.image_count
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.is_loading
+    }
+
+    pub fn thumbnails(&self) -> &[(PathBuf, TextureHandle)] {
+        &self.thumbnails
+    }
+
+    /// Upload the thumbnails loaded since the last call as textures.
+    pub fn receive(&mut self, ctx: &Context) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(ThumbnailMessage::ImageCount(n)) => self.image_count = Some(n),
+                Ok(ThumbnailMessage::Thumbnail(path, image)) => {
+                    let texture = ctx.load_texture(path.to_string_lossy(), image);
+                    self.thumbnails.push((path, texture));
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.is_loading = false;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ThumbnailLoader {
+    fn drop(&mut self) {
+        self.to_stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Get the files in the directory that have an image extension, sorted by path.
+fn get_image_list<D: AsRef<Path>>(dir: D) -> io::Result<Vec<PathBuf>> {
+    let mut image_list = get_file_list(dir)?
+        .into_iter()
+        .filter(|p| ImageFormat::from_path(p).is_ok())
+        .collect::<Vec<_>>();
+    image_list.sort();
+    Ok(image_list)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn image_list_test(){
+        let test_dir = PathBuf::from("test_image_list");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        fs::create_dir_all(test_dir.join("sub")).unwrap();
+        for name in ["b.png", "a.JPG", "note.txt", "sub/c.webp"] {
+            fs::write(test_dir.join(name), "").unwrap();
+        }
+
+        assert_eq!(get_image_list(&test_dir).unwrap(), vec![test_dir.join("a.JPG"), test_dir.join("b.png"), test_dir.join("sub/c.webp")]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
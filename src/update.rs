@@ -0,0 +1,57 @@
+use std::error::Error;
+use serde::Deserialize;
+
+/// GitHub API of the latest release of this program.
+pub const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/altair823/ImageCompressor2/releases/latest";
+
+/// Release published on GitHub.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    pub body: Option<String>,
+}
+
+/// Get the latest release from GitHub.
+///
+/// # Error
+/// - When the request fails, for example without a network connection.
+/// - When the response is not a release.
+pub fn fetch_latest_release() -> Result<Release, Box<dyn Error>> {
+    let release = ureq::get(LATEST_RELEASE_URL)
+        .set("Accept", "application/vnd.github.v3+json")
+        .set("User-Agent", concat!("ImageCompressor/", env!("CARGO_PKG_VERSION")))
+        .call()?
+        .into_json()?;
+    Ok(release)
+}
+
+/// Check whether the version of the tag, like `v1.2.0`, is newer than the current version.
+/// Tags that are not versions are never newer.
+pub fn is_newer(tag_name: &str, current_version: &str) -> bool {
+    match (parse_version(tag_name), parse_version(current_version)) {
+        (Some(tag), Some(current)) => tag > current,
+        _ => false,
+    }
+}
+
+fn parse_version(version: &str) -> Option<Vec<u32>> {
+    version.trim_start_matches('v')
+        .split('.')
+        .map(|n| n.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_newer_test(){
+        assert!(is_newer("v1.3.0", "1.2.9"));
+        assert!(is_newer("2.0", "1.9.9"));
+        assert!(!is_newer("v1.2.0", "1.2.0"));
+        assert!(!is_newer("v1.1.10", "1.2.0"));
+        assert!(!is_newer("nightly", "1.2.0"));
+    }
+}
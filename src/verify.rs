@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::Command;
+use log::warn;
+use sha2::{Digest, Sha256};
+use tar::Archive;
+use xz2::read::XzDecoder;
+use zip::ZipArchive;
+use zstd::stream::read::Decoder as ZstdDecoder;
+use zip_archive::Format;
+use crate::archive_filter::ArchiveFilter;
+use crate::pipeline::ArchiveOutput;
+use crate::seven_zip::get_7z_executable_path;
+use crate::tar_writer::MAX_WINDOW_LOG;
+
+/// Extract the archive into a temporary directory and compare every file under the root path in the archive
+/// with the archived directory by its SHA-256 hash.
+///
+/// Returns a message for every file that is missing, unexpected or different in the archive.
+/// Files that the filter excludes are not expected in the archive.
+/// The list is empty when the archive matches the directory.
+///
+/// # Error
+/// - When the archive cannot be extracted.
+/// - When the files cannot be read.
+pub fn verify_archive<A: AsRef<Path>, D: AsRef<Path>>(archive_path: A, dir: D, output: &ArchiveOutput, root: &Path, filter: &ArchiveFilter) -> Result<Vec<String>, Box<dyn Error>> {
+    let archive_path = archive_path.as_ref();
+    let mut temp_name = format!("image_compressor_verify_{}_", process::id());
+    temp_name.push_str(&archive_path.file_name().unwrap_or_default().to_string_lossy());
+    let temp_dir = env::temp_dir().join(temp_name);
+    if temp_dir.exists() {
+        fs::remove_dir_all(&temp_dir)?;
+    }
+    fs::create_dir_all(&temp_dir)?;
+
+    let result = extract(archive_path, &temp_dir, output)
+        .and_then(|_| compare_dirs(dir.as_ref(), &temp_dir.join(root), filter).map_err(|e| e.into()));
+    if let Err(e) = fs::remove_dir_all(&temp_dir) {
+        warn!("Cannot remove the verification folder {}: {}", temp_dir.display(), e);
+    }
+    result
+}
+
+/// Extract the archive into the directory.
+///
+/// # Error
+/// - When the output is a PDF, which has no files to extract.
+fn extract(archive_path: &Path, temp_dir: &Path, output: &ArchiveOutput) -> Result<(), Box<dyn Error>> {
+    let format = match output {
+        ArchiveOutput::Archive(f) => f,
+        ArchiveOutput::Tar => {
+            Archive::new(File::open(archive_path)?).unpack(temp_dir)?;
+            return Ok(());
+        }
+        ArchiveOutput::TarZstd => {
+            let mut decoder = ZstdDecoder::new(File::open(archive_path)?)?;
+            decoder.window_log_max(MAX_WINDOW_LOG)?;
+            Archive::new(decoder).unpack(temp_dir)?;
+            return Ok(());
+        }
+        ArchiveOutput::Pdf => return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "A PDF file cannot be verified!"))),
+    };
+    match format {
+        Format::Zip => ZipArchive::new(File::open(archive_path)?)?.extract(temp_dir)?,
+        Format::Xz => Archive::new(XzDecoder::new(File::open(archive_path)?)).unpack(temp_dir)?,
+        Format::_7z => {
+            let mut output_arg = String::from("-o");
+            output_arg.push_str(&temp_dir.to_string_lossy());
+            let status = Command::new(get_7z_executable_path()?)
+                .arg("x")
+                .arg("-y")
+                .arg(output_arg)
+                .arg(archive_path)
+                .status()?;
+            if !status.success() {
+                return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("7z cannot extract the archive: {}", status))));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compare the files of the expected directory and the extracted directory.
+fn compare_dirs(expected_dir: &Path, extracted_dir: &Path, filter: &ArchiveFilter) -> io::Result<Vec<String>> {
+    let expected = hash_files(expected_dir, filter)?;
+    let mut extracted = match extracted_dir.is_dir() {
+        true => hash_files(extracted_dir, filter)?,
+        false => BTreeMap::new(),
+    };
+
+    let mut mismatches = Vec::new();
+    for (path, hash) in expected {
+        match extracted.remove(&path) {
+            Some(h) if h == hash => {}
+            Some(_) => mismatches.push(format!("Different in the archive: {}", path.display())),
+            None => mismatches.push(format!("Missing in the archive: {}", path.display())),
+        }
+    }
+    for path in extracted.keys() {
+        mismatches.push(format!("Unexpected in the archive: {}", path.display()));
+    }
+    Ok(mismatches)
+}
+
+/// Write the SHA-256 hash of every file in the directory to a `SHA256SUMS` file beside its archive,
+/// so the archive can be checked without the directory. Returns the path of the file.
+///
+/// The lines are in the format of `sha256sum`, with the paths of the files under the root path in the archive,
+/// so `sha256sum -c` checks the files extracted next to it. Links kept by the filter are not listed.
+///
+/// # Error
+/// - When the files cannot be read or the checksum file cannot be written.
+pub fn write_checksums<D: AsRef<Path>, A: AsRef<Path>>(dir: D, archive_path: A, root: &Path, filter: &ArchiveFilter) -> io::Result<PathBuf> {
+    let dir = dir.as_ref();
+    let mut lines = String::new();
+    for (path, hash) in hash_files(dir, filter)?.into_iter().filter(|(p, _)| !dir.join(p).is_symlink()) {
+        let hex = hash.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        let member = root.join(path).to_string_lossy().replace('\\', "/");
+        lines.push_str(&format!("{}  {}\n", hex, member));
+    }
+    let checksums_path = get_checksums_path(archive_path);
+    fs::write(&checksums_path, lines)?;
+    Ok(checksums_path)
+}
+
+/// Get the path of the checksum file of the archive, like `album.zip.SHA256SUMS`.
+pub fn get_checksums_path<A: AsRef<Path>>(archive_path: A) -> PathBuf {
+    let mut path = archive_path.as_ref().as_os_str().to_os_string();
+    path.push(".SHA256SUMS");
+    PathBuf::from(path)
+}
+
+/// Get the SHA-256 hash of every file in the directory that goes into its archive, by the path relative to the directory.
+///
+/// Links kept by the filter are hashed by their target path,
+/// the same as the files holding the target path that some tools extract from zip links.
+pub fn hash_files(dir: &Path, filter: &ArchiveFilter) -> io::Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut hashes = BTreeMap::new();
+    for file in filter.get_file_list(dir)? {
+        let content = match filter.is_symlink_kept() && file.is_symlink() {
+            true => fs::read_link(&file)?.to_string_lossy().replace('\\', "/").into_bytes(),
+            false => fs::read(&file)?,
+        };
+        let hash = Sha256::digest(content).to_vec();
+        if let Ok(p) = file.strip_prefix(dir) {
+            hashes.insert(p.to_path_buf(), hash);
+        }
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use zip_archive::Archiver;
+    use super::*;
+
+    #[test]
+    fn verify_archive_test(){
+        let test_dir = PathBuf::from("test_verify_archive");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let album = test_dir.join("album");
+        fs::create_dir_all(album.join("sub")).unwrap();
+        fs::write(album.join("1.jpg"), "first image").unwrap();
+        fs::write(album.join("sub").join("2.jpg"), "second image").unwrap();
+
+        let mut archiver = Archiver::new();
+        archiver.set_destination(test_dir.join("archive"));
+        archiver.push(&album);
+        archiver.archive().unwrap();
+        let archive_path = test_dir.join("archive").join("album.zip");
+
+        assert!(verify_archive(&archive_path, &album, &ArchiveOutput::Archive(Format::Zip), Path::new("album"), &ArchiveFilter::default()).unwrap().is_empty());
+        fs::write(album.join("1.jpg"), "changed image").unwrap();
+        fs::write(album.join("3.jpg"), "third image").unwrap();
+        let mismatches = verify_archive(&archive_path, &album, &ArchiveOutput::Archive(Format::Zip), Path::new("album"), &ArchiveFilter::default()).unwrap();
+        assert_eq!(mismatches, vec![
+            format!("Different in the archive: {}", Path::new("1.jpg").display()),
+            format!("Missing in the archive: {}", Path::new("3.jpg").display()),
+        ]);
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn write_checksums_test(){
+        let test_dir = PathBuf::from("test_write_checksums");
+        if test_dir.is_dir() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let album = test_dir.join("album");
+        fs::create_dir_all(album.join("sub")).unwrap();
+        fs::write(album.join("sub").join("a.jpg"), "abc").unwrap();
+        fs::write(album.join("sub").join("a.raw"), "master").unwrap();
+        let checksums_path = write_checksums(&album, test_dir.join("album.zip"), Path::new("album"), &ArchiveFilter::parse("raw")).unwrap();
+        assert_eq!(checksums_path, test_dir.join("album.zip.SHA256SUMS"));
+        assert_eq!(fs::read_to_string(&checksums_path).unwrap(),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  album/sub/a.jpg\n");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crate::remote::{encode_path, to_remote_error, RemoteTarget};
+use crate::zip_writer::get_member_name;
+
+/// Environment variable of the password of the WebDAV server, so that it is never saved in the settings.
+pub const WEBDAV_PASSWORD_VAR: &str = "WEBDAV_PASSWORD";
+
+/// Folder on a WebDAV server, like a NAS or Nextcloud, that the outputs are uploaded to after a job.
+///
+/// It is not a destination by itself: the outputs are written to the local destination first, then uploaded.
+#[derive(Debug)]
+pub struct WebDavTarget {
+    url: String,
+    username: String,
+    password: String,
+    /// Folders known to exist on the server, so that each is created only once.
+    created_dirs: Mutex<HashSet<String>>,
+}
+
+impl WebDavTarget {
+    /// Create a [`WebDavTarget`] of the folder at the URL, like `https://nas.local/dav/photos`,
+    /// with the password in [`WEBDAV_PASSWORD_VAR`].
+    ///
+    /// # Error
+    /// - When the URL is not an HTTP or HTTPS URL.
+    /// - When a username is given with an HTTP URL, since Basic authentication would send the password in plain text.
+    pub fn from_env(url: &str, username: &str) -> Result<Self, Box<dyn Error>> {
+        let url = url.trim().trim_end_matches('/');
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "The WebDAV URL must start with http:// or https://!")));
+        }
+        let username = username.trim();
+        if url.starts_with("http://") && !username.is_empty() {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "The password would be sent unencrypted over http://. Use an https:// URL to sign in!")));
+        }
+        Ok(WebDavTarget {
+            url: url.to_string(),
+            username: username.to_string(),
+            password: env::var(WEBDAV_PASSWORD_VAR).unwrap_or_default(),
+            created_dirs: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Get the URL of the path relative to the folder of the target.
+    fn get_path_url(&self, relative_path: &str) -> String {
+        format!("{}/{}", self.url, encode_path(relative_path))
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = ureq::request(method, url);
+        match self.username.is_empty() {
+            true => request,
+            false => request.set("Authorization", &format!("Basic {}", STANDARD.encode(format!("{}:{}", self.username, self.password)))),
+        }
+    }
+
+    /// Create the folders of the path on the server one by one, since WebDAV creates only one level at a time.
+    fn create_dirs(&self, relative_dir: &str) -> Result<(), Box<dyn Error>> {
+        let mut dir = String::new();
+        for name in relative_dir.split('/').filter(|n| !n.is_empty()) {
+            if !dir.is_empty() {
+                dir.push('/');
+            }
+            dir.push_str(name);
+            if self.created_dirs.lock().map(|d| d.contains(&dir)).unwrap_or(false) {
+                continue;
+            }
+            match self.request("MKCOL", &self.get_path_url(&dir)).call() {
+                // 405 Method Not Allowed means that the folder already exists.
+                Ok(_) | Err(ureq::Error::Status(405, _)) => {}
+                Err(e) => return Err(to_remote_error(e)),
+            }
+            if let Ok(mut created_dirs) = self.created_dirs.lock() {
+                created_dirs.insert(dir.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RemoteTarget for WebDavTarget {
+    /// Create the folders of the file on the server and stream the file with a PUT request.
+    fn upload(&self, file: &Path, relative_path: &Path) -> Result<(), Box<dyn Error>> {
+        let name = get_member_name(relative_path);
+        if name.is_empty() {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "The file has no name on the server!")));
+        }
+        if let Some((dir, _)) = name.rsplit_once('/') {
+            self.create_dirs(dir)?;
+        }
+        let file = File::open(file)?;
+        let size = file.metadata()?.len();
+        self.request("PUT", &self.get_path_url(&name))
+            .set("Content-Length", &size.to_string())
+            .send(file)
+            .map_err(to_remote_error)?;
+        Ok(())
+    }
+
+    fn get_url(&self, relative_path: &Path) -> String {
+        self.get_path_url(&get_member_name(relative_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webdav_target_test(){
+        assert!(WebDavTarget::from_env("nas.local/dav", "").is_err());
+        assert!(WebDavTarget::from_env("http://nas.local/dav", "me").is_err());
+        assert!(WebDavTarget::from_env("http://nas.local/dav", " ").is_ok());
+
+        let target = WebDavTarget::from_env("https://nas.local/dav/photos/", "me").unwrap();
+        assert_eq!(target.get_url(&Path::new("2021").join("a b.jpg")), "https://nas.local/dav/photos/2021/a%20b.jpg");
+    }
+}
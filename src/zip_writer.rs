@@ -0,0 +1,153 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::{Component, Path};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use crate::archive_filter::ArchiveFilter;
+
+/// Size from which a file is written with the Zip64 fields, the largest size the original zip fields can hold.
+pub const ZIP64_SIZE: u64 = u32::MAX as u64;
+
+/// Archive the files of the directory that the filter does not exclude into the zip archive, under the root path in the archive.
+/// `zip_archive` puts them under the name of the directory.
+///
+/// Unlike `zip_archive`, which reads each file into memory and cannot write files of 4 GB or larger,
+/// the files are streamed into the archive and written with the Zip64 fields when they are large.
+/// The `zip` crate adds the Zip64 end of the archive by itself when it is larger than 4 GB or has more than 65535 files.
+/// Member names are separated with `/` on every platform, as the zip format requires.
+/// Links kept by the filter are stored as zip links, which some tools extract as files holding the target path.
+///
+/// # Error
+/// - When the archive already exists.
+/// - When the files cannot be read or the archive cannot be written.
+pub fn archive_zip<D: AsRef<Path>, A: AsRef<Path>>(dir: D, archive_path: A, root: &Path, filter: &ArchiveFilter) -> Result<(), Box<dyn Error>> {
+    let (dir, archive_path) = (dir.as_ref(), archive_path.as_ref());
+    if archive_path.exists() {
+        return Err(Box::new(io::Error::new(io::ErrorKind::AlreadyExists, "The zip archive file already exists!")));
+    }
+    let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(archive_path)?));
+    for file in filter.get_file_list(dir)? {
+        let name = get_member_name(&root.join(file.strip_prefix(dir)?));
+        if filter.is_symlink_kept() && file.is_symlink() {
+            let target = fs::read_link(&file)?.to_string_lossy().replace('\\', "/");
+            zip_writer.add_symlink(name, target, FileOptions::default())?;
+            continue;
+        }
+        zip_writer.start_file(name, get_options(file.metadata()?.len()))?;
+        io::copy(&mut File::open(&file)?, &mut zip_writer)?;
+    }
+    zip_writer.finish()?;
+    Ok(())
+}
+
+/// Options of a file of the size. Deflated files of [`ZIP64_SIZE`] or larger need the Zip64 fields,
+/// because their compressed size can be larger than the file.
+fn get_options(size: u64) -> FileOptions {
+    FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(size >= ZIP64_SIZE)
+}
+
+/// Get the name of the file in the archive from its path in the archive.
+pub(crate) fn get_member_name(relative_path: &Path) -> String {
+    relative_path.components()
+        .filter_map(|c| match c {
+            Component::Normal(n) => Some(n.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use zip::ZipArchive;
+    use super::*;
+
+    fn reset_dir(test_dir: &Path) {
+        if test_dir.is_dir() {
+            fs::remove_dir_all(test_dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn member_name_test(){
+        assert_eq!(get_member_name(&PathBuf::from("album").join("sub").join("a.jpg")), "album/sub/a.jpg");
+    }
+
+    #[test]
+    fn zip_test(){
+        let test_dir = PathBuf::from("test_zip");
+        reset_dir(&test_dir);
+        let dir = test_dir.join("album");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.jpg"), "a").unwrap();
+        fs::write(dir.join("sub").join("b.jpg"), "b").unwrap();
+        let archive_path = test_dir.join("album.zip");
+        archive_zip(&dir, &archive_path, Path::new("album"), &ArchiveFilter::default()).unwrap();
+        assert!(archive_zip(&dir, &archive_path, Path::new("album"), &ArchiveFilter::default()).is_err());
+
+        let mut archive = ZipArchive::new(File::open(&archive_path).unwrap()).unwrap();
+        let mut names = archive.file_names().map(str::to_string).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["album/a.jpg", "album/sub/b.jpg"]);
+        let mut content = String::new();
+        io::Read::read_to_string(&mut archive.by_name("album/sub/b.jpg").unwrap(), &mut content).unwrap();
+        assert_eq!(content, "b");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    /// Writes more than 65535 files for the Zip64 end of the archive, which takes long,
+    /// so it is run only with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn zip_many_files_test(){
+        let test_dir = PathBuf::from("test_zip_many_files");
+        reset_dir(&test_dir);
+        let dir = test_dir.join("album");
+        let file_count = u16::MAX as usize + 10;
+        for i in 0..file_count {
+            let sub_dir = dir.join(format!("{}", i / 1000));
+            if i % 1000 == 0 {
+                fs::create_dir_all(&sub_dir).unwrap();
+            }
+            fs::write(sub_dir.join(format!("{}.jpg", i)), i.to_string()).unwrap();
+        }
+        let archive_path = test_dir.join("album.zip");
+        archive_zip(&dir, &archive_path, Path::new("album"), &ArchiveFilter::default()).unwrap();
+        assert!(archive_zip(&dir, &archive_path, Path::new("album"), &ArchiveFilter::default()).is_err());
+
+        let mut archive = ZipArchive::new(File::open(&archive_path).unwrap()).unwrap();
+        assert_eq!(archive.len(), file_count);
+        let mut content = String::new();
+        io::Read::read_to_string(&mut archive.by_name("album/65/65540.jpg").unwrap(), &mut content).unwrap();
+        assert_eq!(content, "65540");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    /// Writes and reads a file over 4 GB, so it is run only with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn zip_large_file_test(){
+        let test_dir = PathBuf::from("test_zip_large_file");
+        reset_dir(&test_dir);
+        let dir = test_dir.join("album");
+        fs::create_dir_all(&dir).unwrap();
+        let size = ZIP64_SIZE + 1024 * 1024;
+        File::create(dir.join("large.tif")).unwrap().set_len(size).unwrap();
+        fs::write(dir.join("small.jpg"), "small").unwrap();
+        let archive_path = test_dir.join("album.zip");
+        archive_zip(&dir, &archive_path, Path::new("album"), &ArchiveFilter::default()).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&archive_path).unwrap()).unwrap();
+        assert_eq!(archive.by_name("album/large.tif").unwrap().size(), size);
+        let mut content = String::new();
+        io::Read::read_to_string(&mut archive.by_name("album/small.jpg").unwrap(), &mut content).unwrap();
+        assert_eq!(content, "small");
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}